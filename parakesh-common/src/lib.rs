@@ -1,6 +1,15 @@
 pub mod pk_app;
 pub mod pk_app_async;
+pub mod pk_config;
+pub mod pk_error;
+pub mod pk_history;
+pub mod pk_input;
+pub mod pk_multipart;
+#[cfg(test)]
+mod pk_test_mint;
 
 // Reexports
-pub use crate::pk_app::{MintsSummary, PKApp};
+pub use crate::pk_app::{MintsSummary, PKApp, SEED_VERIFY_WORD_COUNT};
 pub use crate::pk_app_async::{AppEvent, PKAppAsync};
+pub use crate::pk_config::PKAppConfig;
+pub use crate::pk_error::PkError;
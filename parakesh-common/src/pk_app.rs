@@ -1,28 +1,55 @@
 use cdk::amount::SplitTarget;
+use cdk::lightning_invoice::Bolt11InvoiceDescription;
 use cdk::mint_url::MintUrl;
 use cdk::nuts::nut00::ProofsMethods;
-use cdk::nuts::{CurrencyUnit, MintQuoteState};
+use cdk::nuts::{
+    CurrencyUnit, MeltOptions, MeltQuoteState, MintQuoteState, PaymentMethod, PaymentRequest,
+    Proofs, SpendingConditions, State, Token,
+};
+use cdk::util::unix_time;
 use cdk::wallet::multi_mint_wallet::MultiMintWallet;
 use cdk::wallet::types::WalletKey;
-use cdk::wallet::{SendOptions, Wallet, WalletBuilder};
-use cdk::Amount;
+use cdk::wallet::{PreparedSend, SendOptions, Wallet, WalletBuilder};
+use cdk::{Amount, Bolt11Invoice};
+use cdk_common::bitcoin::bip32::Xpriv;
+use cdk_common::bitcoin::Network;
 use cdk_common::database::WalletDatabase;
+use cdk_common::secret::Secret;
 use cdk_redb::WalletRedbDatabase;
 // use cdk_sqlite::wallet::memory;
 // use cdk_sqlite::WalletSqliteDatabase;
 
+use bip39::Mnemonic;
+use rand::RngCore;
 use seedstore::{ChildSpecifier, SeedStore, SeedStoreCreator};
+use serde::Serialize;
 
-use std::collections::BTreeMap;
+use crate::pk_config::{MintSelection, PKAppConfig, TokenFormat};
+use crate::pk_error::PkError;
+use crate::pk_history::{
+    HistoryFilter, Transaction, TransactionHistory, TxDirection, TxKind, TxStatus,
+};
+use crate::pk_input::{classify_input, InputKind};
+
+use std::collections::{BTreeMap, HashMap};
 use std::fmt;
 use std::str::FromStr;
 use std::sync::Arc;
 use std::time::Duration;
 
 const KEY_DERIVATION_PATH: &str = "m/84'/0'/0'/0/0";
+/// Derivation path used for the seed check public key (different from the one used for ecash)
+const SEED_CHECK_DERIVATION_PATH: &str = "m/84'/0'/0'/1/0";
+/// Maximum number of secrets derived per keyset, when verifying the seed against the ecash
+const SEED_VERIFY_MAX_COUNTER: u32 = 10_000;
+/// Entropy length for newly generated seeds, 16 bytes (12 BIP39 words)
+const NEW_SEED_ENTROPY_LEN: usize = 16;
+/// Default number of words asked in the seed backup verification
+pub const SEED_VERIFY_WORD_COUNT: usize = 3;
 
 /// Parakesh application, based on CDK.
 pub struct PKApp {
+    config: PKAppConfig,
     /// Stores the seed
     seedstore: SeedStore,
     unit: CurrencyUnit,
@@ -32,10 +59,15 @@ pub struct PKApp {
     multi_mint_wallet: MultiMintWallet,
     /// Current mint, to use with operations
     selected_mint: Option<MintUrl>,
+    /// Log of the transactions
+    history: TransactionHistory,
+    /// The mints of the melt quotes prepared with `melt_quote_start`, by quote ID
+    melt_quote_mints: BTreeMap<String, MintUrl>,
 }
 
 /// Summary info about the mints
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum MintsSummary {
     #[default]
     None,
@@ -44,139 +76,608 @@ pub enum MintsSummary {
 }
 
 /// Ecash wallet struct
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, Serialize)]
 pub struct WalletInfo {
+    #[serde(rename = "is_initialized")]
     pub is_inititalized: bool,
     pub mint_count: usize,
     pub mints_summary: MintsSummary,
     pub selected_mint_url: String,
 }
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, Serialize)]
 pub struct BalanceInfo(pub u64);
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct MintInfo {
     pub url: String,
     pub balance: u64,
+    /// The keysets of the mint, as last retrieved from the mint
+    pub keysets: Vec<KeysetInfo>,
+    /// Part of the balance in keysets deactivated by the mint, to be migrated
+    pub inactive_balance: u64,
+}
+
+/// A keyset of a mint (NUT-02)
+#[derive(Clone, Debug, Serialize)]
+pub struct KeysetInfo {
+    pub id: String,
+    /// Only active keysets are used for new proofs
+    pub active: bool,
+    /// Input fee, in parts per thousand (of the unit) per proof
+    pub input_fee_ppk: u64,
+}
+
+/// Result of migrating the proofs of a mint off its inactive keysets
+#[derive(Clone, Debug, Default)]
+pub struct KeysetMigration {
+    pub proof_count: usize,
+    /// Amount migrated, before the fee
+    pub amount: u64,
+    pub fee: u64,
+}
+
+/// Result of `migrate_inactive_keysets`
+#[derive(Clone, Debug, Default)]
+pub struct KeysetMigrationSummary {
+    pub amount: u64,
+    pub fee: u64,
+    /// Result (or error) per mint
+    pub mints: Vec<(String, Result<KeysetMigration, PkError>)>,
+}
+
+/// Progress report of the restore from seed, sent before and after each mint
+#[derive(Clone, Debug)]
+pub struct RestoreProgress {
+    pub mint_url: String,
+    /// 1-based index of the mint being restored
+    pub mint_index: usize,
+    pub mint_count: usize,
+    /// The restored amount with this mint, None if the mint has just been started
+    pub restored: Option<Result<u64, PkError>>,
+}
+
+/// Result of the restore from seed
+#[derive(Clone, Debug, Default)]
+pub struct RestoreSummary {
+    pub total_restored: u64,
+    /// Restored amount (or error) per mint
+    pub mints: Vec<(String, Result<u64, PkError>)>,
+}
+
+/// Result of the wallet health check with a mint, amounts in the wallet unit
+#[derive(Clone, Debug, Default)]
+pub struct MintHealth {
+    /// Number of proofs checked
+    pub proof_count: usize,
+    /// Proofs stuck as pending or reserved (e.g. by an interrupted operation),
+    /// found unspent, made spendable again
+    pub fixed_amount: u64,
+    /// Proofs stuck as pending or reserved, found spent (the operation went through), removed
+    pub resolved_amount: u64,
+    /// Spendable proofs found spent (e.g. spent by another wallet instance), removed
+    pub lost_amount: u64,
+}
+
+/// Result of the wallet health check
+#[derive(Clone, Debug, Default)]
+pub struct WalletHealthReport {
+    pub fixed_amount: u64,
+    pub resolved_amount: u64,
+    pub lost_amount: u64,
+    /// Result (or error) per mint
+    pub mints: Vec<(String, Result<MintHealth, PkError>)>,
+}
+
+/// Result of consolidating the proofs of a mint, see `consolidate`
+#[derive(Clone, Debug)]
+pub struct ConsolidateResult {
+    pub mint_url: String,
+    pub proofs_before: usize,
+    pub proofs_after: usize,
+    /// Amount of the consolidated proofs, before the fee
+    pub amount: u64,
+    /// Input fee paid to the mint
+    pub fee: u64,
+}
+
+/// Result of sending ecash
+#[derive(Clone, Debug)]
+pub struct SendEcashResult {
+    pub amount: u64,
+    pub fee: u64,
+    /// The mint the ecash was sent from
+    pub mint_url: String,
+    /// The token, in the configured format
+    pub token: String,
+    /// The token in V3 format (`cashuA...`)
+    pub token_v3: String,
+    /// The token in V4 format (`cashuB...`)
+    pub token_v4: String,
+}
+
+/// Result of sending ecash from several mints, see `send_ecash_multi`
+#[derive(Clone, Debug)]
+pub struct MultiSendResult {
+    pub amount: u64,
+    /// The total fee, including the fee of the transfers, if consolidated
+    pub fee: u64,
+    /// The tokens, one per mint used
+    pub tokens: Vec<SendEcashResult>,
+}
+
+/// Details of a Lightning invoice
+#[derive(Clone, Debug)]
+pub struct InvoiceInfo {
+    /// Amount in the invoice, in msats; none for an amountless invoice
+    pub amount_msat: Option<u64>,
+    pub description: String,
+    /// Public key of the payee node
+    pub payee: String,
+    pub payment_hash: String,
+    /// Expiry of the invoice (unix time)
+    pub expiry: u64,
+    /// Bitcoin network of the invoice, e.g. "bitcoin", "signet"
+    pub network: String,
+}
+
+/// Details of an ecash token
+#[derive(Clone, Debug)]
+pub struct TokenInfo {
+    pub mint_urls: Vec<String>,
+    pub unit: Option<String>,
+    pub amount: u64,
+    pub memo: Option<String>,
+    pub proof_count: usize,
+    /// Spending conditions (NUT-10) of the proofs, e.g. P2PK lock; empty if none
+    pub spending_conditions: Vec<String>,
+    /// Whether the mints of the token are known (added to the wallet)
+    pub mint_known: bool,
+}
+
+/// Details of a Cashu payment request (NUT-18), see `send_ecash_for_request`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PaymentRequestInfo {
+    /// Amount requested; none if the payer chooses
+    pub amount: Option<u64>,
+    pub unit: Option<String>,
+    /// The mints accepted by the requester; any mint if empty
+    pub mints: Vec<String>,
+    pub description: Option<String>,
+}
+
+/// A proof stored in the wallet, see `list_proofs`
+#[derive(Clone, Debug, Serialize)]
+pub struct ProofDetail {
+    pub keyset_id: String,
+    /// Whether the keyset is active; proofs of inactive keysets should be migrated
+    pub keyset_active: bool,
+    pub amount: u64,
+    /// State in the wallet store, e.g. `unspent`, `reserved`, `pending_spent`
+    pub state: String,
+    /// Spending condition (NUT-10), e.g. P2PK lock
+    pub spending_condition: Option<String>,
+    /// Whether the proof carries a DLEQ proof (NUT-12)
+    pub has_dleq: bool,
+}
+
+/// The proofs stored for a mint, see `list_proofs`
+#[derive(Clone, Debug, Serialize)]
+pub struct MintProofs {
+    pub mint_url: String,
+    /// The keysets of the mint, as last retrieved from the mint
+    pub keysets: Vec<KeysetInfo>,
+    /// Proofs, larger amounts first
+    pub proofs: Vec<ProofDetail>,
+    /// Number of unspent proofs per amount (denomination)
+    pub denominations: BTreeMap<u64, usize>,
+}
+
+/// A decoded invoice or token, see `decode_input`
+#[derive(Clone, Debug)]
+pub enum DecodedInput {
+    Invoice(InvoiceInfo),
+    Token(TokenInfo),
+}
+
+/// A melt quote for paying a Lightning invoice, with the fee preview, see `melt_quote_start`
+#[derive(Clone, Debug)]
+pub struct MeltQuotePreview {
+    pub quote_id: String,
+    /// The mint which would pay
+    pub mint_url: String,
+    pub amount: u64,
+    /// Lightning fee reserve, the maximum fee; the unused part is returned as change
+    pub fee_reserve: u64,
+    /// Expiry of the quote (unix time)
+    pub expiry: u64,
+    pub invoice: InvoiceInfo,
+}
+
+/// Result of paying a Lightning invoice (melt)
+#[derive(Clone, Debug)]
+pub struct MeltResult {
+    pub amount: u64,
+    pub fee: u64,
+    /// Change returned from the unused part of the fee reserve
+    pub change: u64,
+    /// The mint the payment was made from
+    pub mint_url: String,
+}
+
+/// Result of a multi-path Lightning payment, split across several mints (NUT-15)
+#[derive(Clone, Debug)]
+pub struct MppMeltResult {
+    /// Amount paid, the sum of the paid parts
+    pub amount: u64,
+    /// Fee paid, the sum of the paid parts
+    pub fee: u64,
+    /// The part of each mint, with its outcome
+    pub parts: Vec<MppPart>,
+}
+
+/// The outcome of a part of a multi-path payment
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MppPartState {
+    Paid,
+    /// Not settled yet, the outcome is not known
+    Pending,
+    /// Not paid; the proofs of the part stay in the wallet
+    Failed,
+}
+
+/// A part of a multi-path payment, paid by one mint
+#[derive(Clone, Debug)]
+pub struct MppPart {
+    pub mint_url: String,
+    /// ID of the melt quote of the part
+    pub quote_id: String,
+    pub state: MppPartState,
+    pub amount: u64,
+    /// Fee paid, 0 if not paid
+    pub fee: u64,
+    /// Change returned from the unused part of the fee reserve
+    pub change: u64,
+    /// The error, if not paid
+    pub error: Option<String>,
 }
 
-/// Used with CDK error
-#[derive(Debug, PartialEq, Eq)]
-pub struct StringError(String);
+impl MppMeltResult {
+    /// Check if all the parts have been paid, i.e. the invoice is paid
+    pub fn is_paid(&self) -> bool {
+        self.parts.iter().all(|p| p.state == MppPartState::Paid)
+    }
 
-impl std::error::Error for StringError {}
+    /// Ok if the invoice is paid, otherwise an error with the number of parts paid
+    pub fn check_paid(&self) -> Result<(), PkError> {
+        if self.is_paid() {
+            return Ok(());
+        }
+        let paid_count = self
+            .parts
+            .iter()
+            .filter(|p| p.state == MppPartState::Paid)
+            .count();
+        Err(PkError::MintError(format!(
+            "multi-path payment not completed, {} of {} parts paid",
+            paid_count,
+            self.parts.len()
+        )))
+    }
+}
 
-impl fmt::Display for StringError {
+impl fmt::Display for MppPartState {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.0)
+        match self {
+            MppPartState::Paid => f.pad("paid"),
+            MppPartState::Pending => f.pad("pending"),
+            MppPartState::Failed => f.pad("failed"),
+        }
     }
 }
 
+/// A prepared transfer between two mints, with the fee preview, see `transfer_quote`
+#[derive(Clone, Debug)]
+pub struct TransferQuote {
+    pub from_mint: String,
+    pub to_mint: String,
+    pub amount: u64,
+    /// Lightning fee reserve of the source mint, the maximum fee of the transfer
+    pub fee_reserve: u64,
+    melt_quote: Box<cdk::wallet::MeltQuote>,
+    /// The receive with the target mint
+    receive: Box<MintFromLnIntermediaryResult>,
+}
+
+/// Result of a transfer between two mints
+#[derive(Clone, Debug)]
+pub struct TransferResult {
+    pub from_mint: String,
+    pub to_mint: String,
+    /// Amount sent from the source mint, excluding the fee
+    pub amount_sent: u64,
+    pub fee_paid: u64,
+    /// Amount received with the target mint, 0 if the receive is still pending
+    pub amount_received: u64,
+    /// The receive with the target mint, if not completed yet (the payment has been made).
+    /// It can be polled with `mint_from_ln_check`, it is also persisted, see `claim_pending`.
+    pub pending_receive: Option<Box<MintFromLnIntermediaryResult>>,
+}
+
+/// Result of `check_pending_tokens`
+#[derive(Clone, Debug, Serialize)]
+pub struct PendingTokens {
+    /// The sent tokens still not claimed by the recipient, newest first
+    pub tokens: Vec<Transaction>,
+    /// The number of tokens found claimed in this check
+    pub newly_claimed: usize,
+}
+
 /// Intermediary result used in `mint_from_ln_start` and `mint_from_ln_wait`.
 #[derive(Clone, Debug)]
 pub struct MintFromLnIntermediaryResult {
+    /// The mint which issued the quote; polling and minting is always done with this mint
+    mint_url: MintUrl,
+    unit: CurrencyUnit,
     mint_quote: cdk::wallet::MintQuote,
+    /// ID of the (pending) transaction in the history
+    tx_id: u64,
     /// Set if complete (paid)
-    pub paid_result: Option<Result<u64, String>>,
+    pub paid_result: Option<Result<u64, PkError>>,
 }
 
 impl PKApp {
-    /// Create new app instance
-    pub async fn new() -> Result<PKApp, String> {
-        // TODO should be in config dir
-        let secret_seed_file_name = "./parakesh.secret";
-        // TODO should be user input
-        let seed_encryption_password = "Parakesh+Password1337";
-        let seedstore = match SeedStore::new_from_encrypted_file(
-            secret_seed_file_name,
-            &seed_encryption_password.to_owned(),
-            None,
+    /// Check if the wallet has been initialized, i.e. the secret file exists.
+    pub fn is_wallet_initialized(config: &PKAppConfig) -> bool {
+        config.secret_file_path().exists()
+    }
+
+    /// Initialize a new wallet: generate a new random seed, save it to the secret file
+    /// (encrypted with the given password), and open the wallet.
+    /// Fails if the secret file already exists, it is never overwritten.
+    pub async fn init_new_wallet(
+        config: &PKAppConfig,
+        encryption_password: &str,
+    ) -> Result<PKApp, PkError> {
+        let mut entropy = vec![0u8; NEW_SEED_ENTROPY_LEN];
+        rand::rng().fill_bytes(&mut entropy);
+        let res = Self::create_wallet_from_entropy(config, &entropy, encryption_password).await;
+        entropy.fill(0);
+        res
+    }
+
+    /// Restore a wallet from a BIP39 mnemonic (seed phrase): save the seed to a new secret file
+    /// (encrypted with the given password), and open the wallet.
+    /// Fails if the secret file already exists, it is never overwritten.
+    pub async fn restore_wallet_from_mnemonic(
+        config: &PKAppConfig,
+        mnemonic: &str,
+        encryption_password: &str,
+    ) -> Result<PKApp, PkError> {
+        let mnemonic = Mnemonic::parse_normalized(&mnemonic.to_lowercase())
+            .map_err(|e| PkError::InvalidMnemonic(e.to_string()))?;
+        let mut entropy = mnemonic.to_entropy();
+        let res = Self::create_wallet_from_entropy(config, &entropy, encryption_password).await;
+        entropy.fill(0);
+        res
+    }
+
+    /// Save the seed entropy to a new secret file, and open the wallet
+    async fn create_wallet_from_entropy(
+        config: &PKAppConfig,
+        entropy: &[u8],
+        encryption_password: &str,
+    ) -> Result<PKApp, PkError> {
+        let secret_file = config.secret_file_path().to_string_lossy().to_string();
+        if Self::is_wallet_initialized(config) {
+            return Err(PkError::WalletAlreadyExists(format!(
+                "secret file {}, not creating a new seed",
+                secret_file
+            )));
+        }
+        // Check password first, before creating the file
+        SeedStore::validate_password(encryption_password).map_err(PkError::WeakPassword)?;
+        // the seed store takes a Vec, the copy is wiped after use
+        let mut entropy = entropy.to_vec();
+        let seedstore = SeedStoreCreator::new_from_data(&entropy, None, None);
+        entropy.fill(0);
+        let seedstore = seedstore.map_err(PkError::Other)?;
+        std::fs::create_dir_all(&config.data_dir).map_err(|e| {
+            PkError::Storage(format!(
+                "Could not create data dir {}, {}",
+                config.data_dir.display(),
+                e
+            ))
+        })?;
+        if let Err(e) = SeedStoreCreator::write_to_file(
+            &seedstore,
+            &secret_file,
+            encryption_password,
+            None, // password strength is checked
         ) {
-            Ok(seedstore) => seedstore,
-            Err(_e) => {
-                // Could not read seed, generate a new one
-                // TODO do this with init, read PW, etc.
-                // TODO Should be random!
-                let secret_key = [43u8; 16].to_vec();
-                match SeedStoreCreator::new_from_data(&secret_key, None, None) {
-                    Ok(seedstore) => {
-                        let _res = SeedStoreCreator::write_to_file(
-                            &seedstore,
-                            secret_seed_file_name,
-                            seed_encryption_password,
-                            None, // allow weak pw
-                        )?;
-                        println!("Seed written to secret file {}", secret_seed_file_name);
-                        // Try to open again
-                        match SeedStore::new_from_encrypted_file(
-                            secret_seed_file_name,
-                            &seed_encryption_password.to_owned(),
-                            None,
-                        ) {
-                            Ok(seedstore) => seedstore,
-                            Err(e2) => {
-                                return Err(format!(
-                                    "Could not read seed from freshly-generated secret file ({})",
-                                    e2
-                                ));
-                            }
-                        }
-                    }
-                    Err(e3) => {
-                        return Err(format!("Could not read seed from secret file, and could not save newly-generated seed ({})", e3));
-                    }
+            // Don't leave behind a partially written file
+            let _res = std::fs::remove_file(&secret_file);
+            return Err(PkError::Storage(e));
+        }
+        Self::write_seed_check(config, &seedstore)?;
+        // Open from the file, to make sure it can be read back
+        Self::open_wallet(config, encryption_password).await
+    }
+
+    /// Open an existing wallet, read the seed from the secret file, using the password.
+    /// Fails if there is no secret file, or it cannot be decrypted.
+    pub async fn open_wallet(
+        config: &PKAppConfig,
+        encryption_password: &str,
+    ) -> Result<PKApp, PkError> {
+        let secret_file = config.secret_file_path().to_string_lossy().to_string();
+        if !Self::is_wallet_initialized(config) {
+            return Err(PkError::WalletNotInitialized(format!(
+                "secret file {} not found",
+                secret_file
+            )));
+        }
+        let seedstore = SeedStore::new_from_encrypted_file(&secret_file, encryption_password, None)
+            .map_err(|e| {
+                PkError::SeedDecryptionFailed(format!(
+                    "could not read seed from secret file {}, {}",
+                    secret_file, e
+                ))
+            })?;
+        let app = Self::new_from_seedstore(config, seedstore).await?;
+        app.check_seed().await?;
+        Ok(app)
+    }
+
+    fn seed_check_pubkey(seedstore: &SeedStore) -> Result<String, PkError> {
+        let pubkey = seedstore
+            .get_child_public_key(&ChildSpecifier::Derivation(
+                SEED_CHECK_DERIVATION_PATH.into(),
+            ))
+            .map_err(PkError::Other)?;
+        Ok(pubkey.to_string())
+    }
+
+    /// Save the seed check public key, see `check_seed`
+    fn write_seed_check(config: &PKAppConfig, seedstore: &SeedStore) -> Result<(), PkError> {
+        let pubkey = Self::seed_check_pubkey(seedstore)?;
+        let check_file = config.seed_check_file_path();
+        std::fs::write(&check_file, pubkey).map_err(|e| {
+            PkError::Storage(format!(
+                "Could not write seed check file {}, {}",
+                check_file.display(),
+                e
+            ))
+        })
+    }
+
+    /// Check that the seed is the one the wallet was created with.
+    /// Decrypting with a wrong password does not fail, it just yields a different seed,
+    /// so a non-secret public key derived from the seed is compared to the saved one.
+    /// If there is no saved check (older wallet), the seed is verified against the ecash
+    /// in the wallet, and the check is saved only if it matches.
+    async fn check_seed(&self) -> Result<(), PkError> {
+        let check_file = self.config.seed_check_file_path();
+        match std::fs::read_to_string(&check_file) {
+            Ok(saved_pubkey) => {
+                if saved_pubkey.trim() == Self::seed_check_pubkey(&self.seedstore)? {
+                    Ok(())
+                } else {
+                    Err(PkError::SeedDecryptionFailed(
+                        "the seed does not match the wallet".to_owned(),
+                    ))
                 }
             }
-        };
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                match self.verify_seed_with_proofs().await? {
+                    Some(true) => Self::write_seed_check(&self.config, &self.seedstore),
+                    Some(false) => Err(PkError::SeedDecryptionFailed(
+                        "the seed does not match the ecash in the wallet".to_owned(),
+                    )),
+                    None => Err(PkError::SeedCheckMissing(check_file.display().to_string())),
+                }
+            }
+            Err(e) => Err(PkError::Storage(format!(
+                "Could not read seed check file {}, {}",
+                check_file.display(),
+                e
+            ))),
+        }
+    }
+
+    /// Verify the seed against the stored proofs: their secrets are derived from the seed
+    /// (NUT-13), by keyset and counter, so a proof with a secret derived from this seed
+    /// confirms it. Returns None if there are no proofs to verify with.
+    async fn verify_seed_with_proofs(&self) -> Result<Option<bool>, PkError> {
+        let proof_infos = self.store.get_proofs(None, None, None, None).await?;
+        if proof_infos.is_empty() {
+            return Ok(None);
+        }
+        let mut secrets_by_keyset: BTreeMap<_, Vec<&Secret>> = BTreeMap::new();
+        for proof_info in &proof_infos {
+            secrets_by_keyset
+                .entry(proof_info.proof.keyset_id)
+                .or_default()
+                .push(&proof_info.proof.secret);
+        }
+        let xpriv = Xpriv::new_master(Network::Bitcoin, &self.get_seed()?)
+            .map_err(|e| PkError::Other(e.to_string()))?;
+        for (keyset_id, secrets) in secrets_by_keyset {
+            let counter = self
+                .store
+                .get_keyset_counter(&keyset_id)
+                .await?
+                .unwrap_or_default()
+                .min(SEED_VERIFY_MAX_COUNTER);
+            for i in 0..counter {
+                let derived = Secret::from_xpriv(xpriv, keyset_id, i)
+                    .map_err(|e| PkError::Other(e.to_string()))?;
+                if secrets.contains(&&derived) {
+                    return Ok(Some(true));
+                }
+            }
+        }
+        Ok(Some(false))
+    }
 
-        let unit = CurrencyUnit::Sat;
+    /// Create new app instance, with the seed already read
+    async fn new_from_seedstore(
+        config: &PKAppConfig,
+        seedstore: SeedStore,
+    ) -> Result<PKApp, PkError> {
+        let unit = config.unit.clone();
 
         // Initialize the memory store
         // let store = memory::empty().await?;
-        // TODO should be in config dir
-        let path = std::path::Path::new("./parakesh_data.dedb");
-        let store = Arc::new(WalletRedbDatabase::new(&path).unwrap());
+        let path = config.db_file_path();
+        let store =
+            Arc::new(WalletRedbDatabase::new(&path).map_err(|e| PkError::Storage(e.to_string()))?);
 
         // read the wallets, create Wallet instances
         let mut wallets: Vec<Wallet> = Vec::new();
-        let db_mints = store.get_mints().await.map_err(|e| e.to_string())?;
-        let seed_privkey = seedstore.get_secret_child_private_key(&ChildSpecifier::Derivation(
-            KEY_DERIVATION_PATH.into(),
-        ))?;
+        let db_mints = store.get_mints().await?;
+        let seed_privkey = seedstore
+            .get_secret_child_private_key(&ChildSpecifier::Derivation(KEY_DERIVATION_PATH.into()))
+            .map_err(PkError::Other)?;
         for (mint_url, _) in db_mints {
             let builder = WalletBuilder::new()
                 .mint_url(mint_url.clone())
                 .unit(unit.clone())
                 .localstore(store.clone())
                 .seed(seed_privkey.as_ref());
-            let wallet = builder.build().map_err(|e| e.to_string())?;
+            let wallet = builder.build()?;
             wallets.push(wallet);
         }
 
         let wallets_len = wallets.len();
         let multi_mint_wallet = MultiMintWallet::new(wallets);
+        let history = TransactionHistory::load(&config.history_file_path(), seed_privkey.as_ref())?;
 
         let mut app = PKApp {
+            config: config.clone(),
             seedstore,
             unit,
             store,
             multi_mint_wallet,
             selected_mint: None, // set below
+            history,
+            melt_quote_mints: BTreeMap::new(),
         };
 
         // Select first wallet
         if wallets_len > 0 {
-            let _res = app
-                .select_mint_by_index(1)
-                .await
-                .map_err(|e| e.to_string())?;
+            let _res = app.select_mint_by_index(1).await?;
         }
 
         Ok(app)
     }
 
-    pub async fn get_wallet_info(&self) -> Result<WalletInfo, String> {
+    pub fn config(&self) -> &PKAppConfig {
+        &self.config
+    }
+
+    pub async fn get_wallet_info(&self) -> Result<WalletInfo, PkError> {
         let wallets = self.multi_mint_wallet.get_wallets().await;
         let mint_count = wallets.len();
         let selected_mint_url = if let Some(mint) = &self.selected_mint {
@@ -197,15 +698,12 @@ impl PKApp {
         })
     }
 
-    pub async fn get_balance(&self) -> Result<BalanceInfo, String> {
-        let wallet_balances: BTreeMap<MintUrl, Amount> = self
-            .multi_mint_wallet
-            .get_balances(&CurrencyUnit::Sat)
-            .await
-            .map_err(|e| e.to_string())?;
+    pub async fn get_balance(&self) -> Result<BalanceInfo, PkError> {
+        let wallet_balances: BTreeMap<MintUrl, Amount> =
+            self.multi_mint_wallet.get_balances(&self.unit).await?;
         let total_balance: u64 = wallet_balances
-            .iter()
-            .map(|(_url, a)| {
+            .values()
+            .map(|a| {
                 let u: u64 = (*a).into();
                 u
             })
@@ -213,19 +711,20 @@ impl PKApp {
         Ok(BalanceInfo(total_balance))
     }
 
-    async fn get_mint_wallet(
+    async fn get_mint_wallet(&self, mint_url: MintUrl) -> Result<Wallet, PkError> {
+        self.get_mint_wallet_for_unit(mint_url, self.unit.clone())
+            .await
+    }
+
+    async fn get_mint_wallet_for_unit(
         &self,
         mint_url: MintUrl,
-    ) -> Result<Wallet, Box<dyn std::error::Error>> {
-        let wallet_key = WalletKey::new(mint_url.clone(), self.unit.clone());
+        unit: CurrencyUnit,
+    ) -> Result<Wallet, PkError> {
+        let wallet_key = WalletKey::new(mint_url.clone(), unit);
         match self.multi_mint_wallet.get_wallet(&wallet_key).await {
             Some(wallet) => Ok(wallet.clone()),
-            None => {
-                return Err(Box::new(StringError(format!(
-                    "Mint not found, {}",
-                    mint_url.to_string()
-                ))))
-            }
+            None => Err(PkError::UnknownMint(mint_url.to_string())),
         }
     }
 
@@ -237,35 +736,90 @@ impl PKApp {
     //     self.get_mint_wallet(mint_url).await
     // }
 
-    fn get_seed(&self) -> Result<[u8; 32], String> {
+    fn get_seed(&self) -> Result<[u8; 32], PkError> {
         let seed_privkey = self
             .seedstore
             .get_secret_child_private_key(&ChildSpecifier::Derivation(KEY_DERIVATION_PATH.into()))
-            .map_err(|err| err.to_string())?;
-        Ok(seed_privkey.as_ref().clone())
+            .map_err(PkError::Other)?;
+        Ok(*seed_privkey.as_ref())
     }
 
-    pub async fn add_mint(&mut self, mint_url_str: &str) -> Result<(), String> {
+    /// Return the seed as BIP39 mnemonic (seed phrase), for backup.
+    /// CAUTION: the secret is returned in copy!
+    pub fn get_seed_mnemonic(&self) -> Result<String, PkError> {
+        self.seedstore.get_secret_mnemonic().map_err(PkError::Other)
+    }
+
+    /// Start the seed backup verification: select some random words of the mnemonic,
+    /// which the user should enter, see `seed_verify_check`.
+    /// Returns the 1-based indices of the selected words, in increasing order.
+    pub fn seed_verify_quiz(&self, word_count: usize) -> Result<Vec<usize>, PkError> {
+        let total_words = self.get_seed_mnemonic()?.split_whitespace().count();
+        if word_count == 0 || word_count > total_words {
+            return Err(PkError::InvalidInput(format!(
+                "Invalid number of words to verify {}, maximum is {}",
+                word_count, total_words
+            )));
+        }
+        let mut indices: Vec<usize> =
+            rand::seq::index::sample(&mut rand::rng(), total_words, word_count)
+                .into_iter()
+                .map(|i| i + 1)
+                .collect();
+        indices.sort();
+        Ok(indices)
+    }
+
+    /// Check the words entered by the user against the mnemonic.
+    /// `words`: pairs of 1-based word index and the word.
+    /// Returns true if all the words match.
+    pub fn seed_verify_check(&self, words: &[(usize, String)]) -> Result<bool, PkError> {
+        if words.is_empty() {
+            return Err(PkError::InvalidInput("No words to verify".to_owned()));
+        }
+        let mnemonic = self.get_seed_mnemonic()?;
+        let mnemonic_words: Vec<&str> = mnemonic.split_whitespace().collect();
+        let mut all_match = true;
+        for (index, word) in words {
+            if *index == 0 || *index > mnemonic_words.len() {
+                return Err(PkError::InvalidInput(format!(
+                    "Invalid word index {}",
+                    index
+                )));
+            }
+            if mnemonic_words[*index - 1] != word.trim().to_lowercase() {
+                all_match = false;
+            }
+        }
+        Ok(all_match)
+    }
+
+    pub async fn add_mint(&mut self, mint_url_str: &str) -> Result<(), PkError> {
+        self.add_mint_wallet(mint_url_str).await?;
+        self.select_mint(mint_url_str).await?;
+        Ok(())
+    }
+
+    /// Add the wallet for a mint, without selecting it
+    async fn add_mint_wallet(&mut self, mint_url_str: &str) -> Result<Wallet, PkError> {
         let wallet = Wallet::new(
-            &mint_url_str.to_string(),
+            mint_url_str,
             self.unit.clone(),
             self.store.clone(),
             &self.get_seed()?,
             None,
-        )
-        .map_err(|err| err.to_string())?;
+        )?;
         // This is needed to store the mint in the store
-        let mint_info = wallet
-            .get_mint_info()
-            .await
-            .map_err(|err| err.to_string())?;
+        let mint_info = wallet.get_mint_info().await?;
         if let Some(_info) = mint_info {
         } else {
-            return Err(format!("Could not obtain mint info for {}", mint_url_str));
+            return Err(PkError::MintUnreachable(format!(
+                "could not obtain mint info for {}",
+                mint_url_str
+            )));
         }
-        self.multi_mint_wallet.add_wallet(wallet).await;
-        let _res = self.select_mint(mint_url_str).await?;
-        Ok(())
+        self.multi_mint_wallet.add_wallet(wallet.clone()).await;
+        Ok(wallet)
     }
 
     pub fn selected_mint(&self) -> String {
@@ -275,12 +829,9 @@ impl PKApp {
         }
     }
 
-    pub async fn select_mint(&mut self, mint_url_str: &str) -> Result<String, String> {
-        let mint_url = MintUrl::from_str(mint_url_str).map_err(|e| e.to_string())?;
-        let _wallet = self
-            .get_mint_wallet(mint_url.clone())
-            .await
-            .map_err(|e| e.to_string())?;
+    pub async fn select_mint(&mut self, mint_url_str: &str) -> Result<String, PkError> {
+        let mint_url = MintUrl::from_str(mint_url_str)?;
+        let _wallet = self.get_mint_wallet(mint_url.clone()).await?;
         self.selected_mint = Some(mint_url);
         Ok(mint_url_str.to_owned())
     }
@@ -288,77 +839,478 @@ impl PKApp {
     pub async fn select_mint_by_index(
         &mut self,
         mint_index_1_based: usize,
-    ) -> Result<usize, String> {
+    ) -> Result<usize, PkError> {
         let wallets = &self.multi_mint_wallet.get_wallets().await;
         if mint_index_1_based == 0 {
-            return Err(format!(
+            return Err(PkError::InvalidInput(format!(
                 "Invalid mint index {}, the first is 1",
                 mint_index_1_based
-            ));
+            )));
         }
         let max_index = wallets.len();
         if mint_index_1_based > max_index {
-            return Err(format!(
+            return Err(PkError::InvalidInput(format!(
                 "Invalid mint index {}, maximum is {}",
                 mint_index_1_based, max_index
-            ));
+            )));
         }
         let mint_url = &wallets[mint_index_1_based - 1].mint_url;
         self.selected_mint = Some(mint_url.clone());
         Ok(mint_index_1_based)
     }
 
-    pub async fn get_mints_info(&self) -> Result<Vec<MintInfo>, String> {
+    pub async fn get_mints_info(&self) -> Result<Vec<MintInfo>, PkError> {
         let wallets = &self.multi_mint_wallet.get_wallets().await;
         let mut info = Vec::new();
         for wallet in wallets.iter() {
             let balance: u64 = wallet.total_balance().await.unwrap_or_default().into();
+            let inactive_balance: u64 = self
+                .inactive_keyset_proofs(&wallet.mint_url)
+                .await?
+                .total_amount()?
+                .into();
             info.push(MintInfo {
                 url: wallet.mint_url.to_string(),
                 balance,
+                keysets: self.stored_keysets(&wallet.mint_url).await?,
+                inactive_balance,
             });
         }
         Ok(info)
     }
 
-    pub async fn receive_ecash(&mut self, token: &str) -> Result<u64, String> {
-        if let Some(sel_mint) = &self.selected_mint {
-            let wallet = self
-                .get_mint_wallet(sel_mint.clone())
-                .await
-                .map_err(|e| e.to_string())?;
+    /// Receive an ecash token, with the mint of the token (not necessarily the selected one).
+    /// If the mint is not known yet, `UntrustedMint` is returned, unless `trust_mint` is set,
+    /// in which case the mint is added (without selecting it).
+    pub async fn receive_ecash(&mut self, token: &str, trust_mint: bool) -> Result<u64, PkError> {
+        let token_parsed = Token::from_str(token)?;
+        let token_value: u64 = token_parsed.value()?.into();
+        if let Some(token_unit) = token_parsed.unit() {
+            if token_unit != self.unit {
+                return Err(PkError::InvalidToken(format!(
+                    "token unit {} differs from the wallet unit {}",
+                    token_unit, self.unit
+                )));
+            }
+        }
+        let mint_url = token_parsed.mint_url()?;
+        let wallet = match self.get_mint_wallet(mint_url.clone()).await {
+            Ok(wallet) => wallet,
+            Err(PkError::UnknownMint(_)) if trust_mint => {
+                self.add_mint_wallet(&mint_url.to_string()).await?
+            }
+            Err(PkError::UnknownMint(_)) => {
+                return Err(PkError::UntrustedMint(mint_url.to_string()))
+            }
+            Err(e) => return Err(e),
+        };
 
-            // Receive the token
-            let received = wallet
-                .receive(token, SplitTarget::default(), &[], &[])
-                .await
-                .map_err(|e| e.to_string())?;
-            Ok(received.into())
-        } else {
-            Err("No selected mint!".to_owned())
+        // Receive the token
+        let received: u64 = wallet
+            .receive(token, SplitTarget::default(), &[], &[])
+            .await?
+            .into();
+        self.record_transaction(Transaction::new(
+            TxDirection::Incoming,
+            TxKind::Ecash,
+            received,
+            token_value.saturating_sub(received),
+            &wallet.mint_url.to_string(),
+            token,
+            TxStatus::Completed,
+        ));
+        self.auto_consolidate(&wallet).await;
+        Ok(received)
+    }
+
+    /// Decode a Lightning invoice or an ecash token, for showing its details before use.
+    /// A `lightning:` or `cashu:` URI prefix is accepted.
+    pub async fn decode_input(&self, input: &str) -> Result<DecodedInput, PkError> {
+        let token_str = match classify_input(input) {
+            InputKind::Token(token_str) => token_str,
+            InputKind::Invoice(invoice_str) => {
+                let invoice = Bolt11Invoice::from_str(&invoice_str)
+                    .map_err(|e| PkError::InvalidInvoice(e.to_string()))?;
+                return Ok(DecodedInput::Invoice(InvoiceInfo::from_invoice(&invoice)));
+            }
+            kind => {
+                return Err(PkError::InvalidInput(format!(
+                    "Not an invoice or token ({})",
+                    kind.name()
+                )))
+            }
+        };
+
+        let token = Token::from_str(&token_str)?;
+        let mint_urls = match &token {
+            Token::TokenV3(token) => token.mint_urls(),
+            Token::TokenV4(token) => vec![token.mint_url.clone()],
+        };
+        let mut mint_known = true;
+        for mint_url in &mint_urls {
+            if self.get_mint_wallet(mint_url.clone()).await.is_err() {
+                mint_known = false;
+            }
+        }
+        let proofs = token.proofs();
+        let mut spending_conditions = Vec::new();
+        for proof in &proofs {
+            let condition = match SpendingConditions::try_from(&proof.secret) {
+                Ok(conditions) => describe_spending_conditions(&conditions),
+                Err(_) => continue,
+            };
+            if !spending_conditions.contains(&condition) {
+                spending_conditions.push(condition);
+            }
         }
+        Ok(DecodedInput::Token(TokenInfo {
+            mint_urls: mint_urls.iter().map(|u| u.to_string()).collect(),
+            unit: token.unit().map(|u| u.to_string()),
+            amount: token.value()?.into(),
+            memo: token.memo().clone(),
+            proof_count: proofs.len(),
+            spending_conditions,
+            mint_known,
+        }))
     }
 
-    pub async fn send_ecash(&mut self, amount_sats: u64) -> Result<(u64, String), String> {
-        if let Some(sel_mint) = &self.selected_mint {
-            let wallet = self
-                .get_mint_wallet(sel_mint.clone())
-                .await
-                .map_err(|e| e.to_string())?;
+    /// Send ecash: create a token for the amount, with the mint chosen by
+    /// the mint selection strategy (by default the selected mint).
+    pub async fn send_ecash(&mut self, amount_sats: u64) -> Result<SendEcashResult, PkError> {
+        let mut last_error = PkError::InsufficientBalance;
+        for wallet in self.send_wallet_candidates(amount_sats).await? {
             // Send the token
-            let prepared_send = wallet
+            let prepared_send = match wallet
                 .prepare_send(Amount::from(amount_sats), SendOptions::default())
                 .await
-                .map_err(|e| e.to_string())?;
-            let token = wallet
-                .send(prepared_send, None)
+            {
+                Ok(prepared_send) => prepared_send,
+                Err(e) => {
+                    // e.g. not enough with the fee, try the next mint
+                    last_error = e.into();
+                    continue;
+                }
+            };
+            return self
+                .send_prepared(&wallet, prepared_send, amount_sats)
+                .await;
+        }
+        Err(last_error)
+    }
+
+    /// Send ecash for a Cashu payment request (NUT-18): the amount of the request (or
+    /// `amount_sats` if it has none), from a mint accepted by the requester, trying them
+    /// in the order of the request. Without mints in the request it is like `send_ecash`.
+    /// The token is returned, to be given to the requester; the transports of the request
+    /// (Nostr, HTTP POST) are not used.
+    pub async fn send_ecash_for_request(
+        &mut self,
+        request: &str,
+        amount_sats: Option<u64>,
+    ) -> Result<SendEcashResult, PkError> {
+        let request = PaymentRequestInfo::parse(request)?;
+        if let Some(unit) = &request.unit {
+            if *unit != self.unit.to_string() {
+                return Err(PkError::InvalidInput(format!(
+                    "Payment request in unit {}, the wallet uses {}",
+                    unit, self.unit
+                )));
+            }
+        }
+        let amount_sats = request
+            .amount
+            .or(amount_sats)
+            .ok_or_else(|| PkError::InvalidInput("The payment request has no amount".to_owned()))?;
+        if request.mints.is_empty() {
+            return self.send_ecash(amount_sats).await;
+        }
+        let mut last_error = PkError::UnknownMint(format!(
+            "none of the mints of the payment request is known: {}",
+            request.mints.join(", ")
+        ));
+        for mint_url in &request.mints {
+            let wallet = match self.get_mint_wallet(MintUrl::from_str(mint_url)?).await {
+                Ok(wallet) => wallet,
+                Err(_) => continue,
+            };
+            let prepared_send = match wallet
+                .prepare_send(Amount::from(amount_sats), SendOptions::default())
                 .await
-                .map_err(|e| e.to_string())?;
+            {
+                Ok(prepared_send) => prepared_send,
+                Err(e) => {
+                    // e.g. insufficient balance, try the next mint
+                    last_error = e.into();
+                    continue;
+                }
+            };
+            return self
+                .send_prepared(&wallet, prepared_send, amount_sats)
+                .await;
+        }
+        Err(last_error)
+    }
 
-            Ok((amount_sats, token.to_v3_string()))
-        } else {
-            Err("No selected mint!".to_string())
+    /// Create the token from the prepared send, and record it in the history
+    async fn send_prepared(
+        &mut self,
+        wallet: &Wallet,
+        prepared_send: PreparedSend,
+        amount_sats: u64,
+    ) -> Result<SendEcashResult, PkError> {
+        let fee: u64 = prepared_send.fee().into();
+        let token = wallet.send(prepared_send, None).await?;
+        let token_v3 = token.to_v3_string();
+        let token_v4 = token.to_string();
+        let token = match self.config.token_format {
+            TokenFormat::V3 => token_v3.clone(),
+            TokenFormat::V4 => token_v4.clone(),
+        };
+        let mint_url = wallet.mint_url.to_string();
+        self.record_transaction(Transaction::new(
+            TxDirection::Outgoing,
+            TxKind::Ecash,
+            amount_sats,
+            fee,
+            &mint_url,
+            &token,
+            // Pending until the recipient claims it, see `check_pending_tokens`
+            TxStatus::Pending,
+        ));
+        Ok(SendEcashResult {
+            amount: amount_sats,
+            fee,
+            mint_url,
+            token,
+            token_v3,
+            token_v4,
+        })
+    }
+
+    /// Send ecash combining the balances of several mints, the ones with larger balance first.
+    /// Without `consolidate` a token is created with each mint used. With `consolidate`
+    /// the missing funds are first transferred via Lightning into the selected mint
+    /// (or the one with the largest balance), and a single token is created.
+    pub async fn send_ecash_multi(
+        &mut self,
+        amount_sats: u64,
+        consolidate: bool,
+    ) -> Result<MultiSendResult, PkError> {
+        if amount_sats == 0 {
+            return Err(PkError::InvalidInput("Amount must be positive".to_owned()));
+        }
+        let funded = self.funded_wallets().await?;
+        let total_balance: u64 = funded.iter().map(|(_, balance)| balance).sum();
+        if total_balance < amount_sats {
+            return Err(PkError::InsufficientBalance);
+        }
+        if consolidate {
+            return self.send_ecash_consolidated(amount_sats, funded).await;
         }
+
+        // Prepare the parts first, nothing is sent if the funds are not enough
+        let mut remaining = amount_sats;
+        let mut prepared = Vec::new();
+        for (wallet, balance) in funded {
+            if remaining == 0 {
+                break;
+            }
+            let mut part = remaining.min(balance);
+            let prepared_send = match wallet
+                .prepare_send(Amount::from(part), SendOptions::default())
+                .await
+            {
+                Ok(prepared_send) => prepared_send,
+                Err(cdk::Error::InsufficientFunds) => {
+                    // Not enough with the fee, leave room for the maximum fee
+                    let proofs = wallet.get_unspent_proofs().await?;
+                    let max_fee: u64 = wallet.get_proofs_fee(&proofs).await?.into();
+                    part = remaining.min(balance.saturating_sub(max_fee));
+                    if part == 0 {
+                        continue;
+                    }
+                    match wallet
+                        .prepare_send(Amount::from(part), SendOptions::default())
+                        .await
+                    {
+                        Ok(prepared_send) => prepared_send,
+                        Err(e) => {
+                            eprintln!("Could not send from {}, {}", wallet.mint_url, e);
+                            continue;
+                        }
+                    }
+                }
+                Err(e) => {
+                    eprintln!("Could not send from {}, {}", wallet.mint_url, e);
+                    continue;
+                }
+            };
+            remaining -= part;
+            prepared.push((wallet, prepared_send, part));
+        }
+        if remaining > 0 {
+            return Err(PkError::InsufficientBalance);
+        }
+        let mut tokens = Vec::new();
+        for (wallet, prepared_send, part) in prepared {
+            // The tokens already created are in the history
+            tokens.push(self.send_prepared(&wallet, prepared_send, part).await?);
+        }
+        Ok(MultiSendResult {
+            amount: amount_sats,
+            fee: tokens.iter().map(|t| t.fee).sum(),
+            tokens,
+        })
+    }
+
+    /// Consolidate the funds into one mint with transfers, and send a single token from it,
+    /// see `send_ecash_multi`
+    async fn send_ecash_consolidated(
+        &mut self,
+        amount_sats: u64,
+        funded: Vec<(Wallet, u64)>,
+    ) -> Result<MultiSendResult, PkError> {
+        let target_url = match &self.selected_mint {
+            Some(mint_url) => mint_url.clone(),
+            None => funded
+                .first()
+                .map(|(wallet, _)| wallet.mint_url.clone())
+                .ok_or(PkError::InsufficientBalance)?,
+        };
+        let target_wallet = self.get_mint_wallet(target_url.clone()).await?;
+        let target_balance: u64 = target_wallet.total_balance().await?.into();
+        let mut missing = amount_sats.saturating_sub(target_balance);
+        let mut transfer_fee = 0;
+        for (wallet, balance) in funded {
+            if missing == 0 {
+                break;
+            }
+            if wallet.mint_url == target_url {
+                continue;
+            }
+            let mut part = missing.min(balance);
+            let mut quote = match self.quote_transfer(&wallet, &target_wallet, part).await {
+                Ok(quote) => quote,
+                Err(e) => {
+                    eprintln!("Could not transfer from {}, {}", wallet.mint_url, e);
+                    continue;
+                }
+            };
+            if part + quote.fee_reserve > balance {
+                // Not enough with the fee, transfer less
+                part = balance.saturating_sub(quote.fee_reserve);
+                self.transfer_cancel(quote).await?;
+                if part == 0 {
+                    continue;
+                }
+                quote = match self.quote_transfer(&wallet, &target_wallet, part).await {
+                    Ok(quote) => quote,
+                    Err(e) => {
+                        eprintln!("Could not transfer from {}, {}", wallet.mint_url, e);
+                        continue;
+                    }
+                };
+            }
+            let transfer = self.transfer_execute(quote).await?;
+            transfer_fee += transfer.fee_paid;
+            let mut amount_received = transfer.amount_received;
+            if let Some(receive) = transfer.pending_receive {
+                // The funds are needed for the token
+                amount_received = self.mint_from_ln_wait(*receive).await?;
+            }
+            missing = missing.saturating_sub(amount_received);
+        }
+        if missing > 0 {
+            return Err(PkError::InsufficientBalance);
+        }
+        let prepared_send = target_wallet
+            .prepare_send(Amount::from(amount_sats), SendOptions::default())
+            .await?;
+        let sent = self
+            .send_prepared(&target_wallet, prepared_send, amount_sats)
+            .await?;
+        Ok(MultiSendResult {
+            amount: amount_sats,
+            fee: transfer_fee + sent.fee,
+            tokens: vec![sent],
+        })
+    }
+
+    /// The (trusted) wallets with a non-zero balance, with the balance, the largest first
+    async fn funded_wallets(&self) -> Result<Vec<(Wallet, u64)>, PkError> {
+        let trusted_mints = self.trusted_mint_urls()?;
+        let mut funded = Vec::new();
+        for wallet in self.multi_mint_wallet.get_wallets().await {
+            if wallet.unit != self.unit
+                || (!trusted_mints.is_empty() && !trusted_mints.contains(&wallet.mint_url))
+            {
+                continue;
+            }
+            let balance: u64 = wallet.total_balance().await?.into();
+            if balance > 0 {
+                funded.push((wallet, balance));
+            }
+        }
+        funded.sort_by_key(|(_, balance)| std::cmp::Reverse(*balance));
+        Ok(funded)
+    }
+
+    /// The wallets to send the amount from, in the order of preference, by the mint selection
+    /// strategy of the config: the selected mint only (default), or the (trusted) mints
+    /// with enough balance.
+    async fn send_wallet_candidates(&self, amount_sats: u64) -> Result<Vec<Wallet>, PkError> {
+        let strategy = self.config.mint_selection;
+        if strategy == MintSelection::Selected {
+            let sel_mint = self.selected_mint.clone().ok_or(PkError::NoSelectedMint)?;
+            return Ok(vec![self.get_mint_wallet(sel_mint).await?]);
+        }
+        let trusted_mints = self.trusted_mint_urls()?;
+        let mut candidates = Vec::new();
+        for wallet in self.multi_mint_wallet.get_wallets().await {
+            if wallet.unit != self.unit
+                || (!trusted_mints.is_empty() && !trusted_mints.contains(&wallet.mint_url))
+            {
+                continue;
+            }
+            let balance: u64 = wallet.total_balance().await?.into();
+            if balance < amount_sats || balance == 0 {
+                continue;
+            }
+            let is_selected = self.selected_mint.as_ref() == Some(&wallet.mint_url);
+            let input_fee_ppk = if strategy == MintSelection::LowestFee {
+                match wallet.get_active_mint_keyset().await {
+                    Ok(keyset) => keyset.input_fee_ppk,
+                    // unreachable mint, try it last
+                    Err(_) => u64::MAX,
+                }
+            } else {
+                0
+            };
+            candidates.push((wallet, balance, is_selected, input_fee_ppk));
+        }
+        match strategy {
+            MintSelection::PreferSelected => {
+                candidates.sort_by_key(|(_, balance, is_selected, _)| {
+                    (!is_selected, std::cmp::Reverse(*balance))
+                })
+            }
+            MintSelection::LowestFee => candidates
+                .sort_by_key(|(_, balance, _, fee_ppk)| (*fee_ppk, std::cmp::Reverse(*balance))),
+            _ => candidates.sort_by_key(|(_, balance, _, _)| std::cmp::Reverse(*balance)),
+        }
+        if candidates.is_empty() {
+            return Err(PkError::InsufficientBalance);
+        }
+        Ok(candidates.into_iter().map(|(wallet, ..)| wallet).collect())
+    }
+
+    /// The mints allowed for the automatic mint selection, from the config; empty means all
+    fn trusted_mint_urls(&self) -> Result<Vec<MintUrl>, PkError> {
+        self.config
+            .trusted_mints
+            .iter()
+            .map(|m| Ok(MintUrl::from_str(m)?))
+            .collect()
     }
 
     /// Run `mint_from_ln_start` and `mint_from_ln_wait` in sequence.
@@ -368,7 +1320,7 @@ impl PKApp {
         &mut self,
         amount_sats: u64,
         callback: F,
-    ) -> Result<u64, String> {
+    ) -> Result<u64, PkError> {
         let (invoice, intermediary_res) = self.mint_from_ln_start(amount_sats).await?;
         (callback)(invoice.as_str());
         self.mint_from_ln_wait(intermediary_res).await
@@ -380,143 +1332,1247 @@ impl PKApp {
     pub async fn mint_from_ln_start(
         &mut self,
         amount_sats: u64,
-    ) -> Result<(String, MintFromLnIntermediaryResult), String> {
+    ) -> Result<(String, MintFromLnIntermediaryResult), PkError> {
         if let Some(sel_mint) = &self.selected_mint {
-            let wallet = self
-                .get_mint_wallet(sel_mint.clone())
-                .await
-                .map_err(|e| e.to_string())?;
+            let wallet = self.get_mint_wallet(sel_mint.clone()).await?;
 
             // Request a mint quote from the wallet
-            let mint_quote = wallet
-                .mint_quote(Amount::from(amount_sats), None)
-                .await
-                .map_err(|e| e.to_string())?;
+            let mint_quote = wallet.mint_quote(Amount::from(amount_sats), None).await?;
 
-            // println!("Pay request: {}", quote.request);
             let invoice_to_be_paid = mint_quote.request.clone();
+            let tx_id = self.record_transaction(Transaction::new(
+                TxDirection::Incoming,
+                TxKind::Lightning,
+                amount_sats,
+                0,
+                &wallet.mint_url.to_string(),
+                &invoice_to_be_paid,
+                TxStatus::Pending,
+            ));
             Ok((
                 invoice_to_be_paid,
-                MintFromLnIntermediaryResult {
-                    mint_quote,
-                    paid_result: None,
-                },
+                MintFromLnIntermediaryResult::new(mint_quote, tx_id),
             ))
         } else {
-            Err("No selected mint!".to_string())
+            Err(PkError::NoSelectedMint)
         }
     }
 
-    /// Check for the status once
+    /// Check for the status once, with the mint of the quote (not the currently selected one)
     /// Returns an intermediary result, or the amount if paid
     pub async fn mint_from_ln_check(
         &mut self,
         intermediary_result: MintFromLnIntermediaryResult,
-    ) -> Result<MintFromLnIntermediaryResult, String> {
-        println!("Polling for mint result...");
+    ) -> Result<MintFromLnIntermediaryResult, PkError> {
         if intermediary_result.paid_result.is_some() {
             return Ok(intermediary_result);
         }
-        if let Some(sel_mint) = &self.selected_mint {
-            let wallet = self
-                .get_mint_wallet(sel_mint.clone())
-                .await
-                .map_err(|e| e.to_string())?;
+        let paid = self
+            .check_mint_quote(
+                &intermediary_result.mint_url,
+                &intermediary_result.unit,
+                &intermediary_result.mint_quote,
+                intermediary_result.tx_id,
+            )
+            .await?;
+        let mut res2 = intermediary_result;
+        res2.paid_result = paid.map(Ok);
+        Ok(res2)
+    }
 
-            let quote_id = &intermediary_result.mint_quote.id;
-            let status = wallet
-                .mint_quote_state(quote_id)
-                .await
-                .map_err(|e| e.to_string())?;
-            if status.state == MintQuoteState::Paid || status.state == MintQuoteState::Issued {
-                // Mint the received amount
-                let proofs = wallet
-                    .mint(quote_id, SplitTarget::default(), None)
-                    .await
-                    .map_err(|e| e.to_string())?;
-                let receive_amount = proofs.total_amount().map_err(|e| e.to_string())?;
-                let mut res2 = intermediary_result;
-                res2.paid_result = Some(Ok(receive_amount.into()));
-                return Ok(res2);
+    /// The pending Lightning receives: the mint quotes in the store (from all mints) which
+    /// have not been minted yet, and have not expired. Mint quotes are persisted in the store,
+    /// so these survive restarts; they can be polled with `mint_from_ln_check`,
+    /// or claimed with `claim_pending`.
+    pub async fn get_pending_mint_quotes(
+        &mut self,
+    ) -> Result<Vec<MintFromLnIntermediaryResult>, PkError> {
+        let now = unix_time();
+        let mut pending = Vec::new();
+        for mint_quote in self.store.get_mint_quotes().await? {
+            if mint_quote.unit != self.unit
+                || mint_quote.state == MintQuoteState::Issued
+                || is_quote_expired(&mint_quote, now)
+            {
+                continue;
             }
-            // not paid yet
-            println!("Quote state: {}", status.state);
+            let tx_id = self.pending_quote_tx_id(&mint_quote);
+            pending.push(MintFromLnIntermediaryResult::new(mint_quote, tx_id));
+        }
+        Ok(pending)
+    }
 
-            let res2 = intermediary_result;
-            Ok(res2)
-        } else {
-            Err("No selected mint!".to_string())
+    /// Claim the pending Lightning receives: check all the mint quotes in the store,
+    /// mint the ones which have been paid, and drop the ones which have expired unpaid.
+    /// Returns the total amount received.
+    /// A quote failing does not stop the others; the error is returned only if none succeeded.
+    pub async fn claim_pending(&mut self) -> Result<u64, PkError> {
+        let mut total_received = 0;
+        let mut first_error = None;
+        let mut any_ok = false;
+        for mint_quote in self.store.get_mint_quotes().await? {
+            if mint_quote.unit != self.unit {
+                continue;
+            }
+            match self.claim_mint_quote(mint_quote).await {
+                Ok(received) => {
+                    total_received += received;
+                    any_ok = true;
+                }
+                Err(e) => {
+                    eprintln!("Error claiming pending receive, {}", e);
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+        match first_error {
+            Some(e) if !any_ok => Err(e),
+            _ => Ok(total_received),
+        }
+    }
+
+    /// Check one stored mint quote, with its own mint: mint it if paid, drop it if expired.
+    /// Returns the amount received (0 if not paid yet).
+    async fn claim_mint_quote(
+        &mut self,
+        mint_quote: cdk::wallet::MintQuote,
+    ) -> Result<u64, PkError> {
+        let tx_id = self.pending_quote_tx_id(&mint_quote);
+        match self
+            .check_mint_quote(&mint_quote.mint_url, &mint_quote.unit, &mint_quote, tx_id)
+            .await
+        {
+            Ok(received) => Ok(received.unwrap_or_default()),
+            Err(PkError::QuoteExpired) => Ok(0),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Check the state of a mint quote with the given mint, and mint the ecash if paid.
+    /// Returns the amount received, or None if not paid yet.
+    /// An expired unpaid quote is removed, and `QuoteExpired` is returned.
+    async fn check_mint_quote(
+        &mut self,
+        mint_url: &MintUrl,
+        unit: &CurrencyUnit,
+        mint_quote: &cdk::wallet::MintQuote,
+        tx_id: u64,
+    ) -> Result<Option<u64>, PkError> {
+        let wallet = self
+            .get_mint_wallet_for_unit(mint_url.clone(), unit.clone())
+            .await?;
+        let status = wallet.mint_quote_state(&mint_quote.id).await?;
+        match status.state {
+            MintQuoteState::Paid => {
+                // Mint the received amount
+                let proofs = wallet
+                    .mint(&mint_quote.id, SplitTarget::default(), None)
+                    .await?;
+                let receive_amount: u64 = proofs.total_amount()?.into();
+                self.update_transaction(tx_id, TxStatus::Completed, receive_amount, 0);
+                self.auto_consolidate(&wallet).await;
+                Ok(Some(receive_amount))
+            }
+            MintQuoteState::Issued => {
+                // Already minted (e.g. claimed meanwhile), nothing more to receive
+                self.store.remove_mint_quote(&mint_quote.id).await?;
+                Ok(Some(0))
+            }
+            _ => {
+                // not paid yet
+                if is_quote_expired(mint_quote, unix_time()) {
+                    // Cannot be paid any more, forget it
+                    self.store.remove_mint_quote(&mint_quote.id).await?;
+                    self.update_transaction(tx_id, TxStatus::Failed, mint_quote.amount.into(), 0);
+                    return Err(PkError::QuoteExpired);
+                }
+                Ok(None)
+            }
+        }
+    }
+
+    /// The ID of the history transaction of a pending mint quote.
+    /// If there is none (quote from before the history), it is added.
+    fn pending_quote_tx_id(&mut self, mint_quote: &cdk::wallet::MintQuote) -> u64 {
+        match self.history.find_by_reference(&mint_quote.request) {
+            Some(tx_id) => tx_id,
+            None => self.record_transaction(Transaction::new(
+                TxDirection::Incoming,
+                TxKind::Lightning,
+                mint_quote.amount.into(),
+                0,
+                &mint_quote.mint_url.to_string(),
+                &mint_quote.request,
+                TxStatus::Pending,
+            )),
         }
     }
 
     /// Second part of `mint_from_ln_start`, should be invoked with the intermediary result.
     /// Polls for result, waits until a result is available (invoice had been paid), or timeout.
+    /// After a timeout the receive remains pending, it can be claimed later with `claim_pending`.
     /// Returns the amount received.
     /// Warning: Returns in a long time (waits until user action)
     pub async fn mint_from_ln_wait(
         &mut self,
         intermediary_result: MintFromLnIntermediaryResult,
-    ) -> Result<u64, String> {
+    ) -> Result<u64, PkError> {
         // Check the quote state in a loop with a timeout
+        let timeout = Duration::from_secs(self.config.poll_max_time_sec);
+        let start = std::time::Instant::now();
         let mut int_res = intermediary_result;
         loop {
             let res2 = self.mint_from_ln_check(int_res).await?;
             if let Some(res) = res2.paid_result {
                 return res;
             }
+            if start.elapsed() > timeout {
+                return Err(PkError::Timeout(format!(
+                    "invoice not paid in {} secs, if paid later it can be claimed",
+                    self.config.poll_max_time_sec
+                )));
+            }
             // not paid, wait some more
             int_res = res2;
             // sleep(to_wait).await;
-            tokio::time::sleep(Duration::from_secs(2)).await;
+            tokio::time::sleep(Duration::from_millis(self.config.poll_step_ms)).await;
         }
     }
 
-    pub async fn melt_to_ln(&mut self, ln_invoice: &str) -> Result<u64, String> {
-        if let Some(sel_mint) = &self.selected_mint {
-            let wallet = self
-                .get_mint_wallet(sel_mint.clone())
-                .await
-                .map_err(|e| e.to_string())?;
+    /// Pay a Lightning invoice (melt), with the mint chosen by the mint selection strategy
+    /// (by default the selected mint), see `melt_quote_start` and `melt_confirm`.
+    pub async fn melt_to_ln(&mut self, ln_invoice: &str) -> Result<MeltResult, PkError> {
+        let preview = self.melt_quote_start(ln_invoice).await?;
+        self.melt_confirm(&preview.quote_id).await
+    }
 
-            println!("About to melt_quote...");
+    /// Request a melt quote for paying a Lightning invoice, with the mint chosen by the mint
+    /// selection strategy (by default the selected mint). With the automatic selection the mints
+    /// are asked for a quote, and one which has enough funds for the amount and the fee reserve
+    /// is used. Returns the quote, with the fee reserve as fee preview; it should be paid with
+    /// `melt_confirm`, or dropped with `melt_cancel`.
+    pub async fn melt_quote_start(
+        &mut self,
+        ln_invoice: &str,
+    ) -> Result<MeltQuotePreview, PkError> {
+        let invoice = Bolt11Invoice::from_str(ln_invoice)
+            .map_err(|e| PkError::InvalidInvoice(e.to_string()))?;
+        let strategy = self.config.mint_selection;
+        let mut chosen: Option<(Wallet, cdk::wallet::MeltQuote)> = None;
+        let mut last_error = PkError::InsufficientBalance;
+        for wallet in self.send_wallet_candidates(0).await? {
             // Request a melt quote from the wallet
-            let quote = wallet
-                .melt_quote(ln_invoice.to_string(), None)
-                .await
-                .map_err(|e| e.to_string())?;
-            println!("Melt quote: {} {} {:?}", quote.amount, quote.state, quote,);
+            let quote = match wallet.melt_quote(ln_invoice.to_string(), None).await {
+                Ok(quote) => quote,
+                Err(e) => {
+                    last_error = e.into();
+                    continue;
+                }
+            };
+            if strategy != MintSelection::Selected {
+                let balance = wallet.total_balance().await?;
+                if balance < quote.amount + quote.fee_reserve {
+                    last_error = PkError::InsufficientBalance;
+                    let _res = self.store.remove_melt_quote(&quote.id).await;
+                    continue;
+                }
+            }
+            let is_better = match &chosen {
+                None => true,
+                Some((_, chosen_quote)) => quote.fee_reserve < chosen_quote.fee_reserve,
+            };
+            if is_better {
+                if let Some((_, dropped)) = chosen.take() {
+                    let _res = self.store.remove_melt_quote(&dropped.id).await;
+                }
+                chosen = Some((wallet, quote));
+            } else {
+                let _res = self.store.remove_melt_quote(&quote.id).await;
+            }
+            if strategy != MintSelection::LowestFee {
+                break;
+            }
+        }
+        let (wallet, quote) = chosen.ok_or(last_error)?;
+        self.melt_quote_mints
+            .insert(quote.id.clone(), wallet.mint_url.clone());
+        Ok(MeltQuotePreview {
+            quote_id: quote.id,
+            mint_url: wallet.mint_url.to_string(),
+            amount: quote.amount.into(),
+            fee_reserve: quote.fee_reserve.into(),
+            expiry: quote.expiry,
+            invoice: InvoiceInfo::from_invoice(&invoice),
+        })
+    }
+
+    /// Pay a melt quote prepared with `melt_quote_start`.
+    /// The result contains the actual fee paid, and the change returned from the fee reserve.
+    pub async fn melt_confirm(&mut self, quote_id: &str) -> Result<MeltResult, PkError> {
+        let mint_url = self
+            .melt_quote_mints
+            .get(quote_id)
+            .cloned()
+            .ok_or_else(|| PkError::InvalidInput(format!("Unknown melt quote {}", quote_id)))?;
+        let quote = self
+            .store
+            .get_melt_quote(quote_id)
+            .await?
+            .ok_or_else(|| PkError::InvalidInput(format!("Unknown melt quote {}", quote_id)))?;
+        if quote.expiry != 0 && quote.expiry < unix_time() {
+            self.melt_cancel(quote_id).await?;
+            return Err(PkError::QuoteExpired);
+        }
+        let wallet = self.get_mint_wallet(mint_url.clone()).await?;
+
+        let ln_invoice = quote.request.clone();
+        let res = self
+            .melt_with_quote(&wallet, &quote, &ln_invoice, None)
+            .await;
+        self.melt_quote_mints.remove(quote_id);
+        let (amount, fee, change) = res?;
+        Ok(MeltResult {
+            amount,
+            fee,
+            change,
+            mint_url: mint_url.to_string(),
+        })
+    }
 
-            /*
-            // Check the quote state in a loop with a timeout
-            let timeout = Duration::from_secs(60); // Set a timeout duration
-            let start = std::time::Instant::now();
+    /// Drop a melt quote prepared with `melt_quote_start`, without paying it
+    pub async fn melt_cancel(&mut self, quote_id: &str) -> Result<(), PkError> {
+        self.melt_quote_mints.remove(quote_id);
+        self.store.remove_melt_quote(quote_id).await?;
+        Ok(())
+    }
 
-            loop {
-                let status = wallet.melt_quote_status(&quote.id).await?;
-                println!("status {:?}", status);
+    /// Melt (pay) the melt quote with the wallet, recording it in the history.
+    /// Returns the amount sent, the fee paid, and the change returned.
+    async fn melt_with_quote(
+        &mut self,
+        wallet: &Wallet,
+        quote: &cdk::wallet::MeltQuote,
+        ln_invoice: &str,
+        memo: Option<&str>,
+    ) -> Result<(u64, u64, u64), PkError> {
+        let mut tx = Transaction::new(
+            TxDirection::Outgoing,
+            TxKind::Lightning,
+            quote.amount.into(),
+            quote.fee_reserve.into(),
+            &wallet.mint_url.to_string(),
+            ln_invoice,
+            TxStatus::Pending,
+        );
+        if let Some(memo) = memo {
+            tx = tx.with_memo(memo);
+        }
+        let tx_id = self.record_transaction(tx);
+        // Melt the sent amount
+        let melted = match wallet.melt(&quote.id).await {
+            Ok(melted) => melted,
+            Err(e) => {
+                let status = match e {
+                    cdk::Error::PaymentPending => TxStatus::Pending,
+                    _ => TxStatus::Failed,
+                };
+                self.update_transaction(tx_id, status, quote.amount.into(), 0);
+                return Err(e.into());
+            }
+        };
+        let status = match melted.state {
+            MeltQuoteState::Paid => TxStatus::Completed,
+            MeltQuoteState::Pending | MeltQuoteState::Unknown => TxStatus::Pending,
+            _ => TxStatus::Failed,
+        };
+        self.update_transaction(tx_id, status, melted.amount.into(), melted.fee_paid.into());
+        let change = melted
+            .change
+            .and_then(|proofs| proofs.total_amount().ok())
+            .unwrap_or_default();
+        Ok((melted.amount.into(), melted.fee_paid.into(), change.into()))
+    }
 
-                if status.state == MeltQuoteState::Paid {
-                    break;
+    /// Pay a Lightning invoice from several mints, with a multi-path payment (NUT-15),
+    /// for the case when no single mint has enough funds. Only mints supporting NUT-15 are used,
+    /// the ones with larger balance first. The parts are quoted first (nothing is paid if
+    /// the funds are not enough), then melted concurrently. The result has the outcome of
+    /// each part; the invoice is paid only if all parts are paid, see `MppMeltResult::is_paid`.
+    /// Each part is recorded in the history.
+    pub async fn melt_to_ln_mpp(&mut self, ln_invoice: &str) -> Result<MppMeltResult, PkError> {
+        let invoice = Bolt11Invoice::from_str(ln_invoice)
+            .map_err(|e| PkError::InvalidInvoice(e.to_string()))?;
+        let msat_per_unit = match self.unit {
+            CurrencyUnit::Sat => 1000,
+            CurrencyUnit::Msat => 1,
+            _ => {
+                return Err(PkError::InvalidInput(format!(
+                    "Multi-path payment is not supported with unit {}",
+                    self.unit
+                )))
+            }
+        };
+        let invoice_msat = invoice
+            .amount_milli_satoshis()
+            .ok_or_else(|| PkError::InvalidInvoice("no amount in invoice".to_owned()))?;
+        let mut remaining = invoice_msat.div_ceil(msat_per_unit);
+
+        // The (trusted) mints supporting MPP, with the largest balance first
+        let mut candidates = Vec::new();
+        for (wallet, balance) in self.funded_wallets().await? {
+            let supports_mpp = match self.store.get_mint(wallet.mint_url.clone()).await? {
+                Some(info) => info
+                    .nuts
+                    .nut15
+                    .methods
+                    .iter()
+                    .any(|m| m.method == PaymentMethod::Bolt11 && m.unit == self.unit),
+                None => false,
+            };
+            if supports_mpp {
+                candidates.push((wallet, balance));
+            }
+        }
+
+        // Quote the parts
+        let mut parts = Vec::new();
+        for (wallet, balance) in candidates {
+            if remaining == 0 {
+                break;
+            }
+            let mut part = remaining.min(balance);
+            let mut quote = None;
+            // The fee reserve is known only from the quote, retry with a smaller part if needed
+            for _ in 0..2 {
+                let options = MeltOptions::new_mpp(part * msat_per_unit);
+                match wallet
+                    .melt_quote(ln_invoice.to_string(), Some(options))
+                    .await
+                {
+                    Ok(q) => {
+                        let fee_reserve: u64 = q.fee_reserve.into();
+                        if part + fee_reserve <= balance {
+                            quote = Some(q);
+                            break;
+                        }
+                        let _res = self.store.remove_melt_quote(&q.id).await;
+                        part = balance.saturating_sub(fee_reserve).min(remaining);
+                        if part == 0 {
+                            break;
+                        }
+                    }
+                    Err(e) => {
+                        eprintln!("Could not get MPP quote from {}, {}", wallet.mint_url, e);
+                        break;
+                    }
+                }
+            }
+            if let Some(quote) = quote {
+                remaining -= part;
+                parts.push((wallet, quote));
+            }
+        }
+        if remaining > 0 {
+            for (_, quote) in &parts {
+                let _res = self.store.remove_melt_quote(&quote.id).await;
+            }
+            return Err(PkError::InsufficientBalance);
+        }
+
+        // Melt the parts concurrently
+        let part_count = parts.len();
+        let mut tx_ids = Vec::new();
+        for (i, (wallet, quote)) in parts.iter().enumerate() {
+            tx_ids.push(
+                self.record_transaction(
+                    Transaction::new(
+                        TxDirection::Outgoing,
+                        TxKind::Lightning,
+                        quote.amount.into(),
+                        quote.fee_reserve.into(),
+                        &wallet.mint_url.to_string(),
+                        ln_invoice,
+                        TxStatus::Pending,
+                    )
+                    .with_memo(&format!(
+                        "Multi-path payment, part {}/{}",
+                        i + 1,
+                        part_count
+                    )),
+                ),
+            );
+        }
+        let results = futures::future::join_all(
+            parts
+                .iter()
+                .map(|(wallet, quote)| async move { wallet.melt(&quote.id).await }),
+        )
+        .await;
+
+        let mut mpp_parts = Vec::new();
+        for (((wallet, quote), tx_id), res) in parts.iter().zip(tx_ids).zip(results) {
+            let mut part = MppPart {
+                mint_url: wallet.mint_url.to_string(),
+                quote_id: quote.id.clone(),
+                state: MppPartState::Failed,
+                amount: quote.amount.into(),
+                fee: 0,
+                change: 0,
+                error: None,
+            };
+            match res {
+                Ok(melted) if melted.state == MeltQuoteState::Paid => {
+                    part.state = MppPartState::Paid;
+                    part.amount = melted.amount.into();
+                    part.fee = melted.fee_paid.into();
+                    part.change = melted
+                        .change
+                        .and_then(|proofs| proofs.total_amount().ok())
+                        .unwrap_or_default()
+                        .into();
                 }
-                if start.elapsed() >= timeout {
-                    return Err("Timeout while waiting for mint quote to be paid".into());
+                Ok(melted) => {
+                    part.state = match melted.state {
+                        MeltQuoteState::Pending | MeltQuoteState::Unknown => MppPartState::Pending,
+                        _ => MppPartState::Failed,
+                    };
+                    part.error = Some(format!("payment {}", melted.state));
+                }
+                Err(e) => {
+                    if matches!(e, cdk::Error::PaymentPending) {
+                        part.state = MppPartState::Pending;
+                    }
+                    part.error = Some(e.to_string());
                 }
+            }
+            let status = match part.state {
+                MppPartState::Paid => TxStatus::Completed,
+                MppPartState::Pending => TxStatus::Pending,
+                MppPartState::Failed => TxStatus::Failed,
+            };
+            self.update_transaction(tx_id, status, part.amount, part.fee);
+            mpp_parts.push(part);
+        }
+        let paid_parts = || mpp_parts.iter().filter(|p| p.state == MppPartState::Paid);
+        Ok(MppMeltResult {
+            amount: paid_parts().map(|p| p.amount).sum(),
+            fee: paid_parts().map(|p| p.fee).sum(),
+            parts: mpp_parts,
+        })
+    }
 
-                println!("Quote state: {}", status.state);
-                sleep(Duration::from_millis(1500)).await;
+    /// Prepare a transfer of funds from one mint to another, via Lightning: the target mint
+    /// creates an invoice for the amount, and the source mint quotes the payment of it.
+    /// Returns the quote, with the fee reserve as fee preview; it should be executed with
+    /// `transfer_execute`, or dropped with `transfer_cancel`.
+    pub async fn transfer_quote(
+        &mut self,
+        from_mint: &str,
+        to_mint: &str,
+        amount_sats: u64,
+    ) -> Result<TransferQuote, PkError> {
+        let from_url = MintUrl::from_str(from_mint)?;
+        let to_url = MintUrl::from_str(to_mint)?;
+        if from_url == to_url {
+            return Err(PkError::InvalidInput(
+                "The source and target mints are the same".to_owned(),
+            ));
+        }
+        if amount_sats == 0 {
+            return Err(PkError::InvalidInput("Amount must be positive".to_owned()));
+        }
+        let from_wallet = self.get_mint_wallet(from_url).await?;
+        let to_wallet = self.get_mint_wallet(to_url).await?;
+
+        let quote = self
+            .quote_transfer(&from_wallet, &to_wallet, amount_sats)
+            .await?;
+        let from_balance: u64 = from_wallet.total_balance().await?.into();
+        if from_balance < quote.amount + quote.fee_reserve {
+            self.transfer_cancel(quote).await?;
+            return Err(PkError::InsufficientBalance);
+        }
+        Ok(quote)
+    }
+
+    /// Get the quotes of a transfer: mint quote from the target, melt quote from the source.
+    /// The balance is not checked.
+    async fn quote_transfer(
+        &mut self,
+        from_wallet: &Wallet,
+        to_wallet: &Wallet,
+        amount_sats: u64,
+    ) -> Result<TransferQuote, PkError> {
+        let mint_quote = to_wallet
+            .mint_quote(Amount::from(amount_sats), None)
+            .await?;
+        let melt_quote = match from_wallet
+            .melt_quote(mint_quote.request.clone(), None)
+            .await
+        {
+            Ok(melt_quote) => melt_quote,
+            Err(e) => {
+                let _res = self.store.remove_mint_quote(&mint_quote.id).await;
+                return Err(e.into());
             }
-            */
+        };
+        Ok(TransferQuote {
+            from_mint: from_wallet.mint_url.to_string(),
+            to_mint: to_wallet.mint_url.to_string(),
+            amount: amount_sats,
+            fee_reserve: melt_quote.fee_reserve.into(),
+            melt_quote: Box::new(melt_quote),
+            receive: Box::new(MintFromLnIntermediaryResult::new(mint_quote, 0)),
+        })
+    }
 
-            // Melt the sent amount
-            let melted = wallet.melt(&quote.id).await.map_err(|e| e.to_string())?;
-            Ok(melted.amount.into())
+    /// Execute a transfer prepared with `transfer_quote`: pay the invoice of the target mint
+    /// from the source mint, and mint the ecash with the target mint.
+    /// Both sides are recorded in the history. Once the payment has been made the transfer
+    /// succeeds; the target mint is checked once, and if the receive is not complete yet
+    /// (e.g. the payment is not settled yet, or the target mint is not reachable), it is
+    /// returned in `pending_receive`, to be polled. It is also persisted, and can be claimed
+    /// later with `claim_pending`.
+    pub async fn transfer_execute(
+        &mut self,
+        quote: TransferQuote,
+    ) -> Result<TransferResult, PkError> {
+        let from_wallet = self
+            .get_mint_wallet(MintUrl::from_str(&quote.from_mint)?)
+            .await?;
+        let mut receive = quote.receive;
+        let invoice = receive.mint_quote.request.clone();
+        receive.tx_id = self.record_transaction(
+            Transaction::new(
+                TxDirection::Incoming,
+                TxKind::Lightning,
+                quote.amount,
+                0,
+                &quote.to_mint,
+                &invoice,
+                TxStatus::Pending,
+            )
+            .with_memo(&format!("Transfer from {}", quote.from_mint)),
+        );
+
+        let memo = format!("Transfer to {}", quote.to_mint);
+        let (amount_sent, fee_paid, _change) = match self
+            .melt_with_quote(&from_wallet, &quote.melt_quote, &invoice, Some(&memo))
+            .await
+        {
+            Ok(sent) => sent,
+            Err(e) => {
+                // If the payment has surely not been made, the receive is dropped,
+                // otherwise it remains pending
+                if let Ok(status) = from_wallet.melt_quote_status(&quote.melt_quote.id).await {
+                    if status.state == MeltQuoteState::Unpaid
+                        || status.state == MeltQuoteState::Failed
+                    {
+                        self.store.remove_mint_quote(&receive.mint_quote.id).await?;
+                        self.update_transaction(receive.tx_id, TxStatus::Failed, quote.amount, 0);
+                    }
+                }
+                return Err(e);
+            }
+        };
+
+        let receive = *receive;
+        let checked = match self.mint_from_ln_check(receive.clone()).await {
+            Ok(checked) => checked,
+            Err(e) => {
+                eprintln!(
+                    "Could not complete the receive with {}, {}",
+                    quote.to_mint, e
+                );
+                receive
+            }
+        };
+        let (amount_received, pending_receive) = match checked.paid_result {
+            Some(Ok(amount)) => (amount, None),
+            _ => (0, Some(Box::new(checked))),
+        };
+        Ok(TransferResult {
+            from_mint: quote.from_mint,
+            to_mint: quote.to_mint,
+            amount_sent,
+            fee_paid,
+            amount_received,
+            pending_receive,
+        })
+    }
+
+    /// Drop a transfer prepared with `transfer_quote`, without executing it
+    pub async fn transfer_cancel(&mut self, quote: TransferQuote) -> Result<(), PkError> {
+        self.store
+            .remove_mint_quote(&quote.receive.mint_quote.id)
+            .await?;
+        self.store.remove_melt_quote(&quote.melt_quote.id).await?;
+        Ok(())
+    }
+
+    /// Transfer funds from one mint to another, via Lightning, see `transfer_quote` and
+    /// `transfer_execute`. The fee is at most the fee reserve of the source mint.
+    pub async fn transfer_between_mints(
+        &mut self,
+        from_mint: &str,
+        to_mint: &str,
+        amount_sats: u64,
+    ) -> Result<TransferResult, PkError> {
+        let quote = self.transfer_quote(from_mint, to_mint, amount_sats).await?;
+        self.transfer_execute(quote).await
+    }
+
+    /// Restore ecash from the seed (NUT-09): for each mint, for each keyset, the mint is asked
+    /// to re-sign the deterministic (NUT-13) blinded messages, the spent state of the
+    /// recovered proofs is checked, and the unspent ones are stored.
+    /// - `mints`: the mints to restore from; all known mints if empty. Unknown mints are added.
+    /// - `progress_callback`: called before and after each mint.
+    ///
+    /// A mint failing does not stop the restore from the other mints.
+    pub async fn restore_from_seed<F: FnMut(&RestoreProgress)>(
+        &mut self,
+        mints: &[String],
+        mut progress_callback: F,
+    ) -> Result<RestoreSummary, PkError> {
+        let mint_urls: Vec<String> = if mints.is_empty() {
+            self.multi_mint_wallet
+                .get_wallets()
+                .await
+                .iter()
+                .map(|w| w.mint_url.to_string())
+                .collect()
         } else {
-            Err("No selected mint!".to_owned())
+            mints.to_vec()
+        };
+        if mint_urls.is_empty() {
+            return Err(PkError::InvalidInput(
+                "No mints to restore from, add or specify a mint".to_owned(),
+            ));
+        }
+
+        let mint_count = mint_urls.len();
+        let mut summary = RestoreSummary::default();
+        for (i, mint_url) in mint_urls.iter().enumerate() {
+            let mut progress = RestoreProgress {
+                mint_url: mint_url.clone(),
+                mint_index: i + 1,
+                mint_count,
+                restored: None,
+            };
+            (progress_callback)(&progress);
+            let res = self.restore_from_seed_mint(mint_url).await;
+            if let Ok(restored) = res {
+                summary.total_restored += restored;
+            }
+            progress.restored = Some(res.clone());
+            (progress_callback)(&progress);
+            summary.mints.push((mint_url.clone(), res));
+        }
+
+        if self.selected_mint.is_none() {
+            let _res = self.select_mint_by_index(1).await;
+        }
+        Ok(summary)
+    }
+
+    async fn restore_from_seed_mint(&mut self, mint_url_str: &str) -> Result<u64, PkError> {
+        let mint_url = MintUrl::from_str(mint_url_str)?;
+        let known_wallet = self.get_mint_wallet(mint_url).await.ok();
+        let wallet = match known_wallet {
+            Some(wallet) => wallet,
+            None => self.add_mint_wallet(mint_url_str).await?,
+        };
+        let restored: u64 = wallet.restore().await?.into();
+        if restored > 0 {
+            self.record_transaction(
+                Transaction::new(
+                    TxDirection::Incoming,
+                    TxKind::Ecash,
+                    restored,
+                    0,
+                    &wallet.mint_url.to_string(),
+                    "",
+                    TxStatus::Completed,
+                )
+                .with_memo("Restored from seed"),
+            );
+        }
+        Ok(restored)
+    }
+
+    /// Consolidate the proofs of a mint (the selected mint if None): swap all the unspent
+    /// proofs into the smallest set of denominations, or with `target_value`, into proofs
+    /// of that value (see `SplitTarget::Value`). The input fee of the mint is deducted.
+    /// Nothing is swapped if the proofs are already optimal.
+    pub async fn consolidate(
+        &mut self,
+        mint_url_str: Option<&str>,
+        target_value: Option<u64>,
+    ) -> Result<ConsolidateResult, PkError> {
+        let mint_url = match mint_url_str {
+            Some(mint_url_str) => MintUrl::from_str(mint_url_str)?,
+            None => self.selected_mint.clone().ok_or(PkError::NoSelectedMint)?,
+        };
+        let wallet = self.get_mint_wallet(mint_url).await?;
+        self.consolidate_wallet(&wallet, target_value).await
+    }
+
+    async fn consolidate_wallet(
+        &mut self,
+        wallet: &Wallet,
+        target_value: Option<u64>,
+    ) -> Result<ConsolidateResult, PkError> {
+        // Proofs with spending conditions are left alone, they cannot be swapped as is
+        let proofs: Proofs = self
+            .store
+            .get_proofs(
+                Some(wallet.mint_url.clone()),
+                Some(self.unit.clone()),
+                Some(vec![State::Unspent]),
+                None,
+            )
+            .await?
+            .into_iter()
+            .filter(|p| p.spending_condition.is_none())
+            .map(|p| p.proof)
+            .collect();
+        let total = proofs.total_amount()?;
+        let mut result = ConsolidateResult {
+            mint_url: wallet.mint_url.to_string(),
+            proofs_before: proofs.len(),
+            proofs_after: proofs.len(),
+            amount: total.into(),
+            fee: 0,
+        };
+
+        let split_target = match target_value {
+            Some(value) => SplitTarget::Value(Amount::from(value)),
+            None => {
+                let mut amounts: Vec<Amount> = proofs.iter().map(|p| p.amount).collect();
+                amounts.sort();
+                let mut optimal = total.split();
+                optimal.sort();
+                if amounts == optimal {
+                    // already optimal
+                    return Ok(result);
+                }
+                // the change target is set below, after the fee
+                SplitTarget::None
+            }
+        };
+        let fee = wallet.get_proofs_fee(&proofs).await?;
+        if fee >= total {
+            return Err(PkError::InsufficientBalance);
+        }
+        let split_target = match split_target {
+            SplitTarget::None => SplitTarget::Values((total - fee).split()),
+            split_target => split_target,
+        };
+
+        wallet.swap(None, split_target, proofs, None, false).await?;
+        result.fee = fee.into();
+        result.proofs_after = wallet.get_unspent_proofs().await?.len();
+        if result.fee > 0 {
+            self.record_transaction(
+                Transaction::new(
+                    TxDirection::Outgoing,
+                    TxKind::Ecash,
+                    0,
+                    result.fee,
+                    &result.mint_url,
+                    "",
+                    TxStatus::Completed,
+                )
+                .with_memo("Consolidation"),
+            );
+        }
+        Ok(result)
+    }
+
+    /// Consolidate the proofs of the mint after a receive, if it holds more proofs than
+    /// configured (`auto_consolidate_proofs`). Failing is not an error of the receive.
+    async fn auto_consolidate(&mut self, wallet: &Wallet) {
+        let limit = self.config.auto_consolidate_proofs;
+        if limit == 0 {
+            return;
+        }
+        match wallet.get_unspent_proofs().await {
+            Ok(proofs) if proofs.len() > limit => {
+                if let Err(e) = self.consolidate_wallet(wallet, None).await {
+                    eprintln!("Error consolidating proofs, {}", e);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// The keysets of the mint in the store, as last retrieved from the mint
+    async fn stored_keysets(&self, mint_url: &MintUrl) -> Result<Vec<KeysetInfo>, PkError> {
+        let keysets = self
+            .store
+            .get_mint_keysets(mint_url.clone())
+            .await?
+            .unwrap_or_default();
+        Ok(keysets
+            .into_iter()
+            .filter(|k| k.unit == self.unit)
+            .map(|k| KeysetInfo {
+                id: k.id.to_string(),
+                active: k.active,
+                input_fee_ppk: k.input_fee_ppk,
+            })
+            .collect())
+    }
+
+    /// The unspent proofs of the mint from inactive keysets (without spending conditions)
+    async fn inactive_keyset_proofs(&self, mint_url: &MintUrl) -> Result<Proofs, PkError> {
+        let inactive_ids: Vec<String> = self
+            .stored_keysets(mint_url)
+            .await?
+            .into_iter()
+            .filter(|k| !k.active)
+            .map(|k| k.id)
+            .collect();
+        if inactive_ids.is_empty() {
+            return Ok(Proofs::new());
+        }
+        Ok(self
+            .store
+            .get_proofs(
+                Some(mint_url.clone()),
+                Some(self.unit.clone()),
+                Some(vec![State::Unspent]),
+                None,
+            )
+            .await?
+            .into_iter()
+            .filter(|p| {
+                p.spending_condition.is_none()
+                    && inactive_ids.contains(&p.proof.keyset_id.to_string())
+            })
+            .map(|p| p.proof)
+            .collect())
+    }
+
+    /// Retrieve the keysets of all the mints, to detect keysets deactivated by the mints.
+    /// Returns the info of the mints, see `MintInfo::inactive_balance`.
+    /// A mint failing does not stop the others, its previous keysets are kept.
+    pub async fn refresh_keysets(&self) -> Result<Vec<MintInfo>, PkError> {
+        for wallet in self.multi_mint_wallet.get_wallets().await {
+            if let Err(e) = wallet.get_mint_keysets().await {
+                eprintln!(
+                    "Error retrieving keysets of mint {}, {}",
+                    wallet.mint_url, e
+                );
+            }
+        }
+        self.get_mints_info().await
+    }
+
+    /// Migrate the proofs from inactive keysets into the active keyset, by swapping them,
+    /// with the given mint, or with all mints if None. The input fee of the mint is deducted.
+    pub async fn migrate_inactive_keysets(
+        &mut self,
+        mint_url_str: Option<&str>,
+    ) -> Result<KeysetMigrationSummary, PkError> {
+        let wallets = match mint_url_str {
+            Some(mint_url_str) => vec![
+                self.get_mint_wallet(MintUrl::from_str(mint_url_str)?)
+                    .await?,
+            ],
+            None => self.multi_mint_wallet.get_wallets().await,
+        };
+        let mut summary = KeysetMigrationSummary::default();
+        for wallet in wallets {
+            let res = self.migrate_mint_keysets(&wallet).await;
+            if let Ok(migration) = &res {
+                summary.amount += migration.amount;
+                summary.fee += migration.fee;
+            }
+            summary.mints.push((wallet.mint_url.to_string(), res));
+        }
+        Ok(summary)
+    }
+
+    async fn migrate_mint_keysets(&mut self, wallet: &Wallet) -> Result<KeysetMigration, PkError> {
+        // have the latest keyset states
+        wallet.get_mint_keysets().await?;
+        let proofs = self.inactive_keyset_proofs(&wallet.mint_url).await?;
+        if proofs.is_empty() {
+            return Ok(KeysetMigration::default());
+        }
+        let total = proofs.total_amount()?;
+        let fee = wallet.get_proofs_fee(&proofs).await?;
+        if fee >= total {
+            return Err(PkError::InsufficientBalance);
+        }
+        let migration = KeysetMigration {
+            proof_count: proofs.len(),
+            amount: total.into(),
+            fee: fee.into(),
+        };
+        // the new proofs are always from the active keyset
+        wallet
+            .swap(None, SplitTarget::default(), proofs, None, false)
+            .await?;
+        if migration.fee > 0 {
+            self.record_transaction(
+                Transaction::new(
+                    TxDirection::Outgoing,
+                    TxKind::Ecash,
+                    0,
+                    migration.fee,
+                    &wallet.mint_url.to_string(),
+                    "",
+                    TxStatus::Completed,
+                )
+                .with_memo("Keyset migration"),
+            );
+        }
+        Ok(migration)
+    }
+
+    /// The proofs stored in the wallet, of the given mint, or of all mints if None.
+    /// Only the local store is read, the mints are not contacted.
+    pub async fn list_proofs(
+        &self,
+        mint_url_str: Option<&str>,
+    ) -> Result<Vec<MintProofs>, PkError> {
+        let mint_urls: Vec<MintUrl> = match mint_url_str {
+            Some(mint_url_str) => {
+                let mint_url = MintUrl::from_str(mint_url_str)?;
+                // check that the mint is known
+                let _wallet = self.get_mint_wallet(mint_url.clone()).await?;
+                vec![mint_url]
+            }
+            None => self
+                .multi_mint_wallet
+                .get_wallets()
+                .await
+                .iter()
+                .map(|w| w.mint_url.clone())
+                .collect(),
+        };
+
+        let mut mints = Vec::new();
+        for mint_url in mint_urls {
+            let mut proof_infos = self
+                .store
+                .get_proofs(Some(mint_url.clone()), Some(self.unit.clone()), None, None)
+                .await?;
+            proof_infos.sort_by_key(|p| std::cmp::Reverse(p.proof.amount));
+            let keysets = self.stored_keysets(&mint_url).await?;
+            let mut denominations = BTreeMap::new();
+            let mut proofs = Vec::new();
+            for proof_info in proof_infos {
+                let amount: u64 = proof_info.proof.amount.into();
+                if proof_info.state == State::Unspent {
+                    *denominations.entry(amount).or_insert(0) += 1;
+                }
+                let keyset_id = proof_info.proof.keyset_id.to_string();
+                proofs.push(ProofDetail {
+                    keyset_active: keysets
+                        .iter()
+                        .find(|k| k.id == keyset_id)
+                        .is_none_or(|k| k.active),
+                    keyset_id,
+                    amount,
+                    state: proof_info.state.to_string().to_lowercase(),
+                    spending_condition: proof_info
+                        .spending_condition
+                        .as_ref()
+                        .map(describe_spending_conditions),
+                    has_dleq: proof_info.proof.dleq.is_some(),
+                });
+            }
+            mints.push(MintProofs {
+                mint_url: mint_url.to_string(),
+                keysets,
+                proofs,
+                denominations,
+            });
+        }
+        Ok(mints)
+    }
+
+    /// Check the state of the stored proofs with their mints (NUT-07), and clean up:
+    /// remove the spent ones, and release the ones stuck as pending or reserved by
+    /// interrupted operations. Proofs of sent tokens (pending spent) are not checked,
+    /// see `check_pending_tokens`. A mint failing does not stop the others.
+    /// Pending or reserved proofs found unspent at the mint are all released, so this must
+    /// not run while a melt (or multi-path payment) is in progress: its proofs are pending,
+    /// and still unspent until the mint processes them. `PKAppAsync` runs one request
+    /// at a time, so this holds there.
+    pub async fn check_wallet_health(&mut self) -> Result<WalletHealthReport, PkError> {
+        let mut report = WalletHealthReport::default();
+        for wallet in self.multi_mint_wallet.get_wallets().await {
+            let res = self.check_mint_health(&wallet).await;
+            if let Ok(health) = &res {
+                report.fixed_amount += health.fixed_amount;
+                report.resolved_amount += health.resolved_amount;
+                report.lost_amount += health.lost_amount;
+            }
+            report.mints.push((wallet.mint_url.to_string(), res));
+        }
+        Ok(report)
+    }
+
+    async fn check_mint_health(&self, wallet: &Wallet) -> Result<MintHealth, PkError> {
+        // Not the proofs of sent tokens: the ones found spent would be removed
+        // without the token being marked as claimed
+        let proofs = self
+            .store
+            .get_proofs(
+                Some(wallet.mint_url.clone()),
+                Some(self.unit.clone()),
+                Some(vec![State::Unspent, State::Pending, State::Reserved]),
+                None,
+            )
+            .await?;
+        let mut health = MintHealth {
+            proof_count: proofs.len(),
+            ..Default::default()
+        };
+        if proofs.is_empty() {
+            return Ok(health);
+        }
+
+        // This also removes the spent proofs from the store
+        let mint_states: HashMap<_, _> = wallet
+            .check_proofs_spent(proofs.iter().map(|p| p.proof.clone()).collect())
+            .await?
+            .into_iter()
+            .map(|s| (s.y, s.state))
+            .collect();
+        let mut to_release = Vec::new();
+        for proof_info in &proofs {
+            let amount: u64 = proof_info.proof.amount.into();
+            match (proof_info.state, mint_states.get(&proof_info.y)) {
+                (State::Unspent, Some(State::Spent)) => health.lost_amount += amount,
+                (State::Pending | State::Reserved, Some(State::Spent)) => {
+                    health.resolved_amount += amount
+                }
+                (State::Pending | State::Reserved, Some(State::Unspent)) => {
+                    health.fixed_amount += amount;
+                    to_release.push(proof_info.y);
+                }
+                _ => {}
+            }
+        }
+        if !to_release.is_empty() {
+            self.store
+                .update_proofs_state(to_release, State::Unspent)
+                .await?;
+        }
+        Ok(health)
+    }
+
+    /// Check the sent tokens not claimed yet by the recipient: ask the mint about the state
+    /// of their proofs (NUT-07), and mark the ones fully spent as completed.
+    /// Tokens whose mint cannot be reached stay pending.
+    pub async fn check_pending_tokens(&mut self) -> Result<PendingTokens, PkError> {
+        let mut newly_claimed = 0;
+        for tx in self.history.list(&pending_tokens_filter()) {
+            match self.is_token_claimed(&tx.reference).await {
+                Ok(true) => {
+                    self.update_transaction(tx.id, TxStatus::Completed, tx.amount, tx.fee);
+                    newly_claimed += 1;
+                }
+                Ok(false) => {}
+                Err(e) => eprintln!("Error checking sent token {}, {}", tx.id, e),
+            }
+        }
+        Ok(PendingTokens {
+            tokens: self.history.list(&pending_tokens_filter()),
+            newly_claimed,
+        })
+    }
+
+    /// Check if there are sent tokens not claimed yet (without contacting the mint)
+    pub fn has_pending_tokens(&self) -> bool {
+        let mut filter = pending_tokens_filter();
+        filter.limit = Some(1);
+        !self.history.list(&filter).is_empty()
+    }
+
+    /// Check if all the proofs of a token have been spent
+    async fn is_token_claimed(&self, token_str: &str) -> Result<bool, PkError> {
+        let token = Token::from_str(token_str)?;
+        let wallet = self.get_mint_wallet(token.mint_url()?).await?;
+        let states = wallet.check_proofs_spent(token.proofs()).await?;
+        Ok(states.iter().all(|s| s.state == State::Spent))
+    }
+
+    /// Reclaim a sent token not claimed yet by the recipient: swap its unspent proofs
+    /// back into the wallet. Returns the amount reclaimed (less the swap fee).
+    /// If the recipient has already claimed the token, it is marked as completed.
+    pub async fn reclaim_token(&mut self, tx_id: u64) -> Result<u64, PkError> {
+        let tx = match self.history.get(tx_id) {
+            Some(tx) if tx.direction == TxDirection::Outgoing && tx.kind == TxKind::Ecash => {
+                tx.clone()
+            }
+            _ => {
+                return Err(PkError::InvalidInput(format!(
+                    "Transaction {} is not a sent token",
+                    tx_id
+                )))
+            }
+        };
+        if tx.status != TxStatus::Pending {
+            return Err(PkError::InvalidInput(format!(
+                "Sent token {} is not pending ({})",
+                tx_id, tx.status
+            )));
+        }
+
+        let token = Token::from_str(&tx.reference)?;
+        let wallet = self.get_mint_wallet(token.mint_url()?).await?;
+        let proofs = token.proofs();
+        let states = wallet.check_proofs_spent(proofs.clone()).await?;
+        let unspent: Proofs = proofs
+            .into_iter()
+            .zip(states)
+            .filter_map(|(p, s)| (s.state == State::Unspent).then_some(p))
+            .collect();
+        if unspent.is_empty() {
+            self.update_transaction(tx.id, TxStatus::Completed, tx.amount, tx.fee);
+            return Err(PkError::TokenAlreadySpent);
+        }
+
+        let balance_before: u64 = wallet.total_balance().await?.into();
+        wallet.reclaim_unspent(unspent).await?;
+        let balance_after: u64 = wallet.total_balance().await?.into();
+        let reclaimed = balance_after.saturating_sub(balance_before);
+        self.update_transaction(tx.id, TxStatus::Reclaimed, tx.amount, tx.fee);
+        Ok(reclaimed)
+    }
+
+    /// The transactions from the history matching the filter, newest first
+    pub fn list_transactions(&self, filter: &HistoryFilter) -> Result<Vec<Transaction>, PkError> {
+        Ok(self.history.list(filter))
+    }
+
+    /// Add a transaction to the history, returns its ID.
+    /// Failing to save the history does not fail the operation, the transaction is then
+    /// not recorded, and 0 is returned.
+    fn record_transaction(&mut self, tx: Transaction) -> u64 {
+        match self.history.add(tx) {
+            Ok(id) => id,
+            Err(e) => {
+                eprintln!("Error saving transaction history, {}", e);
+                0
+            }
+        }
+    }
+
+    fn update_transaction(&mut self, tx_id: u64, status: TxStatus, amount: u64, fee: u64) {
+        if let Err(e) = self.history.update(tx_id, status, amount, fee) {
+            eprintln!("Error saving transaction history, {}", e);
         }
     }
 
     pub fn get_recommended_mint_list() -> Vec<(String, String)> {
-        return vec![
+        vec![
             (
                 "https://mint.minibits.cash/Bitcoin".to_owned(),
                 "MiniBits".to_owned(),
@@ -538,12 +2594,354 @@ impl PKApp {
                 "https://cashu.mutinynet.com".to_owned(),
                 "Test, on MutinyNet)".to_owned(),
             ),
-        ];
+        ]
+    }
+}
+
+/// Short description of spending conditions (NUT-10), e.g. the P2PK lock
+fn describe_spending_conditions(conditions: &SpendingConditions) -> String {
+    match conditions {
+        SpendingConditions::P2PKConditions { data, .. } => format!("P2PK, locked to {}", data),
+        SpendingConditions::HTLCConditions { data, .. } => format!("HTLC, hash lock {}", data),
+    }
+}
+
+/// Filter for the sent tokens not claimed yet by the recipient
+fn pending_tokens_filter() -> HistoryFilter {
+    HistoryFilter {
+        direction: Some(TxDirection::Outgoing),
+        kind: Some(TxKind::Ecash),
+        status: Some(TxStatus::Pending),
+        ..Default::default()
+    }
+}
+
+impl InvoiceInfo {
+    pub fn from_invoice(invoice: &Bolt11Invoice) -> Self {
+        let description = match invoice.description() {
+            Bolt11InvoiceDescription::Direct(description) => description.to_string(),
+            Bolt11InvoiceDescription::Hash(hash) => format!("(hash {})", hash.0),
+        };
+        let payee = invoice
+            .payee_pub_key()
+            .cloned()
+            .unwrap_or_else(|| invoice.recover_payee_pub_key());
+        Self {
+            amount_msat: invoice.amount_milli_satoshis(),
+            description,
+            payee: payee.to_string(),
+            payment_hash: invoice.payment_hash().to_string(),
+            expiry: (invoice.duration_since_epoch() + invoice.expiry_time()).as_secs(),
+            network: invoice.network().to_string(),
+        }
+    }
+}
+
+impl PaymentRequestInfo {
+    /// Decode a payment request (`creqA...`)
+    pub fn parse(request: &str) -> Result<Self, PkError> {
+        let request = PaymentRequest::from_str(request.trim())
+            .map_err(|e| PkError::InvalidInput(format!("Invalid payment request, {}", e)))?;
+        Ok(Self {
+            amount: request.amount.map(u64::from),
+            unit: request.unit.map(|unit| unit.to_string()),
+            mints: request
+                .mints
+                .unwrap_or_default()
+                .iter()
+                .map(|mint_url| mint_url.to_string())
+                .collect(),
+            description: request.description,
+        })
     }
 }
 
 impl MintFromLnIntermediaryResult {
+    fn new(mint_quote: cdk::wallet::MintQuote, tx_id: u64) -> Self {
+        Self {
+            mint_url: mint_quote.mint_url.clone(),
+            unit: mint_quote.unit.clone(),
+            mint_quote,
+            tx_id,
+            paid_result: None,
+        }
+    }
+
     pub fn id(&self) -> String {
         self.mint_quote.id.clone()
     }
+
+    /// The mint which issued the quote
+    pub fn mint_url(&self) -> String {
+        self.mint_url.to_string()
+    }
+
+    /// Expiry time of the quote, in Unix secs, 0 if unknown
+    pub fn expiry(&self) -> u64 {
+        self.mint_quote.expiry
+    }
+}
+
+/// Check if the mint quote has expired (quotes without an expiry do not expire)
+fn is_quote_expired(mint_quote: &cdk::wallet::MintQuote, now: u64) -> bool {
+    mint_quote.expiry != 0 && mint_quote.expiry <= now
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pk_app_async::AppEvent;
+    use crate::pk_test_mint::FakeMint;
+
+    const MINT_A: &str = "https://mint-a.example.com";
+    const MINT_B: &str = "https://mint-b.example.com";
+
+    /// A new wallet in a new temp data dir, without mints; `seed` sets the seed bytes
+    async fn new_test_app(name: &str, seed: u8) -> PKApp {
+        let data_dir =
+            std::env::temp_dir().join(format!("parakesh-app-test-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&data_dir);
+        std::fs::create_dir_all(&data_dir).unwrap();
+        let config = PKAppConfig {
+            data_dir,
+            ..Default::default()
+        };
+        let seedstore = SeedStoreCreator::new_from_data(&vec![seed; 16], None, None).unwrap();
+        PKApp::new_from_seedstore(&config, seedstore).await.unwrap()
+    }
+
+    /// Add a mint to the app, served by the fake mint
+    async fn add_fake_mint(app: &mut PKApp, mint_url: &str, mint: &FakeMint) {
+        let wallet = WalletBuilder::new()
+            .mint_url(MintUrl::from_str(mint_url).unwrap())
+            .unit(app.unit.clone())
+            .localstore(app.store.clone())
+            .seed(&app.get_seed().unwrap())
+            .client(mint.clone())
+            .build()
+            .unwrap();
+        wallet.get_mint_info().await.unwrap();
+        app.multi_mint_wallet.add_wallet(wallet).await;
+    }
+
+    async fn mint_balance(app: &PKApp, mint_url: &str) -> u64 {
+        app.get_mint_wallet(MintUrl::from_str(mint_url).unwrap())
+            .await
+            .unwrap()
+            .total_balance()
+            .await
+            .unwrap()
+            .into()
+    }
+
+    /// Receive the amount with the selected mint, paying the invoice at the fake mint
+    async fn fund(app: &mut PKApp, mint: &FakeMint, amount: u64) {
+        let (_invoice, pending) = app.mint_from_ln_start(amount).await.unwrap();
+        mint.pay_mint_quote(&pending.mint_quote.id);
+        let pending = app.mint_from_ln_check(pending).await.unwrap();
+        assert_eq!(pending.paid_result.unwrap().unwrap(), amount);
+    }
+
+    #[tokio::test]
+    async fn pending_receive_stays_with_its_mint_after_select_mint() {
+        let mut app = new_test_app("pending-receive", 1).await;
+        let mint_a = FakeMint::new(1);
+        let mint_b = FakeMint::new(2);
+        add_fake_mint(&mut app, MINT_A, &mint_a).await;
+        add_fake_mint(&mut app, MINT_B, &mint_b).await;
+
+        app.select_mint(MINT_A).await.unwrap();
+        let (invoice, pending) = app.mint_from_ln_start(100).await.unwrap();
+        app.select_mint(MINT_B).await.unwrap();
+        let mint_b_requests = mint_b.request_count();
+
+        // not paid yet: keeps polling mint A
+        let pending = app.mint_from_ln_check(pending).await.unwrap();
+        assert!(pending.paid_result.is_none());
+
+        mint_a.pay_mint_quote(&pending.mint_quote.id);
+        let pending = app.mint_from_ln_check(pending).await.unwrap();
+        assert_eq!(pending.paid_result.unwrap().unwrap(), 100);
+
+        assert_eq!(mint_balance(&app, MINT_A).await, 100);
+        assert_eq!(mint_balance(&app, MINT_B).await, 0);
+        assert_eq!(mint_b.request_count(), mint_b_requests);
+        assert_eq!(
+            app.selected_mint(),
+            MintUrl::from_str(MINT_B).unwrap().to_string()
+        );
+
+        let tx_id = app.history.find_by_reference(&invoice).unwrap();
+        let tx = app.history.get(tx_id).unwrap();
+        assert_eq!(tx.status, TxStatus::Completed);
+        assert_eq!(tx.amount, 100);
+        assert_eq!(tx.mint_url, MintUrl::from_str(MINT_A).unwrap().to_string());
+    }
+
+    #[tokio::test]
+    async fn sent_token_v3_and_v4_round_trip() {
+        let mint = FakeMint::new(1);
+        let mut sender = new_test_app("send-v3-v4", 1).await;
+        add_fake_mint(&mut sender, MINT_A, &mint).await;
+        sender.select_mint(MINT_A).await.unwrap();
+        fund(&mut sender, &mint, 100).await;
+
+        let sent = sender.send_ecash(21).await.unwrap();
+        assert_eq!(sent.amount, 21);
+        assert!(sent.token_v3.starts_with("cashuA"));
+        assert!(sent.token_v4.starts_with("cashuB"));
+        // V4 is the default format
+        assert_eq!(sent.token, sent.token_v4);
+
+        let token_v3 = Token::from_str(&sent.token_v3).unwrap();
+        let token_v4 = Token::from_str(&sent.token_v4).unwrap();
+        assert_eq!(token_v3.proofs(), token_v4.proofs());
+        assert_eq!(token_v3.mint_url().unwrap(), token_v4.mint_url().unwrap());
+        assert_eq!(token_v3.mint_url().unwrap().to_string(), sent.mint_url);
+        assert_eq!(token_v3.unit(), Some(CurrencyUnit::Sat));
+        assert_eq!(token_v4.unit(), Some(CurrencyUnit::Sat));
+        assert_eq!(u64::from(token_v4.value().unwrap()), 21);
+        // re-encoding gives the same strings
+        assert_eq!(token_v4.to_string(), sent.token_v4);
+        assert_eq!(token_v4.to_v3_string(), sent.token_v3);
+
+        // the event carries both formats
+        let json: serde_json::Value =
+            serde_json::from_str(&AppEvent::SendECRes(Ok(sent.clone())).to_json()).unwrap();
+        assert_eq!(json["token"], sent.token_v4.as_str());
+        assert_eq!(json["token_v3"], sent.token_v3.as_str());
+        assert_eq!(json["token_v4"], sent.token_v4.as_str());
+
+        // both formats can be received
+        // (the fake mint does not reject spent proofs, so the same proofs can be received twice)
+        for (i, token) in [&sent.token_v3, &sent.token_v4].into_iter().enumerate() {
+            let mut recipient = new_test_app(&format!("receive-v3-v4-{}", i), 2 + i as u8).await;
+            add_fake_mint(&mut recipient, MINT_A, &mint).await;
+            assert_eq!(recipient.receive_ecash(token, false).await.unwrap(), 21);
+            assert_eq!(mint_balance(&recipient, MINT_A).await, 21);
+        }
+    }
+
+    #[tokio::test]
+    async fn sent_token_in_v3_format() {
+        let mint = FakeMint::new(1);
+        let mut app = new_test_app("send-v3", 1).await;
+        app.config.token_format = TokenFormat::V3;
+        add_fake_mint(&mut app, MINT_A, &mint).await;
+        app.select_mint(MINT_A).await.unwrap();
+        fund(&mut app, &mint, 10).await;
+
+        let sent = app.send_ecash(4).await.unwrap();
+        assert_eq!(sent.token, sent.token_v3);
+        let tx_id = app.history.find_by_reference(&sent.token_v3).unwrap();
+        assert_eq!(app.history.get(tx_id).unwrap().amount, 4);
+    }
+
+    #[tokio::test]
+    async fn health_check_leaves_sent_tokens_alone() {
+        let mint = FakeMint::new(1);
+        let mut app = new_test_app("health-sent-token", 1).await;
+        add_fake_mint(&mut app, MINT_A, &mint).await;
+        app.select_mint(MINT_A).await.unwrap();
+        fund(&mut app, &mint, 100).await;
+        let mint_url = MintUrl::from_str(MINT_A).unwrap();
+
+        let sent = app.send_ecash(21).await.unwrap();
+        let mut recipient = new_test_app("health-sent-token-recipient", 2).await;
+        add_fake_mint(&mut recipient, MINT_A, &mint).await;
+        recipient.receive_ecash(&sent.token, false).await.unwrap();
+
+        // a proof left reserved by an interrupted operation
+        let unspent = app
+            .store
+            .get_proofs(
+                Some(mint_url.clone()),
+                None,
+                Some(vec![State::Unspent]),
+                None,
+            )
+            .await
+            .unwrap();
+        let stuck = &unspent[0];
+        app.store
+            .update_proofs_state(vec![stuck.y], State::Reserved)
+            .await
+            .unwrap();
+
+        let report = app.check_wallet_health().await.unwrap();
+        assert_eq!(report.fixed_amount, u64::from(stuck.proof.amount));
+        assert_eq!(report.resolved_amount, 0);
+        assert_eq!(report.lost_amount, 0);
+
+        // the spent proofs of the sent token are still there, for the token check
+        let pending_spent = app
+            .store
+            .get_proofs(Some(mint_url), None, Some(vec![State::PendingSpent]), None)
+            .await
+            .unwrap();
+        assert!(!pending_spent.is_empty());
+        let pending = app.check_pending_tokens().await.unwrap();
+        assert_eq!(pending.newly_claimed, 1);
+        assert!(pending.tokens.is_empty());
+        let tx_id = app.history.find_by_reference(&sent.token).unwrap();
+        assert_eq!(app.history.get(tx_id).unwrap().status, TxStatus::Completed);
+    }
+
+    #[tokio::test]
+    async fn send_for_payment_request_uses_an_accepted_mint() {
+        let mint_a = FakeMint::new(1);
+        let mint_b = FakeMint::new(2);
+        let mut app = new_test_app("payment-request", 1).await;
+        add_fake_mint(&mut app, MINT_A, &mint_a).await;
+        add_fake_mint(&mut app, MINT_B, &mint_b).await;
+        app.select_mint(MINT_B).await.unwrap();
+        fund(&mut app, &mint_b, 50).await;
+        app.select_mint(MINT_A).await.unwrap();
+        fund(&mut app, &mint_a, 50).await;
+
+        let request = PaymentRequest::builder()
+            .amount(20)
+            .unit(CurrencyUnit::Sat)
+            .add_mint(MintUrl::from_str("https://unknown.example.com").unwrap())
+            .add_mint(MintUrl::from_str(MINT_B).unwrap())
+            .description("coffee")
+            .build()
+            .to_string();
+        let info = PaymentRequestInfo::parse(&request).unwrap();
+        assert_eq!(info.amount, Some(20));
+        assert_eq!(info.mints.len(), 2);
+        assert_eq!(info.description.as_deref(), Some("coffee"));
+
+        // from mint B, though mint A is selected
+        let sent = app.send_ecash_for_request(&request, None).await.unwrap();
+        assert_eq!(sent.amount, 20);
+        assert_eq!(
+            sent.mint_url,
+            MintUrl::from_str(MINT_B).unwrap().to_string()
+        );
+        assert_eq!(mint_balance(&app, MINT_A).await, 50);
+        assert_eq!(mint_balance(&app, MINT_B).await, 30);
+
+        // not enough at the accepted mints
+        let request = PaymentRequest::builder()
+            .amount(40)
+            .add_mint(MintUrl::from_str(MINT_B).unwrap())
+            .build()
+            .to_string();
+        assert!(app.send_ecash_for_request(&request, None).await.is_err());
+
+        // without amount in the request, the given one is used
+        let request = PaymentRequest::builder().build().to_string();
+        assert!(app.send_ecash_for_request(&request, None).await.is_err());
+        let sent = app.send_ecash_for_request(&request, Some(5)).await.unwrap();
+        assert_eq!(sent.amount, 5);
+
+        let request = PaymentRequest::builder()
+            .amount(5)
+            .unit(CurrencyUnit::Usd)
+            .build()
+            .to_string();
+        assert!(app.send_ecash_for_request(&request, None).await.is_err());
+        assert!(PaymentRequestInfo::parse("creqAinvalid").is_err());
+    }
 }
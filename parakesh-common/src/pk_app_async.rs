@@ -1,34 +1,471 @@
-use crate::pk_app::{BalanceInfo, MintFromLnIntermediaryResult, MintInfo, PKApp, WalletInfo};
+use crate::pk_app::{
+    BalanceInfo, ConsolidateResult, DecodedInput, InvoiceInfo, KeysetMigrationSummary,
+    MeltQuotePreview, MeltResult, MintFromLnIntermediaryResult, MintInfo, MintProofs,
+    MppMeltResult, MultiSendResult, PKApp, PendingTokens, RestoreProgress, RestoreSummary,
+    SendEcashResult, TransferQuote, TransferResult, WalletHealthReport, WalletInfo,
+};
+use crate::pk_config::PKAppConfig;
+use crate::pk_error::PkError;
+use crate::pk_history::{HistoryFilter, Transaction};
 use futures::channel::mpsc::{self, Receiver, Sender};
 use futures::task::AtomicWaker;
 use futures::{stream, SinkExt, Stream, StreamExt};
+use serde::ser::{Serialize, SerializeMap, Serializer};
 use std::collections::HashMap;
+use std::fmt;
 use std::pin::Pin;
 use std::sync::{Arc, RwLock};
 use std::task::{Context, Poll};
-use std::time::{Duration, SystemTime};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 /// Events delivered to the callback.
-#[derive(Clone, Debug)]
+#[derive(Clone)]
 pub enum AppEvent {
-    WalletInfo(Result<WalletInfo, String>),
-    BalanceChange(Result<BalanceInfo, String>),
-    BalanceAndWalletInfo(Result<(BalanceInfo, WalletInfo), String>),
-    MintsInfo(Result<Vec<MintInfo>, String>),
-    MintSelectedByUrl(Result<String, String>),
-    MintSelectedByIndex(Result<usize, String>),
-    MintAdded(Result<(), String>),
+    /// Result of opening, initializing or restoring the wallet
+    WalletOpened(Result<(), PkError>),
+    /// The seed as mnemonic (seed phrase), for backup
+    SeedMnemonic(Result<String, PkError>),
+    /// The 1-based indices of the words to ask in the seed backup verification
+    SeedVerifyQuiz(Result<Vec<usize>, PkError>),
+    /// Result of the seed backup verification, true if all the words matched
+    SeedVerifyResult(Result<bool, PkError>),
+    WalletInfo(Result<WalletInfo, PkError>),
+    BalanceChange(Result<BalanceInfo, PkError>),
+    BalanceAndWalletInfo(Result<(BalanceInfo, WalletInfo), PkError>),
+    MintsInfo(Result<Vec<MintInfo>, PkError>),
+    MintSelectedByUrl(Result<String, PkError>),
+    MintSelectedByIndex(Result<usize, PkError>),
+    MintAdded(Result<(), PkError>),
     MintFromLnInvoice(String),
-    MintFromLnRes(Result<u64, String>),
-    ReceivedEC(Result<u64, String>),
-    MeltToLnRes(Result<u64, String>),
-    SendECRes(Result<(u64, String), String>),
+    MintFromLnRes(Result<u64, PkError>),
+    ReceivedEC(Result<u64, PkError>),
+    /// Melt quote for paying an invoice, with the fee preview
+    MeltQuoteRes(Result<MeltQuotePreview, PkError>),
+    MeltToLnRes(Result<MeltResult, PkError>),
+    /// Result of a multi-path Lightning payment, from several mints
+    MeltToLnMppRes(Result<MppMeltResult, PkError>),
+    SendECRes(Result<SendEcashResult, PkError>),
+    /// Result of sending ecash from several mints, with a token per mint
+    SendECMultiRes(Result<MultiSendResult, PkError>),
+    /// Progress of restore from seed, before and after each mint
+    RestoreProgress(RestoreProgress),
+    RestoreRes(Result<RestoreSummary, PkError>),
+    /// Transactions from the history, newest first
+    History(Result<Vec<Transaction>, PkError>),
+    /// Result of claiming the pending Lightning receives, the amount received
+    ClaimPendingRes(Result<u64, PkError>),
+    /// A prepared transfer between mints, with the fee preview
+    TransferQuoteRes(Result<TransferQuote, PkError>),
+    /// Result of a transfer between mints
+    TransferRes(Result<TransferResult, PkError>),
+    /// A decoded invoice or token
+    DecodedInput(Result<DecodedInput, PkError>),
+    /// The sent tokens not claimed yet by the recipient, after checking with the mints
+    PendingTokens(Result<PendingTokens, PkError>),
+    /// Result of reclaiming a sent token, the amount reclaimed
+    ReclaimRes(Result<u64, PkError>),
+    /// Result of the wallet health check, with the cleanup done
+    WalletHealth(Result<WalletHealthReport, PkError>),
+    /// The proofs stored in the wallet, per mint
+    Proofs(Result<Vec<MintProofs>, PkError>),
+    /// Result of consolidating the proofs of a mint
+    ConsolidateRes(Result<ConsolidateResult, PkError>),
+    /// The mints info, after retrieving the keysets from the mints,
+    /// see `MintInfo::inactive_balance`
+    KeysetsRefreshed(Result<Vec<MintInfo>, PkError>),
+    /// Result of migrating the proofs off inactive keysets
+    MigrateKeysetsRes(Result<KeysetMigrationSummary, PkError>),
+}
+
+/// Only the name is shown, as events may carry secrets (mnemonic, token)
+impl fmt::Debug for AppEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "AppEvent::{}", self.name())
+    }
+}
+
+impl AppEvent {
+    /// Name of the event, as used in the JSON output
+    pub fn name(&self) -> &'static str {
+        match self {
+            AppEvent::WalletOpened(_) => "wallet_opened",
+            AppEvent::SeedMnemonic(_) => "seed_mnemonic",
+            AppEvent::SeedVerifyQuiz(_) => "seed_verify_quiz",
+            AppEvent::SeedVerifyResult(_) => "seed_verify_result",
+            AppEvent::WalletInfo(_) => "wallet_info",
+            AppEvent::BalanceChange(_) => "balance",
+            AppEvent::BalanceAndWalletInfo(_) => "balance_and_wallet_info",
+            AppEvent::MintsInfo(_) => "mints_info",
+            AppEvent::MintSelectedByUrl(_) => "mint_selected",
+            AppEvent::MintSelectedByIndex(_) => "mint_selected",
+            AppEvent::MintAdded(_) => "mint_added",
+            AppEvent::MintFromLnInvoice(_) => "receive_ln_invoice",
+            AppEvent::MintFromLnRes(_) => "receive_ln",
+            AppEvent::ReceivedEC(_) => "receive_ecash",
+            AppEvent::MeltQuoteRes(_) => "send_ln_quote",
+            AppEvent::MeltToLnRes(_) => "send_ln",
+            AppEvent::MeltToLnMppRes(_) => "send_ln_mpp",
+            AppEvent::SendECRes(_) => "send_ecash",
+            AppEvent::SendECMultiRes(_) => "send_ecash_multi",
+            AppEvent::RestoreProgress(_) => "restore_progress",
+            AppEvent::RestoreRes(_) => "restore",
+            AppEvent::History(_) => "history",
+            AppEvent::ClaimPendingRes(_) => "claim_pending",
+            AppEvent::TransferQuoteRes(_) => "transfer_quote",
+            AppEvent::TransferRes(_) => "transfer",
+            AppEvent::DecodedInput(_) => "decode",
+            AppEvent::PendingTokens(_) => "pending_tokens",
+            AppEvent::ReclaimRes(_) => "reclaim",
+            AppEvent::WalletHealth(_) => "wallet_health",
+            AppEvent::Proofs(_) => "proofs",
+            AppEvent::ConsolidateRes(_) => "consolidate",
+            AppEvent::KeysetsRefreshed(_) => "keysets",
+            AppEvent::MigrateKeysetsRes(_) => "migrate_keysets",
+        }
+    }
+
+    /// Check if the event carries a successful result (or no result)
+    pub fn is_ok(&self) -> bool {
+        self.error().is_none()
+    }
+
+    /// The error carried by the event, if any
+    pub fn error(&self) -> Option<&PkError> {
+        match self {
+            AppEvent::WalletOpened(res) | AppEvent::MintAdded(res) => res.as_ref().err(),
+            AppEvent::SeedMnemonic(res) => res.as_ref().err(),
+            AppEvent::SeedVerifyQuiz(res) => res.as_ref().err(),
+            AppEvent::SeedVerifyResult(res) => res.as_ref().err(),
+            AppEvent::WalletInfo(res) => res.as_ref().err(),
+            AppEvent::BalanceChange(res) => res.as_ref().err(),
+            AppEvent::BalanceAndWalletInfo(res) => res.as_ref().err(),
+            AppEvent::MintsInfo(res) => res.as_ref().err(),
+            AppEvent::MintSelectedByUrl(res) => res.as_ref().err(),
+            AppEvent::MintSelectedByIndex(res) => res.as_ref().err(),
+            AppEvent::MintFromLnInvoice(_) => None,
+            AppEvent::MintFromLnRes(res)
+            | AppEvent::ReceivedEC(res)
+            | AppEvent::ClaimPendingRes(res)
+            | AppEvent::ReclaimRes(res) => res.as_ref().err(),
+            AppEvent::MeltQuoteRes(res) => res.as_ref().err(),
+            AppEvent::MeltToLnRes(res) => res.as_ref().err(),
+            AppEvent::MeltToLnMppRes(res) => res.as_ref().err(),
+            AppEvent::SendECRes(res) => res.as_ref().err(),
+            AppEvent::SendECMultiRes(res) => res.as_ref().err(),
+            AppEvent::RestoreProgress(progress) => match &progress.restored {
+                Some(Err(err)) => Some(err),
+                _ => None,
+            },
+            AppEvent::RestoreRes(res) => res.as_ref().err(),
+            AppEvent::History(res) => res.as_ref().err(),
+            AppEvent::TransferQuoteRes(res) => res.as_ref().err(),
+            AppEvent::TransferRes(res) => res.as_ref().err(),
+            AppEvent::DecodedInput(res) => res.as_ref().err(),
+            AppEvent::PendingTokens(res) => res.as_ref().err(),
+            AppEvent::WalletHealth(res) => res.as_ref().err(),
+            AppEvent::Proofs(res) => res.as_ref().err(),
+            AppEvent::ConsolidateRes(res) => res.as_ref().err(),
+            AppEvent::KeysetsRefreshed(res) => res.as_ref().err(),
+            AppEvent::MigrateKeysetsRes(res) => res.as_ref().err(),
+        }
+    }
+
+    /// The event as a single-line JSON object, see `Serialize`
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_else(|e| {
+            format!(
+                "{{\"event\":\"{}\",\"ok\":false,\"error_kind\":\"other\",\"error\":\"JSON error {}\"}}",
+                self.name(),
+                e
+            )
+        })
+    }
+}
+
+/// Serialize into a flat object, with the fields:
+/// - `event`: the event name, see `AppEvent::name`
+/// - `ok`: true or false
+/// - `error_kind`: the error kind, if not ok, see `PkError::kind`
+/// - `error`: the error message, if not ok
+/// - the result values, with event-specific names, e.g. `amount`, `token`, `invoice`, `mint_url`
+impl Serialize for AppEvent {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("event", self.name())?;
+        match self {
+            AppEvent::WalletOpened(res) | AppEvent::MintAdded(res) => {
+                serialize_result(&mut map, res, |_, _| Ok(()))?
+            }
+            AppEvent::SeedMnemonic(res) => serialize_result(&mut map, res, |map, mnemonic| {
+                map.serialize_entry("mnemonic", mnemonic)
+            })?,
+            AppEvent::SeedVerifyQuiz(res) => serialize_result(&mut map, res, |map, indices| {
+                map.serialize_entry("word_indices", indices)
+            })?,
+            AppEvent::SeedVerifyResult(res) => serialize_result(&mut map, res, |map, verified| {
+                map.serialize_entry("verified", verified)
+            })?,
+            AppEvent::WalletInfo(res) => serialize_result(&mut map, res, |map, wallet_info| {
+                map.serialize_entry("wallet", wallet_info)
+            })?,
+            AppEvent::BalanceChange(res) => serialize_result(&mut map, res, |map, balance| {
+                map.serialize_entry("balance", balance)
+            })?,
+            AppEvent::BalanceAndWalletInfo(res) => {
+                serialize_result(&mut map, res, |map, (balance, wallet_info)| {
+                    map.serialize_entry("balance", balance)?;
+                    map.serialize_entry("wallet", wallet_info)
+                })?
+            }
+            AppEvent::MintsInfo(res) | AppEvent::KeysetsRefreshed(res) => {
+                serialize_result(&mut map, res, |map, mints| {
+                    map.serialize_entry("mints", mints)
+                })?
+            }
+            AppEvent::MigrateKeysetsRes(res) => serialize_result(&mut map, res, |map, summary| {
+                map.serialize_entry("amount", &summary.amount)?;
+                map.serialize_entry("fee", &summary.fee)?;
+                let mints: Vec<serde_json::Value> = summary
+                    .mints
+                    .iter()
+                    .map(|(mint_url, res)| match res {
+                        Ok(migration) => serde_json::json!({
+                            "mint_url": mint_url, "ok": true,
+                            "proof_count": migration.proof_count,
+                            "amount": migration.amount, "fee": migration.fee,
+                        }),
+                        Err(err) => serde_json::json!({
+                            "mint_url": mint_url, "ok": false,
+                            "error_kind": err.kind(), "error": err.to_string(),
+                        }),
+                    })
+                    .collect();
+                map.serialize_entry("mints", &mints)
+            })?,
+            AppEvent::MintSelectedByUrl(res) => serialize_result(&mut map, res, |map, url| {
+                map.serialize_entry("mint_url", url)
+            })?,
+            AppEvent::MintSelectedByIndex(res) => serialize_result(&mut map, res, |map, index| {
+                map.serialize_entry("mint_index", index)
+            })?,
+            AppEvent::MintFromLnInvoice(invoice) => {
+                map.serialize_entry("ok", &true)?;
+                map.serialize_entry("invoice", invoice)?;
+            }
+            AppEvent::MintFromLnRes(res)
+            | AppEvent::ReceivedEC(res)
+            | AppEvent::ClaimPendingRes(res)
+            | AppEvent::ReclaimRes(res) => serialize_result(&mut map, res, |map, amount| {
+                map.serialize_entry("amount", amount)
+            })?,
+            AppEvent::MeltQuoteRes(res) => serialize_result(&mut map, res, |map, quote| {
+                map.serialize_entry("quote_id", &quote.quote_id)?;
+                map.serialize_entry("mint_url", &quote.mint_url)?;
+                map.serialize_entry("amount", &quote.amount)?;
+                map.serialize_entry("fee_reserve", &quote.fee_reserve)?;
+                map.serialize_entry("expiry", &quote.expiry)?;
+                map.serialize_entry("invoice", &invoice_info_json(&quote.invoice))
+            })?,
+            AppEvent::MeltToLnRes(res) => serialize_result(&mut map, res, |map, melted| {
+                map.serialize_entry("amount", &melted.amount)?;
+                map.serialize_entry("fee", &melted.fee)?;
+                map.serialize_entry("change", &melted.change)?;
+                map.serialize_entry("mint_url", &melted.mint_url)
+            })?,
+            AppEvent::MeltToLnMppRes(res) => serialize_result(&mut map, res, |map, melted| {
+                map.serialize_entry("paid", &melted.is_paid())?;
+                map.serialize_entry("amount", &melted.amount)?;
+                map.serialize_entry("fee", &melted.fee)?;
+                let parts: Vec<serde_json::Value> = melted
+                    .parts
+                    .iter()
+                    .map(|part| {
+                        serde_json::json!({
+                            "mint_url": part.mint_url, "quote_id": part.quote_id,
+                            "state": part.state, "amount": part.amount, "fee": part.fee,
+                            "change": part.change, "error": part.error,
+                        })
+                    })
+                    .collect();
+                map.serialize_entry("parts", &parts)
+            })?,
+            AppEvent::SendECRes(res) => serialize_result(&mut map, res, |map, sent| {
+                map.serialize_entry("amount", &sent.amount)?;
+                map.serialize_entry("fee", &sent.fee)?;
+                map.serialize_entry("mint_url", &sent.mint_url)?;
+                map.serialize_entry("token", &sent.token)?;
+                map.serialize_entry("token_v3", &sent.token_v3)?;
+                map.serialize_entry("token_v4", &sent.token_v4)
+            })?,
+            AppEvent::SendECMultiRes(res) => serialize_result(&mut map, res, |map, sent| {
+                map.serialize_entry("amount", &sent.amount)?;
+                map.serialize_entry("fee", &sent.fee)?;
+                let tokens: Vec<serde_json::Value> = sent
+                    .tokens
+                    .iter()
+                    .map(|t| {
+                        serde_json::json!({
+                            "mint_url": t.mint_url, "amount": t.amount, "fee": t.fee,
+                            "token": t.token, "token_v3": t.token_v3, "token_v4": t.token_v4,
+                        })
+                    })
+                    .collect();
+                map.serialize_entry("tokens", &tokens)
+            })?,
+            AppEvent::RestoreProgress(progress) => {
+                map.serialize_entry("mint_url", &progress.mint_url)?;
+                map.serialize_entry("mint_index", &progress.mint_index)?;
+                map.serialize_entry("mint_count", &progress.mint_count)?;
+                map.serialize_entry("done", &progress.restored.is_some())?;
+                match &progress.restored {
+                    None => map.serialize_entry("ok", &true)?,
+                    Some(res) => serialize_result(&mut map, res, |map, amount| {
+                        map.serialize_entry("amount", amount)
+                    })?,
+                }
+            }
+            AppEvent::RestoreRes(res) => serialize_result(&mut map, res, |map, summary| {
+                map.serialize_entry("amount", &summary.total_restored)?;
+                let mints: Vec<serde_json::Value> = summary
+                    .mints
+                    .iter()
+                    .map(|(mint_url, res)| match res {
+                        Ok(amount) => serde_json::json!({
+                            "mint_url": mint_url, "ok": true, "amount": amount,
+                        }),
+                        Err(err) => serde_json::json!({
+                            "mint_url": mint_url, "ok": false,
+                            "error_kind": err.kind(), "error": err.to_string(),
+                        }),
+                    })
+                    .collect();
+                map.serialize_entry("mints", &mints)
+            })?,
+            AppEvent::History(res) => serialize_result(&mut map, res, |map, transactions| {
+                map.serialize_entry("transactions", transactions)
+            })?,
+            AppEvent::PendingTokens(res) => serialize_result(&mut map, res, |map, pending| {
+                map.serialize_entry("transactions", &pending.tokens)?;
+                map.serialize_entry("newly_claimed", &pending.newly_claimed)
+            })?,
+            AppEvent::WalletHealth(res) => serialize_result(&mut map, res, |map, report| {
+                map.serialize_entry("fixed_amount", &report.fixed_amount)?;
+                map.serialize_entry("resolved_amount", &report.resolved_amount)?;
+                map.serialize_entry("lost_amount", &report.lost_amount)?;
+                let mints: Vec<serde_json::Value> = report
+                    .mints
+                    .iter()
+                    .map(|(mint_url, res)| match res {
+                        Ok(health) => serde_json::json!({
+                            "mint_url": mint_url, "ok": true,
+                            "proof_count": health.proof_count,
+                            "fixed_amount": health.fixed_amount,
+                            "resolved_amount": health.resolved_amount,
+                            "lost_amount": health.lost_amount,
+                        }),
+                        Err(err) => serde_json::json!({
+                            "mint_url": mint_url, "ok": false,
+                            "error_kind": err.kind(), "error": err.to_string(),
+                        }),
+                    })
+                    .collect();
+                map.serialize_entry("mints", &mints)
+            })?,
+            AppEvent::Proofs(res) => serialize_result(&mut map, res, |map, mints| {
+                map.serialize_entry("mints", mints)
+            })?,
+            AppEvent::ConsolidateRes(res) => serialize_result(&mut map, res, |map, result| {
+                map.serialize_entry("mint_url", &result.mint_url)?;
+                map.serialize_entry("amount", &result.amount)?;
+                map.serialize_entry("fee", &result.fee)?;
+                map.serialize_entry("proofs_before", &result.proofs_before)?;
+                map.serialize_entry("proofs_after", &result.proofs_after)
+            })?,
+            AppEvent::TransferQuoteRes(res) => serialize_result(&mut map, res, |map, quote| {
+                map.serialize_entry("from_mint", &quote.from_mint)?;
+                map.serialize_entry("to_mint", &quote.to_mint)?;
+                map.serialize_entry("amount", &quote.amount)?;
+                map.serialize_entry("fee_reserve", &quote.fee_reserve)
+            })?,
+            AppEvent::TransferRes(res) => serialize_result(&mut map, res, |map, transfer| {
+                map.serialize_entry("from_mint", &transfer.from_mint)?;
+                map.serialize_entry("to_mint", &transfer.to_mint)?;
+                map.serialize_entry("amount", &transfer.amount_sent)?;
+                map.serialize_entry("fee", &transfer.fee_paid)?;
+                map.serialize_entry("amount_received", &transfer.amount_received)?;
+                map.serialize_entry("receive_pending", &transfer.pending_receive.is_some())
+            })?,
+            AppEvent::DecodedInput(res) => {
+                serialize_result(&mut map, res, |map, decoded| match decoded {
+                    DecodedInput::Invoice(invoice) => {
+                        map.serialize_entry("type", "invoice")?;
+                        map.serialize_entry("invoice", &invoice_info_json(invoice))
+                    }
+                    DecodedInput::Token(token) => {
+                        map.serialize_entry("type", "token")?;
+                        let token = serde_json::json!({
+                            "mint_urls": token.mint_urls,
+                            "unit": token.unit,
+                            "amount": token.amount,
+                            "memo": token.memo,
+                            "proof_count": token.proof_count,
+                            "spending_conditions": token.spending_conditions,
+                            "mint_known": token.mint_known,
+                        });
+                        map.serialize_entry("token", &token)
+                    }
+                })?
+            }
+        }
+        map.end()
+    }
+}
+
+fn invoice_info_json(invoice: &InvoiceInfo) -> serde_json::Value {
+    serde_json::json!({
+        "amount_msat": invoice.amount_msat,
+        "description": invoice.description,
+        "payee": invoice.payee,
+        "payment_hash": invoice.payment_hash,
+        "expiry": invoice.expiry,
+        "network": invoice.network,
+    })
+}
+
+/// Serialize a result into `ok`, `error_kind` and `error` fields, and the fields written by `ok_fields`
+fn serialize_result<M: SerializeMap, T>(
+    map: &mut M,
+    res: &Result<T, PkError>,
+    ok_fields: impl FnOnce(&mut M, &T) -> Result<(), M::Error>,
+) -> Result<(), M::Error> {
+    match res {
+        Ok(value) => {
+            map.serialize_entry("ok", &true)?;
+            ok_fields(map, value)
+        }
+        Err(err) => {
+            map.serialize_entry("ok", &false)?;
+            map.serialize_entry("error_kind", err.kind())?;
+            map.serialize_entry("error", &err.to_string())
+        }
+    }
 }
 
 /// Requests, used internally to pass requests to processing thread.
-#[derive(Clone, Debug)]
+#[derive(Clone)]
 pub enum AppRequest {
     InitApp(Sender<AppEvent>),
+    /// Open existing wallet, with the password
+    OpenWallet(String),
+    /// Initialize a new wallet, with a new seed, encrypted with the password
+    InitNewWallet(String),
+    /// Restore wallet from the mnemonic, encrypted with the password
+    RestoreWallet(String, String),
+    GetSeedMnemonic,
+    /// Start seed backup verification, with the number of words to ask
+    SeedVerifyQuiz(usize),
+    /// Check the words for seed backup verification, (1-based index, word) pairs
+    SeedVerifyCheck(Vec<(usize, String)>),
     GetWalletInfo,
     GetBalance,
     GetBalanceAndWalletInfo,
@@ -37,14 +474,121 @@ pub enum AppRequest {
     SelectMintByIndex(usize),
     AddMint(String),
     MintFromLn(u64),
-    ReceiveEC(String),
+    /// Receive an ecash token; the flag is set to trust (add) the mint of the token if unknown
+    ReceiveEC(String, bool),
+    /// Pay the invoice, without confirmation of the fee
     MeltToLn(String),
+    /// Request a melt quote for paying the invoice, for the fee preview
+    MeltQuote(String),
+    /// Pay a melt quote, by its ID
+    MeltConfirm(String),
+    /// Drop a melt quote, by its ID, no event is sent
+    MeltCancel(String),
+    /// Pay the invoice from several mints, with a multi-path payment
+    MeltToLnMpp(String),
     SendEC(u64),
+    /// Send ecash from several mints; the flag is set to consolidate into one mint first
+    SendECMulti(u64, bool),
+    /// Send ecash for a payment request, with the amount if the request has none
+    SendECForRequest(String, Option<u64>),
+    /// Restore ecash from seed, from the given mints (all known mints if empty)
+    RestoreFromSeed(Vec<String>),
+    /// Get transactions from the history
+    GetHistory(HistoryFilter),
+    /// Claim the pending Lightning receives
+    ClaimPending,
+    /// Decode an invoice or token
+    DecodeInput(String),
+    /// Check the sent tokens not claimed yet, the list is always sent
+    CheckPendingTokens,
+    /// Periodic check of the sent tokens not claimed yet, the list is sent only if changed
+    CheckPendingTokensPeriodic,
+    /// Reclaim a sent token not claimed yet, by its transaction ID
+    ReclaimToken(u64),
+    /// Check the state of all the stored proofs with the mints, and clean up
+    CheckWalletHealth,
+    /// List the stored proofs, of the given mint, or of all mints
+    ListProofs(Option<String>),
+    /// Consolidate the proofs of a mint (the selected one if None),
+    /// optimally, or into proofs of the given value
+    Consolidate(Option<String>, Option<u64>),
+    /// Retrieve the keysets from the mints, to detect inactive ones; no-op without wallet
+    RefreshKeysets,
+    /// Migrate the proofs off inactive keysets, of a mint, or of all mints if None
+    MigrateKeysets(Option<String>),
+    /// Prepare a transfer between mints (from, to, amount), for the fee preview
+    TransferQuote(String, String, u64),
+    /// Execute a prepared transfer between mints
+    Transfer(TransferQuote),
+    /// Drop a prepared transfer between mints, no event is sent
+    TransferCancel(TransferQuote),
+    /// Transfer between mints (from, to, amount), without confirmation of the fee
+    TransferBetweenMints(String, String, u64),
     /// A poll to execute
     Poll(MintFromLnIntermediaryResult),
 }
 
+impl AppRequest {
+    /// Name of the request, e.g. for logging
+    pub fn name(&self) -> &'static str {
+        match self {
+            AppRequest::InitApp(..) => "init_app",
+            AppRequest::OpenWallet(..) => "open_wallet",
+            AppRequest::InitNewWallet(..) => "init_new_wallet",
+            AppRequest::RestoreWallet(..) => "restore_wallet",
+            AppRequest::GetSeedMnemonic => "get_seed_mnemonic",
+            AppRequest::SeedVerifyQuiz(..) => "seed_verify_quiz",
+            AppRequest::SeedVerifyCheck(..) => "seed_verify_check",
+            AppRequest::GetWalletInfo => "get_wallet_info",
+            AppRequest::GetBalance => "get_balance",
+            AppRequest::GetBalanceAndWalletInfo => "get_balance_and_wallet_info",
+            AppRequest::GetMintsInfo => "get_mints_info",
+            AppRequest::SelectMintByUrl(..) => "select_mint_by_url",
+            AppRequest::SelectMintByIndex(..) => "select_mint_by_index",
+            AppRequest::AddMint(..) => "add_mint",
+            AppRequest::MintFromLn(..) => "mint_from_ln",
+            AppRequest::ReceiveEC(..) => "receive_ec",
+            AppRequest::MeltToLn(..) => "melt_to_ln",
+            AppRequest::MeltQuote(..) => "melt_quote",
+            AppRequest::MeltConfirm(..) => "melt_confirm",
+            AppRequest::MeltCancel(..) => "melt_cancel",
+            AppRequest::MeltToLnMpp(..) => "melt_to_ln_mpp",
+            AppRequest::SendEC(..) => "send_ec",
+            AppRequest::SendECMulti(..) => "send_ec_multi",
+            AppRequest::SendECForRequest(..) => "send_ec_for_request",
+            AppRequest::RestoreFromSeed(..) => "restore_from_seed",
+            AppRequest::GetHistory(..) => "get_history",
+            AppRequest::ClaimPending => "claim_pending",
+            AppRequest::DecodeInput(..) => "decode_input",
+            AppRequest::CheckPendingTokens => "check_pending_tokens",
+            AppRequest::CheckPendingTokensPeriodic => "check_pending_tokens_periodic",
+            AppRequest::ReclaimToken(..) => "reclaim_token",
+            AppRequest::CheckWalletHealth => "check_wallet_health",
+            AppRequest::ListProofs(..) => "list_proofs",
+            AppRequest::Consolidate(..) => "consolidate",
+            AppRequest::RefreshKeysets => "refresh_keysets",
+            AppRequest::MigrateKeysets(..) => "migrate_keysets",
+            AppRequest::TransferQuote(..) => "transfer_quote",
+            AppRequest::Transfer(..) => "transfer",
+            AppRequest::TransferCancel(..) => "transfer_cancel",
+            AppRequest::TransferBetweenMints(..) => "transfer_between_mints",
+            AppRequest::Poll(..) => "poll",
+        }
+    }
+}
+
+/// Only the name is shown, as requests may carry secrets (password, mnemonic, token)
+impl fmt::Debug for AppRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "AppRequest::{}", self.name())
+    }
+}
+
+/// Interval of the periodic check of the sent tokens not claimed yet
+const PENDING_TOKENS_CHECK_INTERVAL: Duration = Duration::from_secs(60);
 const CHECK_STEP_INCREASE: f64 = 1.05;
+/// Maximum polling interval, the interval is increased until this
+const CHECK_STEP_MAX: Duration = Duration::from_secs(60);
 
 /// An operations that needs periodic polling.
 #[derive(Clone)]
@@ -70,13 +614,20 @@ pub struct PendingPolls {
 /// for use in environments without async/await (e.g. iced)
 #[derive(Clone)]
 pub struct PKAppAsync {
+    config: PKAppConfig,
     incoming_sender: Sender<AppRequest>,
 }
 
 impl PendingPoll {
     fn advance(&mut self) {
         self.next_time = self.next_time.checked_add(self.step).unwrap();
-        self.step = self.step.mul_f64(self.slowing_factor);
+        self.step = self.step.mul_f64(self.slowing_factor).min(CHECK_STEP_MAX);
+    }
+}
+
+impl Default for PendingPolls {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -98,14 +649,20 @@ impl PendingPolls {
         self.waker.write().unwrap().wake();
     }
 
+    /// Add a poll for a Lightning receive, polled until the quote expiry,
+    /// or for `max_time_sec` if the quote has no expiry.
     pub fn add2(&mut self, result: MintFromLnIntermediaryResult, step_ms: u64, max_time_sec: u64) {
         let now = SystemTime::now();
+        let stop_time = match result.expiry() {
+            0 => now.checked_add(Duration::from_secs(max_time_sec)).unwrap(),
+            expiry => UNIX_EPOCH + Duration::from_secs(expiry),
+        };
         let poll = PendingPoll {
             result,
             next_time: now,
             step: Duration::from_millis(step_ms),
             start_time: now,
-            stop_time: now.checked_add(Duration::from_secs(max_time_sec)).unwrap(),
+            stop_time,
             slowing_factor: CHECK_STEP_INCREASE,
         };
         self.add(poll);
@@ -133,7 +690,7 @@ impl PendingPolls {
         if let Some((ref key, earliest)) = earliest {
             // check if this earliest is already runnable (in the past)
             let now = SystemTime::now();
-            if let Some(ref poll) = &self.p.read().unwrap().get(key) {
+            if let Some(poll) = self.p.read().unwrap().get(key) {
                 if poll.next_time <= now {
                     (Some(key.clone()), None)
                 } else {
@@ -152,7 +709,7 @@ impl PendingPolls {
     fn prepare_for_run(&mut self, key: &String) -> Option<AppRequest> {
         // check if there should be more iterations
         let more_runs = {
-            if let Some(ref poll) = self.p.read().unwrap().get(key) {
+            if let Some(poll) = self.p.read().unwrap().get(key) {
                 if poll.next_time > poll.stop_time {
                     // no more runs
                     false
@@ -180,6 +737,10 @@ impl PendingPolls {
         }
     }
 
+    fn contains(&self, key: &str) -> bool {
+        self.p.read().unwrap().contains_key(key)
+    }
+
     fn remove(&mut self, key: &str) -> Option<PendingPoll> {
         self.p.write().unwrap().remove(key)
     }
@@ -212,7 +773,7 @@ impl Stream for PendingPolls {
         let (runnable_key, _to_wait) = self.get_runnable();
         if let Some(key) = runnable_key {
             let req = self.prepare_for_run(&key);
-            return Poll::Ready(req);
+            Poll::Ready(req)
         } else {
             Poll::Pending
         }
@@ -225,13 +786,18 @@ impl Stream for PendingPolls {
 
 impl PKAppAsync {
     /// Create instance of the app shell.
+    /// The wallet has to be opened with `open_wallet` or `init_new_wallet` before use.
     /// It has to be initialized for receiving response events:
     /// - by init_with_callback(), to get the events in a callback, OR
-    /// - byinit_with_sender(), to get the events in a queue (Sender).
+    /// - by init_with_sender(), to get the events in a queue (Sender).
+    ///
     /// Starts the background processing thread.
-    pub fn new() -> Result<Self, String> {
+    pub fn new(config: PKAppConfig) -> Result<Self, String> {
         let (incoming_sender, incoming_receiver) = mpsc::channel::<AppRequest>(100);
-        let instance = Self { incoming_sender };
+        let instance = Self {
+            config,
+            incoming_sender,
+        };
 
         // Start background processor thread
         let mut instance_clone = instance.clone();
@@ -247,9 +813,10 @@ impl PKAppAsync {
     /// Create instance of the app shell, initialized to receive events in a callback.
     /// Starts the background processing thread.
     pub fn new_with_callback<F: Fn(AppEvent) + std::marker::Send + 'static>(
+        config: PKAppConfig,
         callback: F,
     ) -> Result<Self, String> {
-        let mut instance = Self::new()?;
+        let mut instance = Self::new(config)?;
 
         let mut event_receiver = instance.init_channels()?;
 
@@ -258,7 +825,7 @@ impl PKAppAsync {
             loop {
                 match event_receiver.next().await {
                     None => {
-                        println!("Error in Subscription: None (310)");
+                        eprintln!("Error in Subscription: None (310)");
                         break;
                     }
                     Some(event) => {
@@ -283,39 +850,86 @@ impl PKAppAsync {
         // Pending poll operations
         let pending_polls = PendingPolls::new();
         let mut pending_polls2 = pending_polls.clone();
-        let mut select_stream = stream::select(incoming_receiver, pending_polls);
+        // Periodic check of the sent tokens
+        let pending_tokens_timer = Box::pin(stream::unfold((), |_| async {
+            tokio::time::sleep(PENDING_TOKENS_CHECK_INTERVAL).await;
+            Some((AppRequest::CheckPendingTokensPeriodic, ()))
+        }));
+        let mut select_stream = stream::select(
+            stream::select(incoming_receiver, pending_polls),
+            pending_tokens_timer,
+        );
         loop {
             match select_stream.next().await {
                 None => {
-                    println!("Error in Subscription: None (222)");
+                    eprintln!("Error in Subscription: None (222)");
                     break;
                 }
                 Some(req) => {
                     // println!("Got request {:?}", req);
-                    if let AppRequest::InitApp(sender) = req {
-                        // initialize the app
-                        let pk_app = PKApp::new().await.expect("App init error");
-                        app = Some(pk_app);
-                        outgoing_sender = Some(sender);
-                        // also retrieve initial info
-                        let _res = self.get_balance_and_wallet_info();
-                        let _res = self.get_mints_info();
-                    } else {
-                        // Took a request
-                        if let Some(ref mut app) = &mut app {
+                    match req {
+                        AppRequest::InitApp(sender) => {
+                            outgoing_sender = Some(sender);
+                        }
+                        AppRequest::OpenWallet(_)
+                        | AppRequest::InitNewWallet(_)
+                        | AppRequest::RestoreWallet(_, _) => {
                             if let Some(out_sender) = &mut outgoing_sender {
-                                Self::process_one_request(
-                                    app,
-                                    out_sender,
-                                    req,
-                                    &mut pending_polls2,
-                                )
-                                .await;
+                                let res = if app.is_some() {
+                                    Err(PkError::WalletAlreadyExists(
+                                        "wallet is already open".to_owned(),
+                                    ))
+                                } else {
+                                    match Self::open_app(&self.config, req).await {
+                                        Ok(pk_app) => {
+                                            app = Some(pk_app);
+                                            Ok(())
+                                        }
+                                        Err(err) => Err(err),
+                                    }
+                                };
+                                let is_ok = res.is_ok();
+                                let _res =
+                                    Self::send_out_event(out_sender, AppEvent::WalletOpened(res))
+                                        .await;
+                                if is_ok {
+                                    // also retrieve initial info
+                                    let _res = self.get_balance_and_wallet_info();
+                                    let _res = self.get_mints_info();
+                                    // resume polling the pending receives
+                                    if let Some(app) = &mut app {
+                                        Self::resume_pending_polls(app, &mut pending_polls2).await;
+                                    }
+                                }
+                            } else {
+                                eprintln!("Error: Open wallet request with missing out_sender");
+                            }
+                        }
+                        AppRequest::CheckPendingTokensPeriodic | AppRequest::RefreshKeysets
+                            if app.is_none() =>
+                        {
+                            // wallet not open yet, nothing to check
+                        }
+                        _ => {
+                            // Took a request
+                            if let Some(ref mut app) = &mut app {
+                                if let Some(out_sender) = &mut outgoing_sender {
+                                    Self::process_one_request(
+                                        app,
+                                        out_sender,
+                                        req,
+                                        &mut pending_polls2,
+                                    )
+                                    .await;
+                                } else {
+                                    eprintln!(
+                                        "Error: Request with missing out_sender, {}",
+                                        req.name()
+                                    );
+                                }
                             } else {
-                                println!("Error: Request with missing out_sender, {:?}", req);
+                                eprintln!("Error: Request with missing app, {}", req.name());
                             }
-                        } else {
-                            println!("Error: Request with missing app, {:?}", req);
                         }
                     }
                 }
@@ -323,18 +937,75 @@ impl PKAppAsync {
         }
     }
 
+    /// Open or initialize the wallet, as requested
+    async fn open_app(config: &PKAppConfig, req: AppRequest) -> Result<PKApp, PkError> {
+        match req {
+            AppRequest::OpenWallet(password) => PKApp::open_wallet(config, &password).await,
+            AppRequest::InitNewWallet(password) => PKApp::init_new_wallet(config, &password).await,
+            AppRequest::RestoreWallet(mnemonic, password) => {
+                PKApp::restore_wallet_from_mnemonic(config, &mnemonic, &password).await
+            }
+            _ => Err(PkError::InvalidInput(format!(
+                "Invalid request for opening the wallet, {}",
+                req.name()
+            ))),
+        }
+    }
+
+    /// Add the pending Lightning receives (e.g. from before a restart) to the polls
+    async fn resume_pending_polls(app: &mut PKApp, pending_polls: &mut PendingPolls) {
+        match app.get_pending_mint_quotes().await {
+            Ok(pending) => {
+                for intermediary_result in pending {
+                    pending_polls.add2(
+                        intermediary_result,
+                        app.config().poll_step_ms,
+                        app.config().poll_max_time_sec,
+                    );
+                }
+            }
+            Err(err) => eprintln!("Error retrieving pending receives, {}", err),
+        }
+    }
+
     #[inline]
     async fn send_out_event(out_sender: &mut Sender<AppEvent>, ev: AppEvent) -> Result<(), String> {
         // println!("Sending out {:?} ...", ev);
         match out_sender.send(ev.clone()).await {
             Ok(_) => Ok(()),
             Err(e) => {
-                println!("Error in send_out_event {:?} {}", ev, e);
+                eprintln!("Error in send_out_event {} {}", ev.name(), e);
                 Err(e.to_string())
             }
         }
     }
 
+    /// Send the transfer result, and the updated balance and mints.
+    /// A pending receive of the transfer is polled further.
+    async fn send_transfer_result(
+        app: &mut PKApp,
+        out_sender: &mut Sender<AppEvent>,
+        res: Result<TransferResult, PkError>,
+        pending_polls: &mut PendingPolls,
+    ) {
+        if let Ok(TransferResult {
+            pending_receive: Some(receive),
+            ..
+        }) = &res
+        {
+            pending_polls.add2(
+                *receive.clone(),
+                app.config().poll_step_ms,
+                app.config().poll_max_time_sec,
+            );
+        }
+        let _res = Self::send_out_event(out_sender, AppEvent::TransferRes(res)).await;
+        let res = app.get_balance().await;
+        let _res = Self::send_out_event(out_sender, AppEvent::BalanceChange(res)).await;
+        let res = app.get_mints_info().await;
+        let _res = Self::send_out_event(out_sender, AppEvent::MintsInfo(res)).await;
+    }
+
     async fn process_one_request(
         app: &mut PKApp,
         out_sender: &mut Sender<AppEvent>,
@@ -343,7 +1014,23 @@ impl PKAppAsync {
     ) {
         // println!("process_one_request: {:?}", req);
         match req {
-            AppRequest::InitApp(_) => {} // handled above
+            // handled above
+            AppRequest::InitApp(_)
+            | AppRequest::OpenWallet(_)
+            | AppRequest::InitNewWallet(_)
+            | AppRequest::RestoreWallet(_, _) => {}
+            AppRequest::GetSeedMnemonic => {
+                let res = app.get_seed_mnemonic();
+                let _res = Self::send_out_event(out_sender, AppEvent::SeedMnemonic(res)).await;
+            }
+            AppRequest::SeedVerifyQuiz(word_count) => {
+                let res = app.seed_verify_quiz(word_count);
+                let _res = Self::send_out_event(out_sender, AppEvent::SeedVerifyQuiz(res)).await;
+            }
+            AppRequest::SeedVerifyCheck(words) => {
+                let res = app.seed_verify_check(&words);
+                let _res = Self::send_out_event(out_sender, AppEvent::SeedVerifyResult(res)).await;
+            }
             AppRequest::GetWalletInfo => {
                 let res = app.get_wallet_info().await;
                 let _res = Self::send_out_event(out_sender, AppEvent::WalletInfo(res)).await;
@@ -405,39 +1092,207 @@ impl PKAppAsync {
                                 .await;
                         pending_polls.add2(
                             intermediary_result,
-                            2000,
-                            30, // TODO increase
+                            app.config().poll_step_ms,
+                            app.config().poll_max_time_sec,
                         );
                     }
                 };
             }
             AppRequest::MeltToLn(invoice) => {
-                let res = app.melt_to_ln(&invoice).await;
+                let res = app.melt_quote_start(&invoice).await;
+                let _res =
+                    Self::send_out_event(out_sender, AppEvent::MeltQuoteRes(res.clone())).await;
+                if let Ok(quote) = res {
+                    let res = app.melt_confirm(&quote.quote_id).await;
+                    let _res = Self::send_out_event(out_sender, AppEvent::MeltToLnRes(res)).await;
+                }
+            }
+            AppRequest::MeltQuote(invoice) => {
+                let res = app.melt_quote_start(&invoice).await;
+                let _res = Self::send_out_event(out_sender, AppEvent::MeltQuoteRes(res)).await;
+            }
+            AppRequest::MeltConfirm(quote_id) => {
+                let res = app.melt_confirm(&quote_id).await;
                 let _res = Self::send_out_event(out_sender, AppEvent::MeltToLnRes(res)).await;
             }
-            AppRequest::ReceiveEC(token) => {
-                let res = app.receive_ecash(&token).await;
+            AppRequest::MeltCancel(quote_id) => {
+                if let Err(e) = app.melt_cancel(&quote_id).await {
+                    eprintln!("Error cancelling melt quote, {}", e);
+                }
+            }
+            AppRequest::MeltToLnMpp(invoice) => {
+                let res = app.melt_to_ln_mpp(&invoice).await;
+                let _res = Self::send_out_event(out_sender, AppEvent::MeltToLnMppRes(res)).await;
+            }
+            AppRequest::ReceiveEC(token, trust_mint) => {
+                let res = app.receive_ecash(&token, trust_mint).await;
                 let _res = Self::send_out_event(out_sender, AppEvent::ReceivedEC(res)).await;
             }
             AppRequest::SendEC(amount) => {
                 let res = app.send_ecash(amount).await;
                 let _res = Self::send_out_event(out_sender, AppEvent::SendECRes(res)).await;
             }
+            AppRequest::SendECMulti(amount, consolidate) => {
+                let res = app.send_ecash_multi(amount, consolidate).await;
+                let _res = Self::send_out_event(out_sender, AppEvent::SendECMultiRes(res)).await;
+            }
+            AppRequest::SendECForRequest(request, amount) => {
+                let res = app.send_ecash_for_request(&request, amount).await;
+                let _res = Self::send_out_event(out_sender, AppEvent::SendECRes(res)).await;
+            }
+            AppRequest::RestoreFromSeed(mints) => {
+                // Progress is queued without limit and forwarded while the restore runs,
+                // so none is dropped; the queue ends when the restore is done
+                let (progress_sender, mut progress_receiver) = mpsc::unbounded();
+                let restore = app.restore_from_seed(&mints, move |progress| {
+                    let _res =
+                        progress_sender.unbounded_send(AppEvent::RestoreProgress(progress.clone()));
+                });
+                let mut forward_sender = out_sender.clone();
+                let forward = async {
+                    while let Some(ev) = progress_receiver.next().await {
+                        let _res = Self::send_out_event(&mut forward_sender, ev).await;
+                    }
+                };
+                let (res, _) = futures::join!(restore, forward);
+                let _res = Self::send_out_event(out_sender, AppEvent::RestoreRes(res)).await;
+                // also update balance and mints
+                let res = app.get_balance().await;
+                let _res = Self::send_out_event(out_sender, AppEvent::BalanceChange(res)).await;
+                let res = app.get_mints_info().await;
+                let _res = Self::send_out_event(out_sender, AppEvent::MintsInfo(res)).await;
+            }
+            AppRequest::DecodeInput(input) => {
+                let res = app.decode_input(&input).await;
+                let _res = Self::send_out_event(out_sender, AppEvent::DecodedInput(res)).await;
+            }
+            AppRequest::CheckPendingTokens => {
+                let res = app.check_pending_tokens().await;
+                let _res = Self::send_out_event(out_sender, AppEvent::PendingTokens(res)).await;
+            }
+            AppRequest::CheckPendingTokensPeriodic => {
+                if app.has_pending_tokens() {
+                    if let Ok(pending) = app.check_pending_tokens().await {
+                        if pending.newly_claimed > 0 {
+                            let _res = Self::send_out_event(
+                                out_sender,
+                                AppEvent::PendingTokens(Ok(pending)),
+                            )
+                            .await;
+                        }
+                    }
+                }
+            }
+            AppRequest::ReclaimToken(tx_id) => {
+                let res = app.reclaim_token(tx_id).await;
+                let _res = Self::send_out_event(out_sender, AppEvent::ReclaimRes(res)).await;
+                // also update balance
+                let res = app.get_balance().await;
+                let _res = Self::send_out_event(out_sender, AppEvent::BalanceChange(res)).await;
+            }
+            AppRequest::CheckWalletHealth => {
+                let res = app.check_wallet_health().await;
+                let _res = Self::send_out_event(out_sender, AppEvent::WalletHealth(res)).await;
+                // also update balance and mints
+                let res = app.get_balance().await;
+                let _res = Self::send_out_event(out_sender, AppEvent::BalanceChange(res)).await;
+                let res = app.get_mints_info().await;
+                let _res = Self::send_out_event(out_sender, AppEvent::MintsInfo(res)).await;
+            }
+            AppRequest::ListProofs(mint_url) => {
+                let res = app.list_proofs(mint_url.as_deref()).await;
+                let _res = Self::send_out_event(out_sender, AppEvent::Proofs(res)).await;
+            }
+            AppRequest::Consolidate(mint_url, target_value) => {
+                let res = app.consolidate(mint_url.as_deref(), target_value).await;
+                let _res = Self::send_out_event(out_sender, AppEvent::ConsolidateRes(res)).await;
+                // also update balance and mints
+                let res = app.get_balance().await;
+                let _res = Self::send_out_event(out_sender, AppEvent::BalanceChange(res)).await;
+                let res = app.get_mints_info().await;
+                let _res = Self::send_out_event(out_sender, AppEvent::MintsInfo(res)).await;
+            }
+            AppRequest::RefreshKeysets => {
+                let res = app.refresh_keysets().await;
+                let _res = Self::send_out_event(out_sender, AppEvent::KeysetsRefreshed(res)).await;
+            }
+            AppRequest::MigrateKeysets(mint_url) => {
+                let res = app.migrate_inactive_keysets(mint_url.as_deref()).await;
+                let _res = Self::send_out_event(out_sender, AppEvent::MigrateKeysetsRes(res)).await;
+                // also update balance and mints
+                let res = app.get_balance().await;
+                let _res = Self::send_out_event(out_sender, AppEvent::BalanceChange(res)).await;
+                let res = app.get_mints_info().await;
+                let _res = Self::send_out_event(out_sender, AppEvent::MintsInfo(res)).await;
+            }
+            AppRequest::ClaimPending => {
+                let res = app.claim_pending().await;
+                let _res = Self::send_out_event(out_sender, AppEvent::ClaimPendingRes(res)).await;
+                // also update balance
+                let res = app.get_balance().await;
+                let _res = Self::send_out_event(out_sender, AppEvent::BalanceChange(res)).await;
+            }
+            AppRequest::TransferQuote(from_mint, to_mint, amount) => {
+                let res = app.transfer_quote(&from_mint, &to_mint, amount).await;
+                let _res = Self::send_out_event(out_sender, AppEvent::TransferQuoteRes(res)).await;
+            }
+            AppRequest::Transfer(quote) => {
+                let res = app.transfer_execute(quote).await;
+                Self::send_transfer_result(app, out_sender, res, pending_polls).await;
+            }
+            AppRequest::TransferCancel(quote) => {
+                if let Err(e) = app.transfer_cancel(quote).await {
+                    eprintln!("Error cancelling transfer, {}", e);
+                }
+            }
+            AppRequest::TransferBetweenMints(from_mint, to_mint, amount) => {
+                let res = app.transfer_quote(&from_mint, &to_mint, amount).await;
+                let _res =
+                    Self::send_out_event(out_sender, AppEvent::TransferQuoteRes(res.clone())).await;
+                if let Ok(quote) = res {
+                    let res = app.transfer_execute(quote).await;
+                    Self::send_transfer_result(app, out_sender, res, pending_polls).await;
+                }
+            }
+            AppRequest::GetHistory(filter) => {
+                let res = app.list_transactions(&filter);
+                let _res = Self::send_out_event(out_sender, AppEvent::History(res)).await;
+            }
             AppRequest::Poll(intermediary_result) => {
+                let id = intermediary_result.id();
                 let res = app.mint_from_ln_check(intermediary_result).await;
-                if let Ok(res) = res {
-                    let id = res.id();
-                    if let Some(result) = res.paid_result {
+                if let Ok(res) = &res {
+                    if let Some(result) = &res.paid_result {
                         // we have a final result; remove from map and notify
                         pending_polls.remove(&id);
-                        let _res =
-                            Self::send_out_event(out_sender, AppEvent::MintFromLnRes(result)).await;
+                        let _res = Self::send_out_event(
+                            out_sender,
+                            AppEvent::MintFromLnRes(result.clone()),
+                        )
+                        .await;
                         // also update balance
                         let res = app.get_balance().await;
                         let _res =
                             Self::send_out_event(out_sender, AppEvent::BalanceChange(res)).await;
+                        return;
                     }
                 }
+                if let Err(PkError::QuoteExpired) = res {
+                    // cannot be paid any more
+                    pending_polls.remove(&id);
+                }
+                if !pending_polls.contains(&id) {
+                    // this was the last poll, give up
+                    let err = match res {
+                        Err(err) => err,
+                        Ok(_) => PkError::Timeout(format!(
+                            "invoice not paid in {} secs, if paid later it can be claimed",
+                            app.config().poll_max_time_sec
+                        )),
+                    };
+                    let _res =
+                        Self::send_out_event(out_sender, AppEvent::MintFromLnRes(Err(err))).await;
+                }
             }
         }
     }
@@ -456,6 +1311,33 @@ impl PKAppAsync {
             .map_err(|e| e.to_string())
     }
 
+    /// Check if the wallet has been initialized (the secret file exists).
+    /// If yes, it should be opened with `open_wallet`, otherwise created with `init_new_wallet`.
+    pub fn is_wallet_initialized(&self) -> bool {
+        PKApp::is_wallet_initialized(&self.config)
+    }
+
+    pub fn config(&self) -> &PKAppConfig {
+        &self.config
+    }
+    pub fn open_wallet(&mut self, password: String) -> Result<(), String> {
+        self.send_to_incoming(AppRequest::OpenWallet(password))
+    }
+    pub fn init_new_wallet(&mut self, password: String) -> Result<(), String> {
+        self.send_to_incoming(AppRequest::InitNewWallet(password))
+    }
+    pub fn restore_wallet(&mut self, mnemonic: String, password: String) -> Result<(), String> {
+        self.send_to_incoming(AppRequest::RestoreWallet(mnemonic, password))
+    }
+    pub fn get_seed_mnemonic(&mut self) -> Result<(), String> {
+        self.send_to_incoming(AppRequest::GetSeedMnemonic)
+    }
+    pub fn seed_verify_quiz(&mut self, word_count: usize) -> Result<(), String> {
+        self.send_to_incoming(AppRequest::SeedVerifyQuiz(word_count))
+    }
+    pub fn seed_verify_check(&mut self, words: Vec<(usize, String)>) -> Result<(), String> {
+        self.send_to_incoming(AppRequest::SeedVerifyCheck(words))
+    }
     pub fn get_wallet_info(&mut self) -> Result<(), String> {
         self.send_to_incoming(AppRequest::GetWalletInfo)
     }
@@ -480,15 +1362,100 @@ impl PKAppAsync {
     pub fn mint_from_ln(&mut self, amount_sats: u64) -> Result<(), String> {
         self.send_to_incoming(AppRequest::MintFromLn(amount_sats))
     }
-    pub fn receive_ec(&mut self, ecash_token: String) -> Result<(), String> {
-        self.send_to_incoming(AppRequest::ReceiveEC(ecash_token))
+    pub fn receive_ec(&mut self, ecash_token: String, trust_mint: bool) -> Result<(), String> {
+        self.send_to_incoming(AppRequest::ReceiveEC(ecash_token, trust_mint))
     }
     pub fn melt_to_ln(&mut self, invoice_to_pay: String) -> Result<(), String> {
         self.send_to_incoming(AppRequest::MeltToLn(invoice_to_pay))
     }
+    pub fn melt_quote(&mut self, invoice_to_pay: String) -> Result<(), String> {
+        self.send_to_incoming(AppRequest::MeltQuote(invoice_to_pay))
+    }
+    pub fn melt_confirm(&mut self, quote_id: String) -> Result<(), String> {
+        self.send_to_incoming(AppRequest::MeltConfirm(quote_id))
+    }
+    pub fn melt_cancel(&mut self, quote_id: String) -> Result<(), String> {
+        self.send_to_incoming(AppRequest::MeltCancel(quote_id))
+    }
+    pub fn melt_to_ln_mpp(&mut self, invoice_to_pay: String) -> Result<(), String> {
+        self.send_to_incoming(AppRequest::MeltToLnMpp(invoice_to_pay))
+    }
     pub fn send_ec(&mut self, amount_sats: u64) -> Result<(), String> {
         self.send_to_incoming(AppRequest::SendEC(amount_sats))
     }
+    pub fn send_ec_multi(&mut self, amount_sats: u64, consolidate: bool) -> Result<(), String> {
+        self.send_to_incoming(AppRequest::SendECMulti(amount_sats, consolidate))
+    }
+    pub fn send_ec_for_request(
+        &mut self,
+        request: String,
+        amount_sats: Option<u64>,
+    ) -> Result<(), String> {
+        self.send_to_incoming(AppRequest::SendECForRequest(request, amount_sats))
+    }
+    pub fn restore_from_seed(&mut self, mints: Vec<String>) -> Result<(), String> {
+        self.send_to_incoming(AppRequest::RestoreFromSeed(mints))
+    }
+    pub fn claim_pending(&mut self) -> Result<(), String> {
+        self.send_to_incoming(AppRequest::ClaimPending)
+    }
+    pub fn decode_input(&mut self, input: String) -> Result<(), String> {
+        self.send_to_incoming(AppRequest::DecodeInput(input))
+    }
+    pub fn check_pending_tokens(&mut self) -> Result<(), String> {
+        self.send_to_incoming(AppRequest::CheckPendingTokens)
+    }
+    pub fn reclaim_token(&mut self, tx_id: u64) -> Result<(), String> {
+        self.send_to_incoming(AppRequest::ReclaimToken(tx_id))
+    }
+    pub fn check_wallet_health(&mut self) -> Result<(), String> {
+        self.send_to_incoming(AppRequest::CheckWalletHealth)
+    }
+    pub fn list_proofs(&mut self, mint_url: Option<String>) -> Result<(), String> {
+        self.send_to_incoming(AppRequest::ListProofs(mint_url))
+    }
+    pub fn consolidate(
+        &mut self,
+        mint_url: Option<String>,
+        target_value: Option<u64>,
+    ) -> Result<(), String> {
+        self.send_to_incoming(AppRequest::Consolidate(mint_url, target_value))
+    }
+    pub fn refresh_keysets(&mut self) -> Result<(), String> {
+        self.send_to_incoming(AppRequest::RefreshKeysets)
+    }
+    pub fn migrate_keysets(&mut self, mint_url: Option<String>) -> Result<(), String> {
+        self.send_to_incoming(AppRequest::MigrateKeysets(mint_url))
+    }
+    pub fn transfer_quote(
+        &mut self,
+        from_mint: String,
+        to_mint: String,
+        amount_sats: u64,
+    ) -> Result<(), String> {
+        self.send_to_incoming(AppRequest::TransferQuote(from_mint, to_mint, amount_sats))
+    }
+    pub fn transfer(&mut self, quote: TransferQuote) -> Result<(), String> {
+        self.send_to_incoming(AppRequest::Transfer(quote))
+    }
+    pub fn transfer_cancel(&mut self, quote: TransferQuote) -> Result<(), String> {
+        self.send_to_incoming(AppRequest::TransferCancel(quote))
+    }
+    pub fn transfer_between_mints(
+        &mut self,
+        from_mint: String,
+        to_mint: String,
+        amount_sats: u64,
+    ) -> Result<(), String> {
+        self.send_to_incoming(AppRequest::TransferBetweenMints(
+            from_mint,
+            to_mint,
+            amount_sats,
+        ))
+    }
+    pub fn get_history(&mut self, filter: HistoryFilter) -> Result<(), String> {
+        self.send_to_incoming(AppRequest::GetHistory(filter))
+    }
     pub fn get_recommended_mint_list() -> Vec<(String, String)> {
         PKApp::get_recommended_mint_list()
     }
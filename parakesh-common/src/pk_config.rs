@@ -0,0 +1,289 @@
+use cdk::nuts::CurrencyUnit;
+
+use std::path::PathBuf;
+use std::str::FromStr;
+
+const DEFAULT_DATA_DIR_NAME: &str = "parakesh";
+/// Data dir of older versions, the current dir
+const LEGACY_DATA_DIR: &str = ".";
+const DEFAULT_DB_FILE_NAME: &str = "parakesh_data.dedb";
+const DEFAULT_SECRET_FILE_NAME: &str = "parakesh.secret";
+const DEFAULT_HISTORY_FILE_NAME: &str = "parakesh_history.json";
+/// Suffix of the seed check file, after the secret file name
+const SEED_CHECK_FILE_SUFFIX: &str = ".check";
+const DEFAULT_POLL_STEP_MS: u64 = 2000;
+const DEFAULT_POLL_MAX_TIME_SEC: u64 = 30;
+
+/// Strategy for choosing the mint to send from (ecash or Lightning)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MintSelection {
+    /// Always use the selected mint
+    #[default]
+    Selected,
+    /// Use the selected mint if it has enough funds, otherwise the one with the largest balance
+    PreferSelected,
+    /// Use the mint with the lowest fee, among the ones with enough funds
+    LowestFee,
+    /// Use the mint with the largest balance
+    LargestBalance,
+}
+
+impl FromStr for MintSelection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "selected" => Ok(MintSelection::Selected),
+            "prefer-selected" => Ok(MintSelection::PreferSelected),
+            "lowest-fee" => Ok(MintSelection::LowestFee),
+            "largest-balance" => Ok(MintSelection::LargestBalance),
+            _ => Err(format!(
+                "Invalid mint selection {}, possible values: selected, prefer-selected, lowest-fee, largest-balance",
+                s
+            )),
+        }
+    }
+}
+
+/// Format of the created ecash tokens
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TokenFormat {
+    /// V3 token, JSON-based (`cashuA...`)
+    V3,
+    /// V4 token, CBOR-based (`cashuB...`), much shorter
+    #[default]
+    V4,
+}
+
+impl FromStr for TokenFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "v3" => Ok(TokenFormat::V3),
+            "v4" => Ok(TokenFormat::V4),
+            _ => Err(format!(
+                "Invalid token format {}, possible values: v3, v4",
+                s
+            )),
+        }
+    }
+}
+
+/// Configuration of the app: location of the data files, unit, polling parameters.
+#[derive(Clone, Debug)]
+pub struct PKAppConfig {
+    /// Directory for the data files (DB and secret file)
+    pub data_dir: PathBuf,
+    /// Name of the ecash store DB file, within `data_dir`
+    pub db_file_name: String,
+    /// Name of the encrypted secret seed file, within `data_dir`
+    pub secret_file_name: String,
+    /// Name of the transaction history file, within `data_dir`
+    pub history_file_name: String,
+    pub unit: CurrencyUnit,
+    /// Initial polling interval for Lightning receive, in millisecs
+    pub poll_step_ms: u64,
+    /// Maximum time to wait for a Lightning receive, in secs.
+    /// In the background the pending receives are polled until the quote expiry,
+    /// this limit applies only to quotes without expiry.
+    pub poll_max_time_sec: u64,
+    /// How to choose the mint for sending; by default the selected mint is used
+    pub mint_selection: MintSelection,
+    /// The mints which can be chosen by the automatic mint selection; all known mints if empty
+    pub trusted_mints: Vec<String>,
+    /// Format of the created tokens
+    pub token_format: TokenFormat,
+    /// Consolidate the proofs of a mint after a receive, if it holds more proofs than this;
+    /// 0 for no automatic consolidation
+    pub auto_consolidate_proofs: usize,
+}
+
+impl Default for PKAppConfig {
+    fn default() -> Self {
+        Self {
+            data_dir: Self::default_data_dir(),
+            db_file_name: DEFAULT_DB_FILE_NAME.to_owned(),
+            secret_file_name: DEFAULT_SECRET_FILE_NAME.to_owned(),
+            history_file_name: DEFAULT_HISTORY_FILE_NAME.to_owned(),
+            unit: CurrencyUnit::Sat,
+            poll_step_ms: DEFAULT_POLL_STEP_MS,
+            poll_max_time_sec: DEFAULT_POLL_MAX_TIME_SEC,
+            mint_selection: MintSelection::default(),
+            trusted_mints: Vec::new(),
+            token_format: TokenFormat::default(),
+            auto_consolidate_proofs: 0,
+        }
+    }
+}
+
+impl PKAppConfig {
+    /// The default data directory: 'parakesh' in the user data dir
+    /// (XDG data dir, e.g. `~/.local/share/parakesh`), or the current dir if there is none.
+    pub fn default_data_dir() -> PathBuf {
+        match dirs::data_dir() {
+            Some(dir) => dir.join(DEFAULT_DATA_DIR_NAME),
+            None => PathBuf::from(LEGACY_DATA_DIR),
+        }
+    }
+
+    /// Older versions kept the wallet files in the current dir. If there is such a wallet,
+    /// and none in the data dir, the current dir is used instead (with a warning),
+    /// so that the existing wallet is not left behind.
+    fn use_legacy_data_dir(&mut self) {
+        let legacy_config = Self {
+            data_dir: PathBuf::from(LEGACY_DATA_DIR),
+            ..self.clone()
+        };
+        if self.data_dir != legacy_config.data_dir
+            && !self.secret_file_path().exists()
+            && legacy_config.secret_file_path().exists()
+        {
+            eprintln!(
+                "WARNING: Using the wallet found in the current directory ({}); the default data directory is now {}. \
+                Move the wallet files there, or use '--datadir {}' to keep using this location.",
+                legacy_config.secret_file_path().display(),
+                self.data_dir.display(),
+                LEGACY_DATA_DIR,
+            );
+            self.data_dir = legacy_config.data_dir;
+        }
+    }
+
+    pub fn db_file_path(&self) -> PathBuf {
+        self.data_dir.join(&self.db_file_name)
+    }
+
+    pub fn secret_file_path(&self) -> PathBuf {
+        self.data_dir.join(&self.secret_file_name)
+    }
+
+    pub fn history_file_path(&self) -> PathBuf {
+        self.data_dir.join(&self.history_file_name)
+    }
+
+    pub fn seed_check_file_path(&self) -> PathBuf {
+        self.data_dir.join(format!(
+            "{}{}",
+            self.secret_file_name, SEED_CHECK_FILE_SUFFIX
+        ))
+    }
+
+    /// Create the config from command line arguments (without the program name),
+    /// starting from the defaults.
+    /// Returns the config, and the remaining (not config-related) arguments.
+    /// Options can be given as `--name value` or `--name=value`, see `usage`.
+    /// Without `--datadir`, a wallet of an older version in the current dir is also found.
+    pub fn from_args(args: &[String]) -> Result<(Self, Vec<String>), String> {
+        let mut config = Self::default();
+        let mut rest = Vec::new();
+        let mut data_dir_set = false;
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            let (name, inline_value) = match arg.split_once('=') {
+                Some((name, value)) if arg.starts_with("--") => (name, Some(value.to_owned())),
+                _ => (arg.as_str(), None),
+            };
+            if !Self::is_option(name) {
+                rest.push(arg.clone());
+                continue;
+            }
+            let value = match inline_value {
+                Some(value) => value,
+                None => match iter.next() {
+                    Some(value) => value.clone(),
+                    None => return Err(format!("Missing value for option {}", name)),
+                },
+            };
+            config.set_option(name, &value)?;
+            data_dir_set |= name == "--datadir";
+        }
+        if !data_dir_set {
+            config.use_legacy_data_dir();
+        }
+        Ok((config, rest))
+    }
+
+    fn is_option(name: &str) -> bool {
+        matches!(
+            name,
+            "--datadir"
+                | "--dbfile"
+                | "--secretfile"
+                | "--historyfile"
+                | "--unit"
+                | "--pollstep"
+                | "--polltime"
+                | "--mintselect"
+                | "--trustedmints"
+                | "--tokenformat"
+                | "--autoconsolidate"
+        )
+    }
+
+    fn set_option(&mut self, name: &str, value: &str) -> Result<(), String> {
+        match name {
+            "--datadir" => self.data_dir = PathBuf::from(value),
+            "--dbfile" => self.db_file_name = value.to_owned(),
+            "--secretfile" => self.secret_file_name = value.to_owned(),
+            "--historyfile" => self.history_file_name = value.to_owned(),
+            "--unit" => {
+                self.unit = CurrencyUnit::from_str(value)
+                    .map_err(|e| format!("Invalid unit {}, {}", value, e))?
+            }
+            "--pollstep" => {
+                self.poll_step_ms = value
+                    .parse::<u64>()
+                    .map_err(|e| format!("Invalid poll step {}, {}", value, e))?
+            }
+            "--polltime" => {
+                self.poll_max_time_sec = value
+                    .parse::<u64>()
+                    .map_err(|e| format!("Invalid poll time {}, {}", value, e))?
+            }
+            "--mintselect" => self.mint_selection = MintSelection::from_str(value)?,
+            "--trustedmints" => {
+                self.trusted_mints = value
+                    .split(',')
+                    .map(|m| m.trim().to_owned())
+                    .filter(|m| !m.is_empty())
+                    .collect()
+            }
+            "--tokenformat" => self.token_format = TokenFormat::from_str(value)?,
+            "--autoconsolidate" => {
+                self.auto_consolidate_proofs = value
+                    .parse::<usize>()
+                    .map_err(|e| format!("Invalid proof count {}, {}", value, e))?
+            }
+            _ => return Err(format!("Unknown option {}", name)),
+        }
+        Ok(())
+    }
+
+    /// Usage text for the config options
+    pub fn usage() -> String {
+        format!(
+            "Options:
+  --datadir <dir>\t\tDirectory for the data files (default: {})
+  --dbfile <name>\t\tName of the ecash DB file (default: {})
+  --secretfile <name>\t\tName of the encrypted seed file (default: {})
+  --historyfile <name>\t\tName of the transaction history file (default: {})
+  --unit <unit>\t\t\tCurrency unit (default: sat)
+  --pollstep <millisecs>\tInitial polling interval for receiving LN (default: {})
+  --polltime <secs>\t\tMaximum polling time for receiving LN (default: {})
+  --mintselect <strategy>\tMint to send from: selected, prefer-selected, lowest-fee,
+  \t\t\t\tlargest-balance (default: selected)
+  --trustedmints <url,...>\tMints allowed for the automatic mint selection (default: all)
+  --tokenformat <v3|v4>\t\tFormat of the created ecash tokens (default: v4)
+  --autoconsolidate <count>\tConsolidate the proofs of a mint after a receive, if there are
+  \t\t\t\tmore than this (default: 0, off)
+  --help\t\t\tShow this help",
+            Self::default_data_dir().display(),
+            DEFAULT_DB_FILE_NAME,
+            DEFAULT_SECRET_FILE_NAME,
+            DEFAULT_HISTORY_FILE_NAME,
+            DEFAULT_POLL_STEP_MS,
+            DEFAULT_POLL_MAX_TIME_SEC,
+        )
+    }
+}
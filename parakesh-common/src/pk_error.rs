@@ -0,0 +1,189 @@
+use std::fmt;
+
+/// Errors of the wallet operations.
+/// CDK errors are mapped into the variants relevant for the user; the rest is kept as text.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PkError {
+    /// There is no wallet (secret file) yet, it has to be initialized first
+    WalletNotInitialized(String),
+    /// The wallet (secret file) already exists, it is not overwritten
+    WalletAlreadyExists(String),
+    /// The seed could not be decrypted, or it does not match the wallet (wrong password)
+    SeedDecryptionFailed(String),
+    /// The wallet has no seed check, and the seed cannot be verified with its ecash either
+    SeedCheckMissing(String),
+    /// The password does not meet the requirements
+    WeakPassword(String),
+    /// Invalid seed phrase (mnemonic)
+    InvalidMnemonic(String),
+    /// There is no selected mint to use
+    NoSelectedMint,
+    /// The mint is not known by the wallet
+    UnknownMint(String),
+    /// The token is from a mint not used yet; it can be received after trusting the mint
+    UntrustedMint(String),
+    /// The mint could not be reached, or did not respond
+    MintUnreachable(String),
+    /// The mint rejected the operation (e.g. quote, keyset, limits)
+    MintError(String),
+    /// Not enough funds for the operation
+    InsufficientBalance,
+    /// The token (proofs) has already been spent
+    TokenAlreadySpent,
+    /// The token (proofs) is pending, in an ongoing operation
+    TokenPending,
+    /// The ecash token could not be parsed, or cannot be used
+    InvalidToken(String),
+    /// The Lightning invoice could not be parsed, or cannot be used
+    InvalidInvoice(String),
+    /// The quote (mint or melt) has expired
+    QuoteExpired,
+    /// The operation did not complete in time (e.g. invoice not paid)
+    Timeout(String),
+    /// Invalid parameter
+    InvalidInput(String),
+    /// Error with the database or the data files
+    Storage(String),
+    /// Any other error
+    Other(String),
+}
+
+impl PkError {
+    /// Short, stable identifier of the error kind, e.g. for JSON output
+    pub fn kind(&self) -> &'static str {
+        match self {
+            PkError::WalletNotInitialized(_) => "wallet_not_initialized",
+            PkError::WalletAlreadyExists(_) => "wallet_already_exists",
+            PkError::SeedDecryptionFailed(_) => "seed_decryption_failed",
+            PkError::SeedCheckMissing(_) => "seed_check_missing",
+            PkError::WeakPassword(_) => "weak_password",
+            PkError::InvalidMnemonic(_) => "invalid_mnemonic",
+            PkError::NoSelectedMint => "no_selected_mint",
+            PkError::UnknownMint(_) => "unknown_mint",
+            PkError::UntrustedMint(_) => "untrusted_mint",
+            PkError::MintUnreachable(_) => "mint_unreachable",
+            PkError::MintError(_) => "mint_error",
+            PkError::InsufficientBalance => "insufficient_balance",
+            PkError::TokenAlreadySpent => "token_already_spent",
+            PkError::TokenPending => "token_pending",
+            PkError::InvalidToken(_) => "invalid_token",
+            PkError::InvalidInvoice(_) => "invalid_invoice",
+            PkError::QuoteExpired => "quote_expired",
+            PkError::Timeout(_) => "timeout",
+            PkError::InvalidInput(_) => "invalid_input",
+            PkError::Storage(_) => "storage",
+            PkError::Other(_) => "other",
+        }
+    }
+}
+
+impl std::error::Error for PkError {}
+
+impl fmt::Display for PkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PkError::WalletNotInitialized(s) => {
+                write!(
+                    f,
+                    "Wallet not initialized, create or restore it first ({})",
+                    s
+                )
+            }
+            PkError::WalletAlreadyExists(s) => write!(f, "Wallet already exists ({})", s),
+            PkError::SeedDecryptionFailed(s) => {
+                write!(f, "Could not open the wallet, wrong password? ({})", s)
+            }
+            PkError::SeedCheckMissing(s) => write!(
+                f,
+                "Cannot verify the password, there is no seed check file ({}) and no ecash to verify the seed with. \
+                If the password is surely correct, move away the secret file and restore the wallet from its seed phrase",
+                s
+            ),
+            PkError::WeakPassword(s) => write!(f, "Password is not strong enough, {}", s),
+            PkError::InvalidMnemonic(s) => write!(f, "Invalid seed phrase, {}", s),
+            PkError::NoSelectedMint => write!(f, "No selected mint, add or select a mint"),
+            PkError::UnknownMint(s) => write!(f, "Mint not found, {}", s),
+            PkError::UntrustedMint(s) => {
+                write!(
+                    f,
+                    "The token is from an unknown mint, {}, trust it first",
+                    s
+                )
+            }
+            PkError::MintUnreachable(s) => {
+                write!(f, "Could not reach the mint, check the connection ({})", s)
+            }
+            PkError::MintError(s) => write!(f, "Mint error, {}", s),
+            PkError::InsufficientBalance => write!(f, "Insufficient balance"),
+            PkError::TokenAlreadySpent => write!(f, "Token already spent"),
+            PkError::TokenPending => write!(f, "Token is pending in another operation"),
+            PkError::InvalidToken(s) => write!(f, "Invalid ecash token, {}", s),
+            PkError::InvalidInvoice(s) => write!(f, "Invalid Lightning invoice, {}", s),
+            PkError::QuoteExpired => write!(f, "Quote expired"),
+            PkError::Timeout(s) => write!(f, "Timeout, {}", s),
+            PkError::InvalidInput(s) => write!(f, "{}", s),
+            PkError::Storage(s) => write!(f, "Storage error, {}", s),
+            PkError::Other(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+impl From<cdk::Error> for PkError {
+    fn from(e: cdk::Error) -> Self {
+        use cdk::Error;
+        match e {
+            Error::InsufficientFunds => PkError::InsufficientBalance,
+            Error::TokenAlreadySpent => PkError::TokenAlreadySpent,
+            Error::TokenPending => PkError::TokenPending,
+            Error::ExpiredQuote(_, _) => PkError::QuoteExpired,
+            Error::HttpError(_) | Error::CouldNotGetMintInfo => {
+                PkError::MintUnreachable(e.to_string())
+            }
+            Error::UnknownWallet(_) => PkError::UnknownMint(e.to_string()),
+            Error::Invoice(_)
+            | Error::InvalidPaymentRequest
+            | Error::InvoiceAmountUndefined
+            | Error::RequestAlreadyPaid => PkError::InvalidInvoice(e.to_string()),
+            Error::NUT00(_) | Error::IncorrectMint | Error::MultiMintTokenNotSupported => {
+                PkError::InvalidToken(e.to_string())
+            }
+            Error::UrlParseError(_) | Error::CashuUrl(_) => PkError::InvalidInput(e.to_string()),
+            Error::Database(_) => PkError::Storage(e.to_string()),
+            Error::UnknownQuote
+            | Error::UnpaidQuote
+            | Error::PendingQuote
+            | Error::IssuedQuote
+            | Error::PaidQuote
+            | Error::PaymentFailed
+            | Error::PaymentPending
+            | Error::MintingDisabled
+            | Error::MeltingDisabled
+            | Error::AmountOutofLimitRange(_, _, _)
+            | Error::UnknownKeySet
+            | Error::KeysetUnknown(_)
+            | Error::InactiveKeyset
+            | Error::TransactionUnbalanced(_, _, _)
+            | Error::UnsupportedUnit
+            | Error::UnknownErrorResponse(_) => PkError::MintError(e.to_string()),
+            _ => PkError::Other(e.to_string()),
+        }
+    }
+}
+
+impl From<cdk::cdk_database::Error> for PkError {
+    fn from(e: cdk::cdk_database::Error) -> Self {
+        PkError::Storage(e.to_string())
+    }
+}
+
+impl From<cdk::mint_url::Error> for PkError {
+    fn from(e: cdk::mint_url::Error) -> Self {
+        PkError::InvalidInput(format!("Invalid mint URL, {}", e))
+    }
+}
+
+impl From<cdk::nuts::nut00::Error> for PkError {
+    fn from(e: cdk::nuts::nut00::Error) -> Self {
+        PkError::InvalidToken(e.to_string())
+    }
+}
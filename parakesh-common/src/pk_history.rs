@@ -0,0 +1,513 @@
+use cdk_common::bitcoin::hashes::{sha256, Hash, HashEngine};
+use cdk_common::bitcoin::hex::{DisplayHex, FromHex};
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+
+use crate::pk_error::PkError;
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Direction of a transaction, as seen from the wallet
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TxDirection {
+    Incoming,
+    Outgoing,
+}
+
+/// Kind of a transaction: Lightning payment, or ecash token
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TxKind {
+    Lightning,
+    Ecash,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TxStatus {
+    /// Started, but not completed yet (e.g. invoice not paid yet)
+    Pending,
+    Completed,
+    Failed,
+    /// Sent token taken back into the wallet, before the recipient claimed it
+    Reclaimed,
+}
+
+/// An entry in the transaction history
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Transaction {
+    /// Sequential ID, unique within the wallet
+    pub id: u64,
+    pub direction: TxDirection,
+    pub kind: TxKind,
+    /// Amount in the wallet unit, excluding the fee
+    pub amount: u64,
+    /// Fee paid, in the wallet unit
+    pub fee: u64,
+    pub mint_url: String,
+    /// Time of the transaction (creation), in Unix secs
+    pub timestamp: u64,
+    /// The Lightning invoice or the ecash token
+    pub reference: String,
+    pub status: TxStatus,
+    pub memo: Option<String>,
+}
+
+impl Transaction {
+    /// New transaction, with the current time; the ID is assigned when added to the history
+    pub fn new(
+        direction: TxDirection,
+        kind: TxKind,
+        amount: u64,
+        fee: u64,
+        mint_url: &str,
+        reference: &str,
+        status: TxStatus,
+    ) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        Self {
+            id: 0,
+            direction,
+            kind,
+            amount,
+            fee,
+            mint_url: mint_url.to_owned(),
+            timestamp,
+            reference: reference.to_owned(),
+            status,
+            memo: None,
+        }
+    }
+
+    pub fn with_memo(mut self, memo: &str) -> Self {
+        self.memo = Some(memo.to_owned());
+        self
+    }
+
+    /// The time of the transaction, as UTC date and time, e.g. `2025-03-01 14:05:09`
+    pub fn time_string(&self) -> String {
+        format_unix_time(self.timestamp)
+    }
+}
+
+/// Format a unix time as UTC date and time, e.g. `2025-03-01 14:05:09`
+pub fn format_unix_time(timestamp: u64) -> String {
+    let days = (timestamp / 86400) as i64;
+    let secs = timestamp % 86400;
+    // Civil date from days since the epoch, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
+
+impl fmt::Display for TxDirection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TxDirection::Incoming => f.pad("in"),
+            TxDirection::Outgoing => f.pad("out"),
+        }
+    }
+}
+
+impl fmt::Display for TxKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TxKind::Lightning => f.pad("LN"),
+            TxKind::Ecash => f.pad("ecash"),
+        }
+    }
+}
+
+impl fmt::Display for TxStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TxStatus::Pending => f.pad("pending"),
+            TxStatus::Completed => f.pad("completed"),
+            TxStatus::Failed => f.pad("failed"),
+            TxStatus::Reclaimed => f.pad("reclaimed"),
+        }
+    }
+}
+
+/// Filter and paging for querying the transaction history.
+/// The default is all transactions, newest first.
+#[derive(Clone, Debug, Default)]
+pub struct HistoryFilter {
+    pub direction: Option<TxDirection>,
+    pub kind: Option<TxKind>,
+    pub status: Option<TxStatus>,
+    pub mint_url: Option<String>,
+    /// Number of (matching) transactions to skip, from the newest
+    pub offset: usize,
+    /// Maximum number of transactions to return, no limit if None
+    pub limit: Option<usize>,
+}
+
+impl HistoryFilter {
+    /// Create the filter from command arguments, in any order:
+    /// `in`/`out`, `ln`/`ecash`, `pending`/`completed`/`failed`/`reclaimed`, a mint URL,
+    /// and the number of transactions to show and to skip (e.g. `10 20`).
+    pub fn from_args(args: &[&str]) -> Result<Self, String> {
+        let mut filter = Self::default();
+        for arg in args {
+            match arg.to_lowercase().as_str() {
+                "in" | "incoming" => filter.direction = Some(TxDirection::Incoming),
+                "out" | "outgoing" => filter.direction = Some(TxDirection::Outgoing),
+                "ln" | "lightning" => filter.kind = Some(TxKind::Lightning),
+                "ecash" | "ec" => filter.kind = Some(TxKind::Ecash),
+                "pending" => filter.status = Some(TxStatus::Pending),
+                "completed" => filter.status = Some(TxStatus::Completed),
+                "failed" => filter.status = Some(TxStatus::Failed),
+                "reclaimed" => filter.status = Some(TxStatus::Reclaimed),
+                _ if arg.starts_with("http") => filter.mint_url = Some(arg.to_string()),
+                _ => {
+                    let number = arg
+                        .parse::<usize>()
+                        .map_err(|_| format!("Invalid history argument {}", arg))?;
+                    if filter.limit.is_none() {
+                        filter.limit = Some(number);
+                    } else {
+                        filter.offset = number;
+                    }
+                }
+            }
+        }
+        Ok(filter)
+    }
+
+    fn matches(&self, tx: &Transaction) -> bool {
+        self.direction.is_none_or(|d| d == tx.direction)
+            && self.kind.is_none_or(|k| k == tx.kind)
+            && self.status.is_none_or(|s| s == tx.status)
+            && self
+                .mint_url
+                .as_ref()
+                .is_none_or(|m| m.trim_end_matches('/') == tx.mint_url.trim_end_matches('/'))
+    }
+}
+
+/// Persistent transaction log, stored in a JSON file, encrypted with a key derived from the seed
+/// (the references contain bearer tokens).
+/// The whole log is kept in memory, and the file is rewritten on every change.
+pub struct TransactionHistory {
+    file_path: PathBuf,
+    key: [u8; 32],
+    transactions: Vec<Transaction>,
+}
+
+/// The format of the history file: the transactions as JSON, encrypted with ChaCha20-Poly1305
+#[derive(Serialize, Deserialize)]
+struct EncryptedHistory {
+    version: u8,
+    /// Hex
+    nonce: String,
+    /// Hex
+    data: String,
+}
+
+const HISTORY_FILE_VERSION: u8 = 1;
+const HISTORY_KEY_TAG: &[u8] = b"parakesh/history";
+
+impl TransactionHistory {
+    /// Load the history from the file; empty if the file does not exist yet.
+    /// The encryption key is derived from the wallet seed.
+    /// A file that cannot be parsed or decrypted is moved away (with a warning) and
+    /// an empty history is started, so the wallet can still be opened.
+    pub fn load(file_path: &Path, seed: &[u8; 32]) -> Result<Self, PkError> {
+        let key = Self::derive_key(seed);
+        let transactions = match std::fs::read_to_string(file_path) {
+            Ok(contents) => match Self::decode(&contents, &key) {
+                Ok(transactions) => transactions,
+                Err(e) => {
+                    let backup_path = Self::backup_path(file_path);
+                    std::fs::rename(file_path, &backup_path).map_err(|e| {
+                        PkError::Storage(format!(
+                            "Could not move away invalid history file {}, {}",
+                            file_path.display(),
+                            e
+                        ))
+                    })?;
+                    eprintln!(
+                        "WARNING: Could not read history file {} ({}), moved to {}, starting with empty history",
+                        file_path.display(),
+                        e,
+                        backup_path.display()
+                    );
+                    Vec::new()
+                }
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => {
+                return Err(PkError::Storage(format!(
+                    "Could not read history file {}, {}",
+                    file_path.display(),
+                    e
+                )))
+            }
+        };
+        Ok(Self {
+            file_path: file_path.to_path_buf(),
+            key,
+            transactions,
+        })
+    }
+
+    fn derive_key(seed: &[u8; 32]) -> [u8; 32] {
+        let mut engine = sha256::Hash::engine();
+        engine.input(HISTORY_KEY_TAG);
+        engine.input(seed);
+        sha256::Hash::from_engine(engine).to_byte_array()
+    }
+
+    /// The file name to move an invalid history file to, not overwriting earlier ones
+    fn backup_path(file_path: &Path) -> PathBuf {
+        let mut n = 0;
+        loop {
+            let mut name = file_path.as_os_str().to_owned();
+            match n {
+                0 => name.push(".corrupt"),
+                _ => name.push(format!(".corrupt{}", n)),
+            }
+            let path = PathBuf::from(name);
+            if !path.exists() {
+                return path;
+            }
+            n += 1;
+        }
+    }
+
+    /// Decrypt and parse the file contents.
+    /// A plain JSON list (written by earlier versions) is also accepted, it gets encrypted on the next save.
+    fn decode(contents: &str, key: &[u8; 32]) -> Result<Vec<Transaction>, String> {
+        if contents.trim_start().starts_with('[') {
+            return serde_json::from_str(contents).map_err(|e| e.to_string());
+        }
+        let encrypted: EncryptedHistory =
+            serde_json::from_str(contents).map_err(|e| e.to_string())?;
+        if encrypted.version != HISTORY_FILE_VERSION {
+            return Err(format!("unsupported version {}", encrypted.version));
+        }
+        let nonce = Vec::<u8>::from_hex(&encrypted.nonce).map_err(|e| e.to_string())?;
+        if nonce.len() != 12 {
+            return Err("invalid nonce".to_owned());
+        }
+        let data = Vec::<u8>::from_hex(&encrypted.data).map_err(|e| e.to_string())?;
+        let plain = ChaCha20Poly1305::new(Key::from_slice(key))
+            .decrypt(Nonce::from_slice(&nonce), data.as_ref())
+            .map_err(|_| "decryption failed".to_owned())?;
+        serde_json::from_slice(&plain).map_err(|e| e.to_string())
+    }
+
+    fn encode(&self) -> Result<String, PkError> {
+        let plain =
+            serde_json::to_vec(&self.transactions).map_err(|e| PkError::Storage(e.to_string()))?;
+        let mut nonce = [0u8; 12];
+        rand::rng().fill_bytes(&mut nonce);
+        let data = ChaCha20Poly1305::new(Key::from_slice(&self.key))
+            .encrypt(Nonce::from_slice(&nonce), plain.as_ref())
+            .map_err(|_| PkError::Storage("Could not encrypt history".to_owned()))?;
+        serde_json::to_string(&EncryptedHistory {
+            version: HISTORY_FILE_VERSION,
+            nonce: nonce.to_lower_hex_string(),
+            data: data.to_lower_hex_string(),
+        })
+        .map_err(|e| PkError::Storage(e.to_string()))
+    }
+
+    /// Write the file: to a temporary file first, which then replaces the old one,
+    /// so an interrupted write does not lose the history.
+    fn save(&self) -> Result<(), PkError> {
+        let contents = self.encode()?;
+        let mut tmp_path = self.file_path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+        let tmp_path = PathBuf::from(tmp_path);
+        std::fs::write(&tmp_path, contents)
+            .and_then(|_| std::fs::rename(&tmp_path, &self.file_path))
+            .map_err(|e| {
+                PkError::Storage(format!(
+                    "Could not write history file {}, {}",
+                    self.file_path.display(),
+                    e
+                ))
+            })
+    }
+
+    /// Add a new transaction with a new ID, and save. Returns the ID.
+    /// If saving fails, the transaction is not added.
+    pub fn add(&mut self, mut tx: Transaction) -> Result<u64, PkError> {
+        tx.id = self.transactions.last().map(|tx| tx.id + 1).unwrap_or(1);
+        let id = tx.id;
+        self.transactions.push(tx);
+        if let Err(e) = self.save() {
+            self.transactions.pop();
+            return Err(e);
+        }
+        Ok(id)
+    }
+
+    /// Update the status (and the amount and fee) of a transaction, and save.
+    /// If saving fails, the transaction is left unchanged.
+    pub fn update(
+        &mut self,
+        id: u64,
+        status: TxStatus,
+        amount: u64,
+        fee: u64,
+    ) -> Result<(), PkError> {
+        let index = self
+            .transactions
+            .iter()
+            .position(|tx| tx.id == id)
+            .ok_or_else(|| PkError::InvalidInput(format!("Unknown transaction {}", id)))?;
+        let old = self.transactions[index].clone();
+        let tx = &mut self.transactions[index];
+        tx.status = status;
+        tx.amount = amount;
+        tx.fee = fee;
+        if let Err(e) = self.save() {
+            self.transactions[index] = old;
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Get a transaction by its ID
+    pub fn get(&self, id: u64) -> Option<&Transaction> {
+        self.transactions.iter().find(|tx| tx.id == id)
+    }
+
+    /// Find a transaction by its reference (invoice or token), returns its ID
+    pub fn find_by_reference(&self, reference: &str) -> Option<u64> {
+        self.transactions
+            .iter()
+            .rev()
+            .find(|tx| tx.reference == reference)
+            .map(|tx| tx.id)
+    }
+
+    /// The transactions matching the filter, newest first, with paging
+    pub fn list(&self, filter: &HistoryFilter) -> Vec<Transaction> {
+        self.transactions
+            .iter()
+            .rev()
+            .filter(|tx| filter.matches(tx))
+            .skip(filter.offset)
+            .take(filter.limit.unwrap_or(usize::MAX))
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEED: [u8; 32] = [7; 32];
+    const TOKEN: &str = "cashuBo2FteBtodHRwczovL21pbnQuZXhhbXBsZS5jb20vYWJj";
+
+    fn temp_file(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "parakesh-history-test-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.join("history.json")
+    }
+
+    fn sent_token() -> Transaction {
+        Transaction::new(
+            TxDirection::Outgoing,
+            TxKind::Ecash,
+            21,
+            0,
+            "https://mint.example.com",
+            TOKEN,
+            TxStatus::Pending,
+        )
+    }
+
+    #[test]
+    fn save_and_load_encrypted() {
+        let path = temp_file("roundtrip");
+        let mut history = TransactionHistory::load(&path, &SEED).unwrap();
+        let id = history.add(sent_token()).unwrap();
+        history.update(id, TxStatus::Completed, 21, 1).unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert!(!contents.contains(TOKEN));
+        assert!(!contents.contains("mint.example.com"));
+
+        let history = TransactionHistory::load(&path, &SEED).unwrap();
+        let tx = history.get(id).unwrap();
+        assert_eq!(tx.reference, TOKEN);
+        assert_eq!(tx.status, TxStatus::Completed);
+        assert_eq!(tx.fee, 1);
+        assert_eq!(history.find_by_reference(TOKEN), Some(id));
+    }
+
+    #[test]
+    fn load_plain_legacy_file() {
+        let path = temp_file("legacy");
+        let mut tx = sent_token();
+        tx.id = 5;
+        std::fs::write(&path, serde_json::to_string(&vec![tx]).unwrap()).unwrap();
+
+        let mut history = TransactionHistory::load(&path, &SEED).unwrap();
+        assert_eq!(history.get(5).unwrap().reference, TOKEN);
+        assert_eq!(history.add(sent_token()).unwrap(), 6);
+        assert!(!std::fs::read_to_string(&path).unwrap().contains(TOKEN));
+    }
+
+    #[test]
+    fn invalid_file_is_moved_away() {
+        let path = temp_file("corrupt");
+        std::fs::write(&path, "{ not json").unwrap();
+        let history = TransactionHistory::load(&path, &SEED).unwrap();
+        assert!(history.list(&HistoryFilter::default()).is_empty());
+        assert!(!path.exists());
+        let backup = path.with_file_name("history.json.corrupt");
+        assert_eq!(std::fs::read_to_string(backup).unwrap(), "{ not json");
+
+        // encrypted with a different key
+        let mut history = TransactionHistory::load(&path, &[8; 32]).unwrap();
+        history.add(sent_token()).unwrap();
+        let history = TransactionHistory::load(&path, &SEED).unwrap();
+        assert!(history.list(&HistoryFilter::default()).is_empty());
+        assert!(path.with_file_name("history.json.corrupt1").exists());
+    }
+
+    #[test]
+    fn failed_save_leaves_history_unchanged() {
+        let path = temp_file("readonly")
+            .with_file_name("missing")
+            .join("history.json");
+        let mut history = TransactionHistory::load(&path, &SEED).unwrap();
+        assert!(history.add(sent_token()).is_err());
+        assert!(history.list(&HistoryFilter::default()).is_empty());
+    }
+}
@@ -0,0 +1,193 @@
+use crate::pk_multipart::MultipartDecoder;
+use cdk::mint_url::MintUrl;
+
+use std::str::FromStr;
+
+/// URI schemes accepted in front of invoices and tokens
+const URI_PREFIXES: [&str; 2] = ["lightning:", "cashu:"];
+/// Human-readable prefixes of BOLT11 invoices (mainnet, testnet, signet, regtest)
+const INVOICE_PREFIXES: [&str; 4] = ["lnbc", "lntbs", "lntb", "lnbcrt"];
+
+/// The kind of a user input, e.g. a pasted string, see `classify_input`.
+/// The payload is the input without URI prefix and surrounding whitespace.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InputKind {
+    /// Cashu ecash token (`cashuA...` or `cashuB...`)
+    Token(String),
+    /// BOLT11 Lightning invoice
+    Invoice(String),
+    /// Cashu payment request (NUT-18, `creqA...`)
+    PaymentRequest(String),
+    MintUrl(String),
+    Unknown,
+}
+
+impl InputKind {
+    /// Name of the kind, for messages
+    pub fn name(&self) -> &'static str {
+        match self {
+            InputKind::Token(_) => "ecash token",
+            InputKind::Invoice(_) => "Lightning invoice",
+            InputKind::PaymentRequest(_) => "payment request",
+            InputKind::MintUrl(_) => "mint URL",
+            InputKind::Unknown => "unknown input",
+        }
+    }
+}
+
+/// Recognize the kind of a user input. Only the format is checked, not the validity
+/// (e.g. the checksum of an invoice); that is done when the input is used.
+/// The parts of a multi-part string (`ur:bytes/...`, from an animated QR code),
+/// separated by whitespace, are joined first.
+pub fn classify_input(input: &str) -> InputKind {
+    let mut input = input.trim();
+    if MultipartDecoder::is_part(input) {
+        return match decode_multipart(input) {
+            Some(decoded) => classify_input(&decoded),
+            None => InputKind::Unknown,
+        };
+    }
+    for prefix in URI_PREFIXES {
+        if input.len() > prefix.len()
+            && input
+                .get(..prefix.len())
+                .is_some_and(|p| p.eq_ignore_ascii_case(prefix))
+        {
+            input = input[prefix.len()..].trim_start_matches('/');
+            break;
+        }
+    }
+    if input.is_empty() || input.contains(char::is_whitespace) {
+        return InputKind::Unknown;
+    }
+    // Bech32 strings may be all uppercase (e.g. in QR codes)
+    let lowercase = input.to_lowercase();
+
+    if input.starts_with("cashuA") || input.starts_with("cashuB") {
+        InputKind::Token(input.to_owned())
+    } else if input.starts_with("creqA") {
+        InputKind::PaymentRequest(input.to_owned())
+    } else if INVOICE_PREFIXES.iter().any(|p| lowercase.starts_with(p)) {
+        InputKind::Invoice(lowercase)
+    } else if lowercase.starts_with("http://") || lowercase.starts_with("https://") {
+        match MintUrl::from_str(input) {
+            Ok(mint_url) => InputKind::MintUrl(mint_url.to_string()),
+            Err(_) => InputKind::Unknown,
+        }
+    } else {
+        InputKind::Unknown
+    }
+}
+
+/// Join the parts of a multi-part string, separated by whitespace; invalid parts are skipped
+fn decode_multipart(input: &str) -> Option<String> {
+    let mut decoder = MultipartDecoder::new();
+    input
+        .split_whitespace()
+        .find_map(|part| decoder.receive_part(part).ok().flatten())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pk_multipart::{encode_parts, DEFAULT_FRAGMENT_LEN, MIN_FRAGMENT_LEN};
+
+    const INVOICE: &str = "lnbc10n1pn0p2zcpp5xvuyq3p0s8q4qdqqcqzzsxqyz5vqsp5";
+
+    #[test]
+    fn classify_tokens_and_requests() {
+        assert_eq!(
+            classify_input(" cashuBo2FteBtodHRwczovL21pbnQ \n"),
+            InputKind::Token("cashuBo2FteBtodHRwczovL21pbnQ".to_owned())
+        );
+        assert_eq!(
+            classify_input("cashu:cashuAeyJ0b2tlbiI6"),
+            InputKind::Token("cashuAeyJ0b2tlbiI6".to_owned())
+        );
+        assert_eq!(
+            classify_input("creqApWF0gaNhdGVub3N0cg"),
+            InputKind::PaymentRequest("creqApWF0gaNhdGVub3N0cg".to_owned())
+        );
+    }
+
+    #[test]
+    fn classify_lightning() {
+        assert_eq!(
+            classify_input(INVOICE),
+            InputKind::Invoice(INVOICE.to_owned())
+        );
+        // uppercase, with URI prefix, as in QR codes
+        assert_eq!(
+            classify_input(&format!("LIGHTNING:{}", INVOICE.to_uppercase())),
+            InputKind::Invoice(INVOICE.to_owned())
+        );
+    }
+
+    #[test]
+    fn classify_mint_url() {
+        assert_eq!(
+            classify_input("https://mint.example.com/"),
+            InputKind::MintUrl("https://mint.example.com".to_owned())
+        );
+    }
+
+    #[test]
+    fn classify_multipart() {
+        let token = format!("cashuB{}", "o2FteBtodHRwczovL21pbnQ".repeat(20));
+        let parts = encode_parts(&token, MIN_FRAGMENT_LEN);
+        assert!(parts.len() > 2);
+        // in any order, uppercase as read from a QR code
+        let mut input: Vec<String> = parts.iter().rev().map(|p| p.to_uppercase()).collect();
+        assert_eq!(
+            classify_input(&input.join(" ")),
+            InputKind::Token(token.clone())
+        );
+        assert_eq!(classify_input(&parts.join("\n")), InputKind::Token(token));
+        // not all parts
+        input.truncate(1);
+        assert_eq!(classify_input(&input.join(" ")), InputKind::Unknown);
+
+        let parts = encode_parts(INVOICE, DEFAULT_FRAGMENT_LEN);
+        assert_eq!(parts.len(), 1);
+        assert_eq!(
+            classify_input(&parts[0]),
+            InputKind::Invoice(INVOICE.to_owned())
+        );
+    }
+
+    #[test]
+    fn classify_unknown() {
+        for input in [
+            "",
+            "   ",
+            "lightning:",
+            "cashu:",
+            "hello world",
+            "cashu token",
+            "@example.com",
+            "user@example",
+            "12345",
+            // LNURL and Lightning addresses are not supported (no LNURL client)
+            "lightning:LNURL1DP68GURN8GHJ7",
+            "satoshi@example.com",
+            "ur:bytes/1-2/lpadao",
+        ] {
+            assert_eq!(classify_input(input), InputKind::Unknown, "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn classify_non_ascii() {
+        // a multi-byte char across the length of a URI prefix must not panic
+        for input in [
+            "€€€€",
+            "€€€€€€€",
+            "lightning€",
+            "cashu€€",
+            "ñlightning:lnbc",
+            "ü@ex.com",
+        ] {
+            assert_eq!(classify_input(input), InputKind::Unknown, "{:?}", input);
+        }
+    }
+}
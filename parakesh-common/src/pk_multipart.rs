@@ -0,0 +1,713 @@
+//! Multi-part encoding of long strings (e.g. ecash tokens) for animated QR codes,
+//! in the Uniform Resources format (BC-UR, BCR-2020-005), as `ur:bytes/...` parts:
+//! the string is wrapped in a CBOR byte string, split into fragments, and sent as
+//! fountain-coded parts, so the parts can be read in any order, and missed ones are
+//! made up by later parts.
+
+use crate::pk_error::PkError;
+use cdk_common::bitcoin::hashes::{sha256, Hash};
+
+use std::collections::BTreeSet;
+
+/// Default maximum length of the fragments (bytes), fits well in a QR code readable by phones
+pub const DEFAULT_FRAGMENT_LEN: usize = 200;
+/// Minimum of the maximum fragment length, shorter would make too many parts
+pub const MIN_FRAGMENT_LEN: usize = 50;
+/// Lower limit of the fragment length when spreading the message evenly over the parts
+const MIN_NOMINAL_FRAGMENT_LEN: usize = 10;
+/// Maximum length of a message accepted by the decoder, this limits the memory used
+const MAX_MESSAGE_LEN: usize = 1 << 20;
+/// Maximum number of fragments accepted by the decoder
+const MAX_FRAGMENT_COUNT: usize = 1000;
+/// The only UR type used: the string as a CBOR byte string
+const UR_PREFIX: &str = "ur:bytes/";
+
+/// The 256 Bytewords; a byte is encoded with the first and last letter of its word
+const BYTEWORDS: [&str; 256] = [
+    "able", "acid", "also", "apex", "aqua", "arch", "atom", "aunt", "away", "axis", "back", "bald",
+    "barn", "belt", "beta", "bias", "blue", "body", "brag", "brew", "bulb", "buzz", "calm", "cash",
+    "cats", "chef", "city", "claw", "code", "cola", "cook", "cost", "crux", "curl", "cusp", "cyan",
+    "dark", "data", "days", "deli", "dice", "diet", "door", "down", "draw", "drop", "drum", "dull",
+    "duty", "each", "easy", "echo", "edge", "epic", "even", "exam", "exit", "eyes", "fact", "fair",
+    "fern", "figs", "film", "fish", "fizz", "flap", "flew", "flux", "foxy", "free", "frog", "fuel",
+    "fund", "gala", "game", "gear", "gems", "gift", "girl", "glow", "good", "gray", "grim", "guru",
+    "gush", "gyro", "half", "hang", "hard", "hawk", "heat", "help", "high", "hill", "holy", "hope",
+    "horn", "huts", "iced", "idea", "idle", "inch", "inky", "into", "iris", "iron", "item", "jade",
+    "jazz", "join", "jolt", "jowl", "judo", "jugs", "jump", "junk", "jury", "keep", "keno", "kept",
+    "keys", "kick", "kiln", "king", "kite", "kiwi", "knob", "lamb", "lava", "lazy", "leaf", "legs",
+    "liar", "limp", "lion", "list", "logo", "loud", "love", "luau", "luck", "lung", "main", "many",
+    "math", "maze", "memo", "menu", "meow", "mild", "mint", "miss", "monk", "nail", "navy", "need",
+    "news", "next", "noon", "note", "numb", "obey", "oboe", "omit", "onyx", "open", "oval", "owls",
+    "paid", "part", "peck", "play", "plus", "poem", "pool", "pose", "puff", "puma", "purr", "quad",
+    "quiz", "race", "ramp", "real", "redo", "rich", "road", "rock", "roof", "ruby", "ruin", "runs",
+    "rust", "safe", "saga", "scar", "sets", "silk", "skew", "slot", "soap", "solo", "song", "stub",
+    "surf", "swan", "taco", "task", "taxi", "tent", "tied", "time", "tiny", "toil", "tomb", "toys",
+    "trip", "tuna", "twin", "ugly", "undo", "unit", "urge", "user", "vast", "very", "veto", "vial",
+    "vibe", "view", "visa", "void", "vows", "wall", "wand", "warm", "wasp", "wave", "waxy", "webs",
+    "what", "when", "whiz", "wolf", "work", "yank", "yawn", "yell", "yoga", "yurt", "zaps", "zero",
+    "zest", "zinc", "zone", "zoom",
+];
+
+/// Split a long string (e.g. an ecash token) into parts, for an animated QR code.
+/// The fragments are at most `max_fragment_len` bytes long (but not shorter than
+/// `MIN_FRAGMENT_LEN`). A string fitting in one fragment gives a single-part UR
+/// (`ur:bytes/<data>`). Otherwise there are twice as many parts as fragments
+/// (`ur:bytes/<seq>-<count>/<data>`): the fragments one by one, then as many mixed parts,
+/// to be shown in a loop. The parts can be read with `MultipartDecoder`, or any BC-UR decoder.
+pub fn encode_parts(data: &str, max_fragment_len: usize) -> Vec<String> {
+    let message = cbor_bytes(data.as_bytes());
+    let fragment_len = nominal_fragment_len(message.len(), max_fragment_len.max(MIN_FRAGMENT_LEN));
+    let count = message.len().div_ceil(fragment_len);
+    if count == 1 {
+        return vec![format!("{}{}", UR_PREFIX, bytewords_encode(&message))];
+    }
+    encode_message_parts(&message, fragment_len, 2 * count)
+}
+
+/// The first `part_count` parts of the message, split into fragments of `fragment_len`
+fn encode_message_parts(message: &[u8], fragment_len: usize, part_count: usize) -> Vec<String> {
+    let count = message.len().div_ceil(fragment_len);
+    let checksum = crc32fast::hash(message);
+    let fragments: Vec<Vec<u8>> = message
+        .chunks(fragment_len)
+        .map(|chunk| {
+            let mut fragment = chunk.to_vec();
+            fragment.resize(fragment_len, 0);
+            fragment
+        })
+        .collect();
+    (1..=part_count as u32)
+        .map(|seq| {
+            let mut fragment = vec![0; fragment_len];
+            for index in choose_fragments(seq, count, checksum) {
+                xor_into(&mut fragment, &fragments[index]);
+            }
+            let mut body = Vec::new();
+            cbor_head(&mut body, 4, 5);
+            cbor_head(&mut body, 0, seq as u64);
+            cbor_head(&mut body, 0, count as u64);
+            cbor_head(&mut body, 0, message.len() as u64);
+            cbor_head(&mut body, 0, checksum as u64);
+            cbor_head(&mut body, 2, fragment_len as u64);
+            body.extend_from_slice(&fragment);
+            format!("{}{}-{}/{}", UR_PREFIX, seq, count, bytewords_encode(&body))
+        })
+        .collect()
+}
+
+/// The parameters of a multi-part message, the same in all its parts
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct MessageParams {
+    count: usize,
+    message_len: usize,
+    checksum: u32,
+    fragment_len: usize,
+}
+
+/// A received part: the indexes of the fragments mixed in it, and their XOR
+#[derive(Clone, Debug)]
+struct MixedPart {
+    indexes: BTreeSet<usize>,
+    data: Vec<u8>,
+}
+
+/// Collects the parts of a multi-part string, see `encode_parts`.
+/// The parts can come in any order, repeated (e.g. read from a looping animated QR code).
+/// A part of a different string starts over with that string.
+#[derive(Clone, Debug, Default)]
+pub struct MultipartDecoder {
+    params: Option<MessageParams>,
+    fragments: Vec<Option<Vec<u8>>>,
+    /// Parts mixing several fragments, not resolved yet
+    mixed: Vec<MixedPart>,
+}
+
+impl MultipartDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Check if the string is a part of a multi-part string (or a single-part UR)
+    pub fn is_part(s: &str) -> bool {
+        s.get(..UR_PREFIX.len())
+            .is_some_and(|p| p.eq_ignore_ascii_case(UR_PREFIX))
+    }
+
+    /// Drop the parts received so far
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Add a part. Returns the whole string once all parts have been received.
+    /// Invalid parts are rejected; if the whole string does not match its checksum,
+    /// the decoder is reset.
+    pub fn receive_part(&mut self, part: &str) -> Result<Option<String>, PkError> {
+        self.receive_message_part(part)?
+            .map(|message| decode_message(&message))
+            .transpose()
+    }
+
+    /// Add a part, returns the whole message (the CBOR-encoded data) when complete
+    fn receive_message_part(&mut self, part: &str) -> Result<Option<Vec<u8>>, PkError> {
+        let invalid = || PkError::InvalidInput(format!("Invalid part {}", part));
+        if !Self::is_part(part) {
+            return Err(invalid());
+        }
+        let path = &part[UR_PREFIX.len()..];
+        let (seq, body) = match path.split_once('/') {
+            Some((seq, body)) => (Some(seq), body),
+            None => (None, path),
+        };
+        let body = bytewords_decode(body).ok_or_else(invalid)?;
+        let Some(seq) = seq else {
+            // Single-part
+            self.reset();
+            return Ok(Some(body));
+        };
+        let (seq_num, params, fragment) = parse_part(seq, &body).ok_or_else(invalid)?;
+
+        if self.params != Some(params) {
+            // First part, or a part of a different message
+            self.reset();
+            self.params = Some(params);
+            self.fragments = vec![None; params.count];
+        }
+        let indexes = choose_fragments(seq_num, params.count, params.checksum);
+        self.add_part(MixedPart {
+            indexes: indexes.into_iter().collect(),
+            data: fragment.to_vec(),
+        });
+
+        if self.fragments.iter().any(|f| f.is_none()) {
+            return Ok(None);
+        }
+        let mut message: Vec<u8> = self.fragments.iter().flatten().flatten().copied().collect();
+        message.truncate(params.message_len);
+        self.reset();
+        if crc32fast::hash(&message) != params.checksum {
+            return Err(PkError::InvalidInput(
+                "Checksum mismatch in multi-part string".to_owned(),
+            ));
+        }
+        Ok(Some(message))
+    }
+
+    /// The number of fragments received so far, and the total number of fragments
+    /// (0 if not known yet)
+    pub fn progress(&self) -> (usize, usize) {
+        let received = self.fragments.iter().filter(|f| f.is_some()).count();
+        (received, self.fragments.len())
+    }
+
+    /// Add a part: remove the known fragments from it, and if a single fragment remains,
+    /// store it, and remove it from the other parts, which may resolve more fragments
+    fn add_part(&mut self, part: MixedPart) {
+        let mut queue = vec![part];
+        while let Some(mut part) = queue.pop() {
+            let known: Vec<usize> = part
+                .indexes
+                .iter()
+                .copied()
+                .filter(|i| self.fragments[*i].is_some())
+                .collect();
+            for index in known {
+                if let Some(fragment) = &self.fragments[index] {
+                    xor_into(&mut part.data, fragment);
+                }
+                part.indexes.remove(&index);
+            }
+            match part.indexes.len() {
+                0 => {}
+                1 => {
+                    let index = *part.indexes.first().expect("one index");
+                    self.fragments[index] = Some(part.data);
+                    // The mixed parts containing this fragment are processed again
+                    let (affected, rest) = std::mem::take(&mut self.mixed)
+                        .into_iter()
+                        .partition(|p| p.indexes.contains(&index));
+                    self.mixed = rest;
+                    queue.extend(affected);
+                }
+                _ => {
+                    if !self.mixed.iter().any(|p| p.indexes == part.indexes) {
+                        self.mixed.push(part);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Parse the sequence (`<seq>-<count>`) and the body of a part,
+/// returns the sequence number, the message parameters, and the fragment
+fn parse_part<'a>(seq: &str, body: &'a [u8]) -> Option<(u32, MessageParams, &'a [u8])> {
+    let (seq_num, count) = seq.split_once('-')?;
+    let seq_num = seq_num.parse::<u32>().ok()?;
+    let count = count.parse::<usize>().ok()?;
+
+    let mut pos = 0;
+    if cbor_read_head(body, &mut pos)? != (4, 5) {
+        return None;
+    }
+    let mut read_uint = || match cbor_read_head(body, &mut pos)? {
+        (0, value) => Some(value),
+        _ => None,
+    };
+    let body_seq_num = read_uint()?;
+    let body_count = read_uint()?;
+    let message_len = read_uint()?;
+    let checksum = read_uint()?;
+    let (2, fragment_len) = cbor_read_head(body, &mut pos)? else {
+        return None;
+    };
+    let fragment = body.get(pos..)?;
+    if body_seq_num != seq_num as u64
+        || body_count != count as u64
+        || seq_num == 0
+        || checksum > u32::MAX as u64
+        || message_len == 0
+        || message_len > MAX_MESSAGE_LEN as u64
+        || fragment_len != fragment.len() as u64
+        || fragment.is_empty()
+        || count > MAX_FRAGMENT_COUNT
+        || message_len.div_ceil(fragment_len) != count as u64
+    {
+        return None;
+    }
+    let params = MessageParams {
+        count,
+        message_len: message_len as usize,
+        checksum: checksum as u32,
+        fragment_len: fragment.len(),
+    };
+    Some((seq_num, params, fragment))
+}
+
+/// Get the string from the message, a CBOR byte string
+fn decode_message(message: &[u8]) -> Result<String, PkError> {
+    let invalid = || PkError::InvalidInput("Invalid multi-part string".to_owned());
+    let mut pos = 0;
+    let (2, len) = cbor_read_head(message, &mut pos).ok_or_else(invalid)? else {
+        return Err(invalid());
+    };
+    if message.len() - pos != len as usize {
+        return Err(invalid());
+    }
+    String::from_utf8(message[pos..].to_vec()).map_err(|_| invalid())
+}
+
+/// The fragment length, to split the message evenly, with at most `max_fragment_len`
+fn nominal_fragment_len(message_len: usize, max_fragment_len: usize) -> usize {
+    let max_count = (message_len / MIN_NOMINAL_FRAGMENT_LEN).max(1);
+    (1..=max_count)
+        .map(|count| message_len.div_ceil(count))
+        .find(|len| *len <= max_fragment_len)
+        .unwrap_or(MIN_NOMINAL_FRAGMENT_LEN)
+}
+
+/// The indexes of the fragments mixed in the part with the sequence number:
+/// the first `count` parts are the fragments in order, the rest is pseudo-random
+fn choose_fragments(seq_num: u32, count: usize, checksum: u32) -> Vec<usize> {
+    if seq_num as usize <= count {
+        return vec![seq_num as usize - 1];
+    }
+    let mut seed = seq_num.to_be_bytes().to_vec();
+    seed.extend_from_slice(&checksum.to_be_bytes());
+    let mut rng = Xoshiro256::new(&seed);
+    let degree = choose_degree(count, &mut rng);
+    let mut remaining: Vec<usize> = (0..count).collect();
+    let mut chosen = Vec::with_capacity(degree);
+    while chosen.len() < degree {
+        let index = rng.next_int(0, remaining.len() as u64 - 1) as usize;
+        chosen.push(remaining.remove(index));
+    }
+    chosen
+}
+
+/// The number of fragments mixed in a part, between 1 and `count`,
+/// with probabilities proportional to 1/degree
+fn choose_degree(count: usize, rng: &mut Xoshiro256) -> usize {
+    let weights: Vec<f64> = (1..=count).map(|i| 1.0 / i as f64).collect();
+    RandomSampler::new(&weights).next(rng) + 1
+}
+
+/// Weighted random sampler, with Vose's alias method
+struct RandomSampler {
+    probs: Vec<f64>,
+    aliases: Vec<usize>,
+}
+
+impl RandomSampler {
+    fn new(weights: &[f64]) -> Self {
+        let n = weights.len();
+        let sum: f64 = weights.iter().sum();
+        let mut p: Vec<f64> = weights.iter().map(|w| w * n as f64 / sum).collect();
+        let mut small = Vec::new();
+        let mut large = Vec::new();
+        for i in (0..n).rev() {
+            if p[i] < 1.0 {
+                small.push(i);
+            } else {
+                large.push(i);
+            }
+        }
+        let mut probs = vec![0.0; n];
+        let mut aliases = vec![0; n];
+        while !small.is_empty() && !large.is_empty() {
+            let a = small.pop().expect("not empty");
+            let g = large.pop().expect("not empty");
+            probs[a] = p[a];
+            aliases[a] = g;
+            p[g] += p[a] - 1.0;
+            if p[g] < 1.0 {
+                small.push(g);
+            } else {
+                large.push(g);
+            }
+        }
+        for i in large.into_iter().chain(small) {
+            probs[i] = 1.0;
+        }
+        Self { probs, aliases }
+    }
+
+    fn next(&self, rng: &mut Xoshiro256) -> usize {
+        let r1 = rng.next_double();
+        let r2 = rng.next_double();
+        let i = (self.probs.len() as f64 * r1) as usize;
+        if r2 < self.probs[i] {
+            i
+        } else {
+            self.aliases[i]
+        }
+    }
+}
+
+/// The xoshiro256** generator, seeded with the SHA-256 hash of the seed, as used in BC-UR
+struct Xoshiro256 {
+    s: [u64; 4],
+}
+
+impl Xoshiro256 {
+    fn new(seed: &[u8]) -> Self {
+        let hash = sha256::Hash::hash(seed).to_byte_array();
+        let mut s = [0u64; 4];
+        for (i, chunk) in hash.chunks(8).enumerate() {
+            s[i] = u64::from_be_bytes(chunk.try_into().expect("8 bytes"));
+        }
+        Self { s }
+    }
+
+    fn next(&mut self) -> u64 {
+        let s = &mut self.s;
+        let result = s[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = s[1] << 17;
+        s[2] ^= s[0];
+        s[3] ^= s[1];
+        s[1] ^= s[2];
+        s[0] ^= s[3];
+        s[2] ^= t;
+        s[3] = s[3].rotate_left(45);
+        result
+    }
+
+    fn next_double(&mut self) -> f64 {
+        self.next() as f64 / (u64::MAX as f64 + 1.0)
+    }
+
+    /// Random integer in the closed range
+    fn next_int(&mut self, low: u64, high: u64) -> u64 {
+        (self.next_double() * (high - low + 1) as f64) as u64 + low
+    }
+}
+
+fn xor_into(target: &mut [u8], other: &[u8]) {
+    for (t, o) in target.iter_mut().zip(other) {
+        *t ^= o;
+    }
+}
+
+/// Bytewords, minimal style: two letters per byte, with a CRC32 checksum appended
+fn bytewords_encode(data: &[u8]) -> String {
+    let checksum = crc32fast::hash(data).to_be_bytes();
+    data.iter()
+        .chain(checksum.iter())
+        .flat_map(|b| {
+            let word = BYTEWORDS[*b as usize].as_bytes();
+            [word[0] as char, word[3] as char]
+        })
+        .collect()
+}
+
+/// Decode Bytewords (minimal style, any case), checking the checksum
+fn bytewords_decode(s: &str) -> Option<Vec<u8>> {
+    let s = s.to_ascii_lowercase();
+    if !s.len().is_multiple_of(2) || !s.is_ascii() {
+        return None;
+    }
+    let mut bytes = s
+        .as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            BYTEWORDS
+                .iter()
+                .position(|w| w.as_bytes()[0] == pair[0] && w.as_bytes()[3] == pair[1])
+                .map(|i| i as u8)
+        })
+        .collect::<Option<Vec<u8>>>()?;
+    if bytes.len() < 4 {
+        return None;
+    }
+    let checksum = bytes.split_off(bytes.len() - 4);
+    (crc32fast::hash(&bytes).to_be_bytes()[..] == checksum[..]).then_some(bytes)
+}
+
+/// A CBOR byte string
+fn cbor_bytes(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() + 9);
+    cbor_head(&mut out, 2, data.len() as u64);
+    out.extend_from_slice(data);
+    out
+}
+
+/// Write a CBOR item head: major type and argument
+fn cbor_head(out: &mut Vec<u8>, major: u8, value: u64) {
+    let major = major << 5;
+    match value {
+        0..=23 => out.push(major | value as u8),
+        24..=0xff => out.extend_from_slice(&[major | 24, value as u8]),
+        0x100..=0xffff => {
+            out.push(major | 25);
+            out.extend_from_slice(&(value as u16).to_be_bytes());
+        }
+        0x10000..=0xffff_ffff => {
+            out.push(major | 26);
+            out.extend_from_slice(&(value as u32).to_be_bytes());
+        }
+        _ => {
+            out.push(major | 27);
+            out.extend_from_slice(&value.to_be_bytes());
+        }
+    }
+}
+
+/// Read a CBOR item head at the position, returns the major type and the argument
+fn cbor_read_head(data: &[u8], pos: &mut usize) -> Option<(u8, u64)> {
+    let first = *data.get(*pos)?;
+    *pos += 1;
+    let len = match first & 0x1f {
+        info @ 0..=23 => return Some((first >> 5, info as u64)),
+        24 => 1,
+        25 => 2,
+        26 => 4,
+        27 => 8,
+        _ => return None,
+    };
+    let bytes = data.get(*pos..*pos + len)?;
+    *pos += len;
+    let value = bytes.iter().fold(0u64, |v, b| (v << 8) | *b as u64);
+    Some((first >> 5, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOKEN: &str = "cashuBo2FteBtodHRwczovL3Rlc3RudXQuY2FzaHUuc3BhY2VhdWNzYXRhdIGiYWlIAJofKTJT5B5hcIGkYWEBYXN4QDk3ZGEwYWQxMjQ1ZmNkNDJjYTVhMWU2NWQ3MjJlNTA5YTE4ZDYzYjIzYmNlYWJmZjg5MDk3NzY5ZDg4NDgzMDJhY1ghA2JjZjRkMDc2ZTI2YzVhMmI0NTFjM2UzOWE0Y2E0YWE2YTY0NzRiZjA3NWI4YjYyYjJlNjdjYmY5NjY2OWJlMzRhZKNhZVggbSmQyJuEZ0VJXB0qTZzx5HmpY1l0yRBn8hKgQnOLXyZhc1ggeRkfbDZMVZ7ZDaZxhdKlZb10LJxQX4SgSgLJMjcrR3NhclggYT7bIg2YrzyyIK2Ke6lnOC7TFbkCR6YixgxPRzJsMWs";
+
+    /// The message of the BC-UR reference test vectors: 256 pseudo-random bytes
+    fn wolf_message() -> Vec<u8> {
+        let mut rng = Xoshiro256::new(b"Wolf");
+        let data: Vec<u8> = (0..256).map(|_| rng.next_int(0, 255) as u8).collect();
+        cbor_bytes(&data)
+    }
+
+    #[test]
+    fn bytewords_and_rng_match_reference() {
+        assert_eq!(bytewords_encode(&[0, 1, 2, 128, 255]), "aeadaolazmjendeoti");
+        assert_eq!(
+            bytewords_decode("AEADAOLAZMJENDEOTI"),
+            Some(vec![0, 1, 2, 128, 255])
+        );
+        assert_eq!(bytewords_decode("aeadaolazmjendeota"), None);
+
+        let mut rng = Xoshiro256::new(b"Wolf");
+        let numbers: Vec<u64> = (0..10).map(|_| rng.next() % 100).collect();
+        assert_eq!(numbers, [42, 81, 85, 8, 82, 84, 76, 73, 70, 88]);
+
+        let degrees: Vec<usize> = (1..=30)
+            .map(|i| choose_degree(11, &mut Xoshiro256::new(format!("Wolf-{}", i).as_bytes())))
+            .collect();
+        assert_eq!(
+            degrees,
+            [
+                11, 3, 6, 5, 2, 1, 2, 11, 1, 3, 9, 10, 10, 4, 2, 1, 1, 2, 1, 1, 5, 2, 4, 10, 3, 2,
+                1, 1, 3, 11
+            ]
+        );
+    }
+
+    #[test]
+    fn parts_match_reference() {
+        let message = wolf_message();
+        let parts = encode_message_parts(&message, nominal_fragment_len(message.len(), 30), 20);
+        assert_eq!(parts.len(), 20);
+        assert_eq!(parts[0], "ur:bytes/1-9/lpadascfadaxcywenbpljkhdcahkadaemejtswhhylkepmykhhtsytsnoyoyaxaedsuttydmmhhpktpmsrjtdkgslpgh");
+        assert_eq!(parts[1], "ur:bytes/2-9/lpaoascfadaxcywenbpljkhdcagwdpfnsboxgwlbaawzuefywkdplrsrjynbvygabwjldapfcsgmghhkhstlrdcxaefz");
+        assert_eq!(parts[8], "ur:bytes/9-9/lpasascfadaxcywenbpljkhdcajskecpmdckihdyhphfotjojtfmlnwmadspaxrkytbztpbauotbgtgtaeaevtgavtny");
+        assert_eq!(parts[9], "ur:bytes/10-9/lpbkascfadaxcywenbpljkhdcahkadaemejtswhhylkepmykhhtsytsnoyoyaxaedsuttydmmhhpktpmsrjtwdkiplzs");
+        assert_eq!(parts[11], "ur:bytes/12-9/lpbnascfadaxcywenbpljkhdcarllaluzmdmgstospeyiefmwejlwtpedamktksrvlcygmzemovovllarodtmtbnptrs");
+        assert_eq!(parts[19], "ur:bytes/20-9/lpbbascfadaxcywenbpljkhdcayapmrleeleaxpasfrtrdkncffwjyjzgyetdmlewtkpktgllepfrltataztksmhkbot");
+
+        let checksum = crc32fast::hash(&message);
+        assert_eq!(choose_fragments(13, 9, checksum), [3, 7, 8]);
+    }
+
+    #[test]
+    fn decode_from_mixed_parts() {
+        let message = wolf_message();
+        let parts = encode_message_parts(&message, nominal_fragment_len(message.len(), 30), 20);
+        let mut decoder = MultipartDecoder::new();
+        // Fragment 5 is not in any of the mixed parts 10-20, the others are
+        assert_eq!(decoder.receive_message_part(&parts[4]).unwrap(), None);
+        // Part 19 resolves the last fragments from the earlier mixed parts
+        for part in &parts[9..18] {
+            assert_eq!(decoder.receive_message_part(part).unwrap(), None);
+        }
+        let result = decoder.receive_message_part(&parts[18]).unwrap();
+        assert_eq!(result, Some(message));
+        assert_eq!(decoder.progress(), (0, 0));
+    }
+
+    #[test]
+    fn round_trip() {
+        let parts = encode_parts(TOKEN, MIN_FRAGMENT_LEN);
+        assert!(parts.len() > 2);
+        assert!(parts.iter().all(|p| MultipartDecoder::is_part(p)));
+        let count = parts.len() / 2;
+
+        let mut decoder = MultipartDecoder::new();
+        for (i, part) in parts[..count].iter().enumerate() {
+            let result = decoder.receive_part(part).unwrap();
+            if i + 1 < count {
+                assert_eq!(result, None);
+                assert_eq!(decoder.progress(), (i + 1, count));
+            } else {
+                assert_eq!(result.as_deref(), Some(TOKEN));
+            }
+        }
+
+        // Short strings fit in a single part; uppercase, as in a QR code
+        let parts = encode_parts("cashuAshort", DEFAULT_FRAGMENT_LEN);
+        assert_eq!(parts.len(), 1);
+        let result = decoder.receive_part(&parts[0].to_uppercase()).unwrap();
+        assert_eq!(result.as_deref(), Some("cashuAshort"));
+    }
+
+    #[test]
+    fn out_of_order_and_duplicate_parts() {
+        let parts = encode_parts(TOKEN, MIN_FRAGMENT_LEN);
+        let count = parts.len() / 2;
+        let mut decoder = MultipartDecoder::new();
+        let mut result = None;
+        for i in (0..count).rev().skip(1) {
+            assert_eq!(decoder.receive_part(&parts[i]).unwrap(), None);
+            assert_eq!(decoder.receive_part(&parts[i]).unwrap(), None);
+        }
+        assert_eq!(decoder.progress(), (count - 1, count));
+        // The missing fragment is recovered from the mixed parts
+        for part in &parts[count..] {
+            result = decoder.receive_part(part).unwrap();
+            if result.is_some() {
+                break;
+            }
+        }
+        assert_eq!(result.as_deref(), Some(TOKEN));
+    }
+
+    #[test]
+    fn checksum_mismatch() {
+        let parts = encode_parts(TOKEN, MIN_FRAGMENT_LEN);
+        let count = parts.len() / 2;
+        // A part of the same message length and count, but with a different fragment,
+        // and re-encoded with a valid bytewords checksum
+        let (seq, body) = parts[0]["ur:bytes/".len()..].split_once('/').unwrap();
+        let mut body = bytewords_decode(body).unwrap();
+        *body.last_mut().unwrap() ^= 1;
+        let tampered = format!("ur:bytes/{}/{}", seq, bytewords_encode(&body));
+
+        let mut decoder = MultipartDecoder::new();
+        assert_eq!(decoder.receive_part(&tampered).unwrap(), None);
+        for part in &parts[1..count - 1] {
+            assert_eq!(decoder.receive_part(part).unwrap(), None);
+        }
+        assert!(decoder.receive_part(&parts[count - 1]).is_err());
+        // Reset, starts over
+        assert_eq!(decoder.progress(), (0, 0));
+        for part in &parts[..count - 1] {
+            assert_eq!(decoder.receive_part(part).unwrap(), None);
+        }
+        let result = decoder.receive_part(&parts[count - 1]).unwrap();
+        assert_eq!(result.as_deref(), Some(TOKEN));
+    }
+
+    #[test]
+    fn different_message_resets() {
+        let parts1 = encode_parts(TOKEN, MIN_FRAGMENT_LEN);
+        let other = TOKEN.replace('a', "b");
+        let parts2 = encode_parts(&other, MIN_FRAGMENT_LEN);
+        let count = parts2.len() / 2;
+
+        let mut decoder = MultipartDecoder::new();
+        assert_eq!(decoder.receive_part(&parts1[0]).unwrap(), None);
+        assert_eq!(decoder.receive_part(&parts1[1]).unwrap(), None);
+        assert_eq!(decoder.progress().0, 2);
+        for part in &parts2[..count - 1] {
+            assert_eq!(decoder.receive_part(part).unwrap(), None);
+        }
+        assert_eq!(decoder.progress(), (count - 1, count));
+        let result = decoder.receive_part(&parts2[count - 1]).unwrap();
+        assert_eq!(result.as_deref(), Some(other.as_str()));
+    }
+
+    #[test]
+    fn malformed_parts() {
+        let parts = encode_parts(TOKEN, MIN_FRAGMENT_LEN);
+        let (seq, body) = parts[0]["ur:bytes/".len()..].split_once('/').unwrap();
+        let body_bytes = bytewords_decode(body).unwrap();
+        let mut decoder = MultipartDecoder::new();
+
+        let mut bad_crc = parts[0].clone();
+        bad_crc.pop();
+        bad_crc.push('a');
+        let mut header = body_bytes.clone();
+        header[0] = 0x84;
+        let bad_header = format!("ur:bytes/{}/{}", seq, bytewords_encode(&header));
+        // A count not matching the message length, too many fragments for the decoder
+        let mut big = Vec::new();
+        for (major, value) in [(4, 5), (0, 1), (0, 100_000), (0, 100_000), (0, 1)] {
+            cbor_head(&mut big, major, value);
+        }
+        cbor_head(&mut big, 2, 1);
+        big.push(0);
+        let too_many = format!("ur:bytes/1-100000/{}", bytewords_encode(&big));
+        let not_string = format!("ur:bytes/{}", bytewords_encode(&[0x18, 0x2a]));
+
+        for part in [
+            "cashuAabc",
+            "ur:bytes",
+            "ur:crypto-psbt/1-2/lpad",
+            "ur:bytes/1-2/xyz",
+            &bad_crc,
+            &format!("ur:bytes/2-{}", &parts[0]["ur:bytes/1-".len()..]),
+            &format!("ur:bytes/x-y/{}", body),
+            &bad_header,
+            &too_many,
+            &not_string,
+        ] {
+            assert!(decoder.receive_part(part).is_err(), "{}", part);
+            assert_eq!(decoder.progress(), (0, 0));
+        }
+        assert_eq!(decoder.receive_part(&parts[0]).unwrap(), None);
+    }
+}
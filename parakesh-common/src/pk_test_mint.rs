@@ -0,0 +1,277 @@
+//! A fake mint for the tests: implements the mint API (`MintConnector`) in memory,
+//! signing with fixed keys, with Lightning invoices paid on demand.
+
+use async_trait::async_trait;
+use cdk::dhke::sign_message;
+use cdk::nuts::{
+    BlindSignature, BlindedMessage, CheckStateRequest, CheckStateResponse, CurrencyUnit, Id,
+    KeySet, KeySetInfo, Keys, KeysetResponse, MeltBolt11Request, MeltQuoteBolt11Request,
+    MeltQuoteBolt11Response, MintBolt11Request, MintBolt11Response, MintInfo,
+    MintQuoteBolt11Request, MintQuoteBolt11Response, MintQuoteState, ProofState, PublicKey,
+    RestoreRequest, RestoreResponse, SecretKey, State, SwapRequest, SwapResponse,
+};
+use cdk::wallet::{AuthWallet, MintConnector};
+use cdk::{Amount, Error};
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Arc, Mutex};
+
+/// Number of keys of the keyset, for the amounts 1, 2, 4, ...
+const KEY_COUNT: u32 = 20;
+
+#[derive(Debug, Default)]
+struct FakeMintState {
+    /// Mint quotes by ID, with amount and state
+    mint_quotes: BTreeMap<String, (Amount, MintQuoteState)>,
+    /// Number of requests received
+    request_count: usize,
+    /// The Ys of the proofs spent in swaps; spending them again is not rejected
+    spent: BTreeSet<PublicKey>,
+}
+
+/// Fake mint, clones share the same state
+#[derive(Clone, Debug)]
+pub struct FakeMint {
+    keys: Arc<BTreeMap<Amount, SecretKey>>,
+    keyset_id: Id,
+    state: Arc<Mutex<FakeMintState>>,
+}
+
+impl FakeMint {
+    /// New mint, `key_seed` makes the keys (and the keyset) different for different mints
+    pub fn new(key_seed: u8) -> Self {
+        let keys: BTreeMap<Amount, SecretKey> = (0..KEY_COUNT)
+            .map(|i| {
+                let mut secret = [0u8; 32];
+                secret[30] = key_seed;
+                secret[31] = i as u8 + 1;
+                let secret = SecretKey::from_slice(&secret).expect("valid key");
+                (Amount::from(1u64 << i), secret)
+            })
+            .collect();
+        let keyset_id = Id::from(&Self::public_keys(&keys));
+        Self {
+            keys: Arc::new(keys),
+            keyset_id,
+            state: Arc::new(Mutex::new(FakeMintState::default())),
+        }
+    }
+
+    fn public_keys(keys: &BTreeMap<Amount, SecretKey>) -> Keys {
+        Keys::new(
+            keys.iter()
+                .map(|(amount, secret)| (*amount, secret.public_key()))
+                .collect(),
+        )
+    }
+
+    /// Mark a mint quote as paid, as if its invoice had been paid
+    pub fn pay_mint_quote(&self, quote_id: &str) {
+        if let Some(quote) = self.state.lock().unwrap().mint_quotes.get_mut(quote_id) {
+            quote.1 = MintQuoteState::Paid;
+        }
+    }
+
+    /// The number of API requests received so far
+    pub fn request_count(&self) -> usize {
+        self.state.lock().unwrap().request_count
+    }
+
+    /// Lock the state, counting a request
+    fn state(&self) -> std::sync::MutexGuard<'_, FakeMintState> {
+        let mut state = self.state.lock().unwrap();
+        state.request_count += 1;
+        state
+    }
+
+    fn count_request(&self) {
+        let _state = self.state();
+    }
+
+    fn sign(&self, outputs: &[BlindedMessage]) -> Result<Vec<BlindSignature>, Error> {
+        outputs
+            .iter()
+            .map(|output| {
+                let secret = self.keys.get(&output.amount).ok_or(Error::AmountKey)?;
+                Ok(BlindSignature {
+                    amount: output.amount,
+                    keyset_id: self.keyset_id,
+                    c: sign_message(secret, &output.blinded_secret)?,
+                    dleq: None,
+                })
+            })
+            .collect()
+    }
+
+    fn keyset(&self) -> KeySet {
+        KeySet {
+            id: self.keyset_id,
+            unit: CurrencyUnit::Sat,
+            keys: Self::public_keys(&self.keys),
+        }
+    }
+
+    fn mint_quote_response(
+        quote_id: &str,
+        amount: Amount,
+        state: MintQuoteState,
+    ) -> MintQuoteBolt11Response<String> {
+        MintQuoteBolt11Response {
+            quote: quote_id.to_owned(),
+            request: format!("lnbcfake{}", quote_id),
+            amount: Some(amount),
+            unit: Some(CurrencyUnit::Sat),
+            state,
+            expiry: None,
+            pubkey: None,
+        }
+    }
+
+    fn unsupported<T>(what: &str) -> Result<T, Error> {
+        Err(Error::Custom(format!(
+            "{} not supported by the fake mint",
+            what
+        )))
+    }
+}
+
+#[async_trait]
+impl MintConnector for FakeMint {
+    async fn get_mint_keys(&self) -> Result<Vec<KeySet>, Error> {
+        self.count_request();
+        Ok(vec![self.keyset()])
+    }
+
+    async fn get_mint_keyset(&self, keyset_id: Id) -> Result<KeySet, Error> {
+        self.count_request();
+        if keyset_id != self.keyset_id {
+            return Err(Error::UnknownKeySet);
+        }
+        Ok(self.keyset())
+    }
+
+    async fn get_mint_keysets(&self) -> Result<KeysetResponse, Error> {
+        self.count_request();
+        Ok(KeysetResponse {
+            keysets: vec![KeySetInfo {
+                id: self.keyset_id,
+                unit: CurrencyUnit::Sat,
+                active: true,
+                input_fee_ppk: 0,
+            }],
+        })
+    }
+
+    async fn post_mint_quote(
+        &self,
+        request: MintQuoteBolt11Request,
+    ) -> Result<MintQuoteBolt11Response<String>, Error> {
+        let mut state = self.state();
+        let quote_id = format!("quote{}", state.mint_quotes.len() + 1);
+        state
+            .mint_quotes
+            .insert(quote_id.clone(), (request.amount, MintQuoteState::Unpaid));
+        Ok(Self::mint_quote_response(
+            &quote_id,
+            request.amount,
+            MintQuoteState::Unpaid,
+        ))
+    }
+
+    async fn get_mint_quote_status(
+        &self,
+        quote_id: &str,
+    ) -> Result<MintQuoteBolt11Response<String>, Error> {
+        let state = self.state();
+        let (amount, quote_state) = state.mint_quotes.get(quote_id).ok_or(Error::UnknownQuote)?;
+        Ok(Self::mint_quote_response(quote_id, *amount, *quote_state))
+    }
+
+    async fn post_mint(
+        &self,
+        request: MintBolt11Request<String>,
+    ) -> Result<MintBolt11Response, Error> {
+        let mut state = self.state();
+        let quote = state
+            .mint_quotes
+            .get_mut(&request.quote)
+            .ok_or(Error::UnknownQuote)?;
+        if quote.1 != MintQuoteState::Paid {
+            return Err(Error::UnpaidQuote);
+        }
+        quote.1 = MintQuoteState::Issued;
+        Ok(MintBolt11Response {
+            signatures: self.sign(&request.outputs)?,
+        })
+    }
+
+    async fn post_melt_quote(
+        &self,
+        _request: MeltQuoteBolt11Request,
+    ) -> Result<MeltQuoteBolt11Response<String>, Error> {
+        Self::unsupported("melt")
+    }
+
+    async fn get_melt_quote_status(
+        &self,
+        _quote_id: &str,
+    ) -> Result<MeltQuoteBolt11Response<String>, Error> {
+        Self::unsupported("melt")
+    }
+
+    async fn post_melt(
+        &self,
+        _request: MeltBolt11Request<String>,
+    ) -> Result<MeltQuoteBolt11Response<String>, Error> {
+        Self::unsupported("melt")
+    }
+
+    async fn post_swap(&self, request: SwapRequest) -> Result<SwapResponse, Error> {
+        {
+            let mut state = self.state();
+            for proof in &request.inputs {
+                state.spent.insert(proof.y()?);
+            }
+        }
+        Ok(SwapResponse {
+            signatures: self.sign(&request.outputs)?,
+        })
+    }
+
+    async fn get_mint_info(&self) -> Result<MintInfo, Error> {
+        self.count_request();
+        Ok(MintInfo::default())
+    }
+
+    async fn post_check_state(
+        &self,
+        request: CheckStateRequest,
+    ) -> Result<CheckStateResponse, Error> {
+        let state = self.state();
+        Ok(CheckStateResponse {
+            states: request
+                .ys
+                .into_iter()
+                .map(|y| ProofState {
+                    y,
+                    state: if state.spent.contains(&y) {
+                        State::Spent
+                    } else {
+                        State::Unspent
+                    },
+                    witness: None,
+                })
+                .collect(),
+        })
+    }
+
+    async fn post_restore(&self, _request: RestoreRequest) -> Result<RestoreResponse, Error> {
+        Self::unsupported("restore")
+    }
+
+    async fn get_auth_wallet(&self) -> Option<AuthWallet> {
+        None
+    }
+
+    async fn set_auth_wallet(&self, _wallet: Option<AuthWallet>) {}
+}
@@ -1,7 +1,98 @@
-use parakesh_common::{MintsSummary, PKApp};
+use parakesh_common::pk_app::{
+    ConsolidateResult, DecodedInput, KeysetMigrationSummary, MintInfo, MintProofs, MppMeltResult,
+    RestoreProgress, SendEcashResult, WalletHealthReport,
+};
+use parakesh_common::pk_history::{format_unix_time, HistoryFilter, Transaction};
+use parakesh_common::pk_input::{classify_input, InputKind};
+use parakesh_common::{
+    AppEvent, MintsSummary, PKApp, PKAppConfig, PkError, SEED_VERIFY_WORD_COUNT,
+};
 
 use std::io;
 use std::io::Write;
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Number of password attempts when opening the wallet
+const OPEN_PASSWORD_ATTEMPTS: usize = 3;
+/// Environment variable for the wallet password, for non-interactive use
+const PASSWORD_ENV_VAR: &str = "PARAKESH_PASSWORD";
+
+/// With JSON output, stdout has only the JSON objects, one per line;
+/// other text (prompts, help, argument errors) goes to stderr
+static JSON_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+/// Print a line of text which is not a result: to stdout, or to stderr with JSON output
+macro_rules! textln {
+    ($($arg:tt)*) => {
+        if JSON_OUTPUT.load(Ordering::Relaxed) {
+            eprintln!($($arg)*)
+        } else {
+            println!($($arg)*)
+        }
+    };
+}
+
+/// Print text which is not a result, without newline (e.g. a prompt), see `textln`
+macro_rules! text {
+    ($($arg:tt)*) => {
+        if JSON_OUTPUT.load(Ordering::Relaxed) {
+            eprint!($($arg)*);
+            std::io::stderr().flush().unwrap();
+        } else {
+            print!($($arg)*);
+            std::io::stdout().flush().unwrap();
+        }
+    };
+}
+
+/// Exit codes of the one-shot (non-interactive) mode
+const EXIT_FAILED: u8 = 1;
+const EXIT_INVALID_ARGS: u8 = 2;
+const EXIT_OPEN_FAILED: u8 = 3;
+const EXIT_INSUFFICIENT_BALANCE: u8 = 4;
+const EXIT_MINT_UNREACHABLE: u8 = 5;
+const EXIT_TOKEN_SPENT: u8 = 6;
+
+/// Outcome of a command
+#[derive(Debug, PartialEq)]
+enum CmdRes {
+    Ok,
+    /// The command was executed, but failed
+    Failed(PkError),
+    /// Unknown command, or invalid or missing arguments
+    InvalidArgs,
+}
+
+impl CmdRes {
+    fn from_result(res: Result<(), PkError>) -> Self {
+        match res {
+            Ok(()) => CmdRes::Ok,
+            Err(err) => CmdRes::Failed(err),
+        }
+    }
+
+    fn exit_code(&self) -> ExitCode {
+        match self {
+            CmdRes::Ok => ExitCode::SUCCESS,
+            CmdRes::Failed(err) => ExitCode::from(error_exit_code(err)),
+            CmdRes::InvalidArgs => ExitCode::from(EXIT_INVALID_ARGS),
+        }
+    }
+}
+
+/// Exit code for a failed command, based on the kind of the error
+fn error_exit_code(err: &PkError) -> u8 {
+    match err {
+        PkError::WalletNotInitialized(_)
+        | PkError::SeedDecryptionFailed(_)
+        | PkError::SeedCheckMissing(_) => EXIT_OPEN_FAILED,
+        PkError::InsufficientBalance => EXIT_INSUFFICIENT_BALANCE,
+        PkError::MintUnreachable(_) => EXIT_MINT_UNREACHABLE,
+        PkError::TokenAlreadySpent => EXIT_TOKEN_SPENT,
+        _ => EXIT_FAILED,
+    }
+}
 
 async fn print_status(app: &PKApp) {
     let info = match app.get_wallet_info().await {
@@ -30,275 +121,1518 @@ async fn print_status(app: &PKApp) {
     print!(" \t ");
     print!("Selected: {}", info.selected_mint_url);
     print!(" \t ");
-    println!("");
+    println!();
 }
 
 fn cmd_help() {
-    println!("\nAvailable commands:");
-    println!("  help\t\t\t\tShows a list of commands.");
-    println!("  quit | exit | q\t\tExit.");
-    println!("");
-    println!("  status\t\t\tShow wallet status.");
-    println!("  listmints\t\t\tList used mints.");
-    println!("  addmint <mint_url>\t\tAdd a mint.");
-    println!("  selectmint <mint_number>\tSelect a mint, from known ones, by number, as listed in 'listminst', e.g. '1'; OR");
-    println!("  selectmint <mint_url>\t\tSelect a mint, from known ones, by url.");
-    println!("");
-    println!("  recln <amount_sats>\t\tReceive LN, show LN invoice to-be-paid, for the specified amount, with the current mint.");
-    println!("  sendln <ln_invoice>\t\tSend LN.");
-    println!("  rec <ecash_token>\t\tReceive ecash");
-    println!("  send <amount_sats>\t\tSend ecash, prepare ecash token for sending.");
-    println!("");
-}
-
-async fn cmd_status(app: &PKApp) {
-    print_status(app).await
-}
-
-async fn cmd_list_mints(app: &PKApp) {
-    let mints = match app.get_mints_info().await {
+    textln!("\nAvailable commands:");
+    textln!("  help\t\t\t\tShows a list of commands.");
+    textln!("  quit | exit | q\t\tExit.");
+    textln!();
+    textln!("  seed\t\t\t\tShow the seed phrase (mnemonic), for backup.");
+    textln!("  verifyseed\t\t\tVerify the seed phrase backup, asks for some of the words.");
+    textln!("  status\t\t\tShow wallet status.");
+    textln!("  balance\t\t\tShow the balance.");
+    textln!("  listmints\t\t\tList used mints.");
+    textln!("  addmint <mint_url>\t\tAdd a mint.");
+    textln!("  selectmint <mint_number>\tSelect a mint, from known ones, by number, as listed in 'listminst', e.g. '1'; OR");
+    textln!("  selectmint <mint_url>\t\tSelect a mint, from known ones, by url.");
+    textln!();
+    textln!("  recln <amount_sats> [--wait]\tReceive LN, show LN invoice to-be-paid, for the specified amount, with the current mint;");
+    textln!("  \t\t\t\twait for the payment (always done in interactive mode).");
+    textln!("  claim\t\t\t\tClaim the pending LN receives, paid after the waiting has ended.");
+    textln!("  decode <ln_invoice|ecash_token>\tShow the details of an invoice or token.");
+    textln!("  pay <input> [--trust]\t\tAct on a pasted input: pay an invoice (sendln), receive a token (rec),");
+    textln!(
+        "  \t\t\t\tpay a payment request (creqA...) with a token, or add a mint URL (addmint)."
+    );
+    textln!("  \t\t\t\tThe parts of an animated QR code (ur:bytes/...) can be given, separated by spaces.");
+    textln!("  sendln <ln_invoice> [--mpp]\tSend LN; with --mpp, pay from several mints (multi-path payment).");
+    textln!("  rec | receive <ecash_token> [--trust]\tReceive ecash, with the mint of the token;");
+    textln!("  \t\t\t\tan unknown mint is added with --trust (asked in interactive mode).");
+    textln!("  send <amount_sats> [--multi|--consolidate]");
+    textln!(
+        "  \t\t\t\tSend ecash, prepare ecash token for sending; with --multi, from several mints,"
+    );
+    textln!("  \t\t\t\twith a token per mint; with --consolidate, transfer to one mint first.");
+    textln!("  pending\t\t\tShow the sent tokens not claimed yet by the recipient, checked with the mints.");
+    textln!("  reclaim <tx_id>\t\tReclaim a sent token not claimed yet, by its ID, as listed in 'pending'.");
+    textln!();
+    textln!("  transfer <from_mint_url> <to_mint_url> <amount_sats>");
+    textln!("  \t\t\t\tTransfer funds from one mint to another, via Lightning.");
+    textln!();
+    textln!("  recover [<mint_url> ...]\tRecover ecash from the seed, from all known mints, or the given ones.");
+    textln!("  health\t\t\tCheck the state of all proofs with the mints, remove spent ones,");
+    textln!("  \t\t\t\trelease ones stuck by interrupted operations.");
+    textln!("  proofs [<mint_url>]\t\tList the stored proofs and denominations, of all mints or the given one.");
+    textln!("  consolidate [<mint_url>] [--value <amount>]");
+    textln!(
+        "  \t\t\t\tSwap the proofs of the mint (default: selected) into the fewest denominations,"
+    );
+    textln!("  \t\t\t\tor into proofs of the given value.");
+    textln!(
+        "  keysets\t\t\tRetrieve the keysets of the mints, check for funds in inactive keysets."
+    );
+    textln!("  migrate [<mint_url>]\t\tMove the funds from inactive keysets to the active one, of all mints or the given one.");
+    textln!();
+    textln!("  history [<count> [<skip>]]\tShow the transaction history, newest first; can be filtered by");
+    textln!("  \t\t\t\tin|out, ln|ecash, pending|completed|failed|reclaimed, <mint_url>.");
+    textln!();
+}
+
+/// Read a new password, asking twice, until the two match
+fn read_new_password() -> Option<String> {
+    loop {
+        let password = match rpassword::prompt_password("Enter password for the new wallet: ") {
+            Ok(password) => password,
+            Err(err) => {
+                textln!("\nERROR reading password {}", err);
+                return None;
+            }
+        };
+        let password2 = match rpassword::prompt_password("Repeat password: ") {
+            Ok(password) => password,
+            Err(err) => {
+                textln!("\nERROR reading password {}", err);
+                return None;
+            }
+        };
+        if password == password2 {
+            return Some(password);
+        }
+        textln!("\nERROR: The passwords don't match, try again");
+    }
+}
+
+/// Read a line from the console, after printing the prompt
+fn read_line(prompt: &str) -> Option<String> {
+    text!("{}", prompt);
+    let mut line = String::new();
+    match io::stdin().read_line(&mut line) {
+        Ok(0) => None,
+        Ok(_) => Some(line.trim().to_owned()),
+        Err(e) => {
+            textln!("ERROR: {}", e);
+            None
+        }
+    }
+}
+
+/// Open the wallet if it exists (asking for the password), otherwise create or restore one
+async fn open_or_init_wallet(config: &PKAppConfig) -> Option<PKApp> {
+    if PKApp::is_wallet_initialized(config) {
+        for _i in 0..OPEN_PASSWORD_ATTEMPTS {
+            let password = match rpassword::prompt_password("Enter wallet password: ") {
+                Ok(password) => password,
+                Err(err) => {
+                    textln!("\nERROR reading password {}", err);
+                    return None;
+                }
+            };
+            match PKApp::open_wallet(config, &password).await {
+                Ok(app) => return Some(app),
+                Err(err) => textln!("\nERROR opening wallet, {}", err),
+            }
+        }
+        None
+    } else {
+        textln!("No wallet found.");
+        loop {
+            match read_line("Create a (n)ew wallet, or (r)estore from seed phrase? [n/r] ")?
+                .as_str()
+            {
+                "n" => return init_wallet(config).await,
+                "r" => return restore_wallet(config).await,
+                _ => {}
+            }
+        }
+    }
+}
+
+async fn init_wallet(config: &PKAppConfig) -> Option<PKApp> {
+    textln!("Creating a new wallet, with a new random seed, stored encrypted with a password.");
+    loop {
+        let password = read_new_password()?;
+        match PKApp::init_new_wallet(config, &password).await {
+            Ok(app) => {
+                textln!(
+                    "Seed written to secret file {}",
+                    config.secret_file_path().display()
+                );
+                return Some(app);
+            }
+            Err(err) => textln!("\nERROR creating wallet, {}", err),
+        }
+    }
+}
+
+async fn restore_wallet(config: &PKAppConfig) -> Option<PKApp> {
+    loop {
+        let mnemonic = read_line("Enter the seed phrase (mnemonic words): ")?;
+        let password = read_new_password()?;
+        match PKApp::restore_wallet_from_mnemonic(config, &mnemonic, &password).await {
+            Ok(app) => {
+                textln!(
+                    "Seed written to secret file {}",
+                    config.secret_file_path().display()
+                );
+                textln!("Wallet restored. Use 'recover <mint_url> ...' to recover the ecash from the mints used.");
+                return Some(app);
+            }
+            Err(err) => textln!("\nERROR restoring wallet, {}", err),
+        }
+    }
+}
+
+/// Print the result as JSON, and return its error, if any
+fn print_json(event: AppEvent) -> Result<(), PkError> {
+    println!("{}", event.to_json());
+    match event.error() {
+        Some(err) => Err(err.clone()),
+        None => Ok(()),
+    }
+}
+
+fn cmd_seed(app: &PKApp, json: bool) -> Result<(), PkError> {
+    let res = app.get_seed_mnemonic();
+    if json {
+        return print_json(AppEvent::SeedMnemonic(res));
+    }
+    match res {
+        Ok(mnemonic) => {
+            println!("Seed phrase, write it down and keep it secret!\n");
+            for (i, word) in mnemonic.split_whitespace().enumerate() {
+                println!("  {:2}. {}", i + 1, word);
+            }
+            println!();
+            Ok(())
+        }
+        Err(err) => {
+            println!("\nERROR retrieving seed, {}", err);
+            Err(err)
+        }
+    }
+}
+
+fn cmd_verifyseed(app: &PKApp, json: bool) -> Result<(), PkError> {
+    let indices = match app.seed_verify_quiz(SEED_VERIFY_WORD_COUNT) {
+        Ok(indices) => indices,
+        Err(err) => {
+            if json {
+                return print_json(AppEvent::SeedVerifyQuiz(Err(err)));
+            }
+            println!("\nERROR in seed verification, {}", err);
+            return Err(err);
+        }
+    };
+    let mut words = Vec::new();
+    for index in indices {
+        let word = match read_line(&format!("Enter word #{} of your seed phrase: ", index)) {
+            Some(word) => word,
+            None => return Err(PkError::InvalidInput("No word entered".to_owned())),
+        };
+        words.push((index, word));
+    }
+    let res = app.seed_verify_check(&words);
+    let mismatch_err = || PkError::InvalidMnemonic("the words don't match".to_owned());
+    if json {
+        let verified = matches!(res, Ok(true));
+        print_json(AppEvent::SeedVerifyResult(res))?;
+        return if verified {
+            Ok(())
+        } else {
+            Err(mismatch_err())
+        };
+    }
+    match res {
+        Ok(true) => {
+            println!("Seed phrase backup verified OK");
+            Ok(())
+        }
+        Ok(false) => {
+            println!("\nERROR: The words don't match, check your seed phrase backup!");
+            Err(mismatch_err())
+        }
+        Err(err) => {
+            println!("\nERROR in seed verification, {}", err);
+            Err(err)
+        }
+    }
+}
+
+async fn cmd_status(app: &PKApp, json: bool) -> Result<(), PkError> {
+    if json {
+        let res = match (app.get_balance().await, app.get_wallet_info().await) {
+            (Ok(balance), Ok(wallet_info)) => Ok((balance, wallet_info)),
+            (Err(err), _) | (_, Err(err)) => Err(err),
+        };
+        return print_json(AppEvent::BalanceAndWalletInfo(res));
+    }
+    print_status(app).await;
+    Ok(())
+}
+
+async fn cmd_balance(app: &PKApp, json: bool) -> Result<(), PkError> {
+    let res = app.get_balance().await;
+    if json {
+        return print_json(AppEvent::BalanceChange(res));
+    }
+    match res {
+        Ok(balance) => {
+            println!("Balance: {} sats", balance.0);
+            Ok(())
+        }
+        Err(err) => {
+            println!("\nERROR retrieving balance! {}", err);
+            Err(err)
+        }
+    }
+}
+
+async fn cmd_list_mints(app: &PKApp, json: bool) -> Result<(), PkError> {
+    let res = app.get_mints_info().await;
+    if json {
+        return print_json(AppEvent::MintsInfo(res));
+    }
+    let mints = match res {
         Ok(mints) => mints,
         Err(err) => {
-            println!("\nERROR: {}", err.to_string());
-            return;
+            println!("\nERROR: {}", err);
+            return Err(err);
         }
     };
+    print_mints(&mints);
+    Ok(())
+}
+
+fn print_mints(mints: &[MintInfo]) {
     if mints.is_empty() {
         println!("No mints used.");
-    } else {
-        println!("Mints used: ({})", mints.len());
-        for (i, mint) in mints.iter().enumerate() {
-            println!("    {}\t{}\t{}", i + 1, mint.url, mint.balance);
+        return;
+    }
+    println!("Mints used: ({})", mints.len());
+    for (i, mint) in mints.iter().enumerate() {
+        println!("    {}\t{}\t{}", i + 1, mint.url, mint.balance);
+        let keysets: Vec<String> = mint
+            .keysets
+            .iter()
+            .map(|k| {
+                format!(
+                    "{} ({}, fee {} ppk)",
+                    k.id,
+                    if k.active { "active" } else { "inactive" },
+                    k.input_fee_ppk
+                )
+            })
+            .collect();
+        if !keysets.is_empty() {
+            println!("      \tkeysets: {}", keysets.join(", "));
+        }
+        if mint.inactive_balance > 0 {
+            println!("      \t{} sats in inactive keysets", mint.inactive_balance);
         }
     }
 }
 
-async fn cmd_addmint(app: &mut PKApp, mint_url: &str) {
-    match app.add_mint(mint_url).await {
+fn print_inactive_keysets(mints: &[MintInfo]) {
+    let mut any = false;
+    for mint in mints.iter().filter(|m| m.inactive_balance > 0) {
+        println!(
+            "WARNING: {} sats at mint {} are in keysets deactivated by the mint, use 'migrate' to move them",
+            mint.inactive_balance, mint.url
+        );
+        any = true;
+    }
+    if !any {
+        println!("Keysets checked, no funds in inactive keysets");
+    }
+}
+
+fn print_keyset_migration(summary: &KeysetMigrationSummary) {
+    for (mint_url, res) in &summary.mints {
+        match res {
+            Ok(migration) if migration.proof_count == 0 => {}
+            Ok(migration) => println!(
+                "  {}  migrated {} sats in {} proofs, fee {} sats",
+                mint_url, migration.amount, migration.proof_count, migration.fee
+            ),
+            Err(err) => println!("  {}  ERROR migrating, {}", mint_url, err),
+        }
+    }
+    println!(
+        "Keyset migration done: {} sats migrated, fee {} sats",
+        summary.amount, summary.fee
+    );
+}
+
+async fn cmd_addmint(app: &mut PKApp, mint_url: &str, json: bool) -> Result<(), PkError> {
+    let res = app.add_mint(mint_url).await;
+    if json {
+        return print_json(AppEvent::MintAdded(res));
+    }
+    match res {
         Ok(_) => {}
         Err(err) => {
-            println!("\nERROR adding mint {} {}", mint_url, err.to_string());
-            return;
+            println!("\nERROR adding mint {} {}", mint_url, err);
+            return Err(err);
         }
     }
     println!("Selected mint: {}", app.selected_mint());
+    Ok(())
 }
 
-async fn cmd_selectmint_by_index(app: &mut PKApp, mint_number: usize) {
-    match app.select_mint_by_index(mint_number).await {
-        Ok(_) => println!("Selected mint: {}", app.selected_mint()),
-        Err(err) => println!(
-            "\nERROR selecting mint {}; {}",
-            mint_number,
-            err.to_string()
-        ),
+async fn cmd_selectmint_by_index(
+    app: &mut PKApp,
+    mint_number: usize,
+    json: bool,
+) -> Result<(), PkError> {
+    let res = app.select_mint_by_index(mint_number).await;
+    if json {
+        return print_json(AppEvent::MintSelectedByIndex(res));
+    }
+    match res {
+        Ok(_) => {
+            println!("Selected mint: {}", app.selected_mint());
+            Ok(())
+        }
+        Err(err) => {
+            println!("\nERROR selecting mint {}; {}", mint_number, err);
+            Err(err)
+        }
     }
 }
 
-async fn cmd_selectmint_by_url(app: &mut PKApp, mint_url: &str) {
-    match app.select_mint(mint_url).await {
+async fn cmd_selectmint_by_url(app: &mut PKApp, mint_url: &str, json: bool) -> Result<(), PkError> {
+    let res = app.select_mint(mint_url).await;
+    if json {
+        return print_json(AppEvent::MintSelectedByUrl(res));
+    }
+    match res {
         Ok(_) => {}
         Err(err) => {
-            println!("\nERROR selecting mint {} {}", mint_url, err.to_string());
-            return;
+            println!("\nERROR selecting mint {} {}", mint_url, err);
+            return Err(err);
         }
     }
     println!("Selected mint: {}", app.selected_mint());
+    Ok(())
 }
 
-async fn cmd_recln(app: &mut PKApp, amount_sats: u64) {
+async fn cmd_recln(
+    app: &mut PKApp,
+    amount_sats: u64,
+    wait: bool,
+    json: bool,
+) -> Result<(), PkError> {
     match app.mint_from_ln_start(amount_sats).await {
         Ok((invoice, intermediary_result)) => {
-            println!("Pay the invoice: {} !", invoice);
-            match app.mint_from_ln_wait(intermediary_result).await {
-                Ok(minted) => println!(
-                    "Received LN, got ecash for {} sats, with mint {}",
-                    minted,
-                    app.selected_mint()
-                ),
-                Err(err) => println!("\nERROR receiving LN, {}", err),
+            if json {
+                let _res = print_json(AppEvent::MintFromLnInvoice(invoice));
+            } else {
+                println!("Pay the invoice: {} !", invoice);
+            }
+            if !wait {
+                return Ok(());
+            }
+            let mint_url = intermediary_result.mint_url();
+            let res = app.mint_from_ln_wait(intermediary_result).await;
+            if json {
+                return print_json(AppEvent::MintFromLnRes(res));
+            }
+            match res {
+                Ok(minted) => {
+                    println!(
+                        "Received LN, got ecash for {} sats, with mint {}",
+                        minted, mint_url
+                    );
+                    Ok(())
+                }
+                Err(err) => {
+                    println!("\nERROR receiving LN, {}", err);
+                    Err(err)
+                }
+            }
+        }
+        Err(err) => {
+            if json {
+                return print_json(AppEvent::MintFromLnRes(Err(err)));
             }
+            println!("\nERROR receiving LN, {}", err);
+            Err(err)
         }
-        Err(err) => println!("\nERROR receiving LN, {}", err),
     }
 }
 
-async fn cmd_sendln(app: &mut PKApp, ln_invoice: &str) {
-    match app.melt_to_ln(ln_invoice).await {
-        Ok(sent) => println!(
-            "Sent LN, amount {} sats, from mint {}",
-            sent,
-            app.selected_mint()
-        ),
-        Err(err) => println!("\nERROR sending LN, {}", err.to_string()),
+async fn cmd_claim(app: &mut PKApp, json: bool) -> Result<(), PkError> {
+    let res = app.claim_pending().await;
+    if json {
+        return print_json(AppEvent::ClaimPendingRes(res));
+    }
+    match res {
+        Ok(0) => {
+            println!("No paid pending receives");
+            Ok(())
+        }
+        Ok(received) => {
+            println!("Claimed pending receives, got ecash for {} sats", received);
+            Ok(())
+        }
+        Err(err) => {
+            println!("\nERROR claiming pending receives, {}", err);
+            Err(err)
+        }
+    }
+}
+
+async fn cmd_decode(app: &mut PKApp, input: &str, json: bool) -> Result<(), PkError> {
+    let res = app.decode_input(input).await;
+    if json {
+        return print_json(AppEvent::DecodedInput(res));
+    }
+    match res {
+        Ok(decoded) => {
+            print_decoded_input(&decoded);
+            Ok(())
+        }
+        Err(err) => {
+            println!("\nERROR decoding, {}", err);
+            Err(err)
+        }
+    }
+}
+
+fn print_decoded_input(decoded: &DecodedInput) {
+    match decoded {
+        DecodedInput::Invoice(invoice) => {
+            println!("Lightning invoice");
+            match invoice.amount_msat {
+                Some(msat) if msat % 1000 == 0 => println!("  amount:       {} sats", msat / 1000),
+                Some(msat) => println!("  amount:       {} msats", msat),
+                None => println!("  amount:       (not specified)"),
+            }
+            println!("  description:  {}", invoice.description);
+            println!("  payee:        {}", invoice.payee);
+            println!("  payment hash: {}", invoice.payment_hash);
+            println!("  expiry:       {} UTC", format_unix_time(invoice.expiry));
+            println!("  network:      {}", invoice.network);
+        }
+        DecodedInput::Token(token) => {
+            println!("Ecash token");
+            for mint_url in &token.mint_urls {
+                println!("  mint:         {}", mint_url);
+            }
+            if !token.mint_known {
+                println!("                (unknown mint)");
+            }
+            let unit = token.unit.as_deref().unwrap_or("sat");
+            println!("  amount:       {} {}", token.amount, unit);
+            println!(
+                "  memo:         {}",
+                token.memo.as_deref().unwrap_or_default()
+            );
+            println!("  proofs:       {}", token.proof_count);
+            if token.spending_conditions.is_empty() {
+                println!("  conditions:   none");
+            }
+            for condition in &token.spending_conditions {
+                println!("  conditions:   {}", condition);
+            }
+        }
+    }
+}
+
+async fn cmd_sendln(
+    app: &mut PKApp,
+    ln_invoice: &str,
+    interactive: bool,
+    json: bool,
+) -> Result<(), PkError> {
+    let quote = match app.melt_quote_start(ln_invoice).await {
+        Ok(quote) => quote,
+        Err(err) => {
+            if json {
+                return print_json(AppEvent::MeltQuoteRes(Err(err)));
+            }
+            println!("\nERROR preparing send LN, {}", err);
+            return Err(err);
+        }
+    };
+    if json {
+        let _res = print_json(AppEvent::MeltQuoteRes(Ok(quote.clone())));
+    } else {
+        println!(
+            "Pay {} sats from mint {}, fee reserve {} sats",
+            quote.amount, quote.mint_url, quote.fee_reserve
+        );
+        if !quote.invoice.description.is_empty() {
+            println!("Invoice description: {}", quote.invoice.description);
+        }
+        if interactive && read_line("Proceed? (y/n): ").unwrap_or_default() != "y" {
+            println!("Send LN cancelled");
+            return app.melt_cancel(&quote.quote_id).await;
+        }
+    }
+    let res = app.melt_confirm(&quote.quote_id).await;
+    if json {
+        return print_json(AppEvent::MeltToLnRes(res));
+    }
+    match res {
+        Ok(melted) => {
+            println!(
+                "Sent LN, amount {} sats, fee {} sats, change {} sats, from mint {}",
+                melted.amount, melted.fee, melted.change, melted.mint_url
+            );
+            Ok(())
+        }
+        Err(err) => {
+            println!("\nERROR sending LN, {}", err);
+            Err(err)
+        }
     }
 }
 
-async fn cmd_rec(app: &mut PKApp, token: &str) {
-    match app.receive_ecash(token).await {
-        Ok(received) => println!("Received ecash for {} sats", received,),
-        Err(err) => println!("\nERROR receiving, {}", err.to_string()),
+fn print_mpp_parts(melted: &MppMeltResult) {
+    for part in &melted.parts {
+        match &part.error {
+            None => println!(
+                "  {} sats, fee {} sats, from mint {}",
+                part.amount, part.fee, part.mint_url
+            ),
+            Some(err) => println!(
+                "  {} sats {} from mint {} (quote {}), {}",
+                part.amount, part.state, part.mint_url, part.quote_id, err
+            ),
+        }
+    }
+}
+
+async fn cmd_sendln_mpp(app: &mut PKApp, ln_invoice: &str, json: bool) -> Result<(), PkError> {
+    let res = app.melt_to_ln_mpp(ln_invoice).await;
+    if json {
+        print_json(AppEvent::MeltToLnMppRes(res.clone()))?;
+        return res.and_then(|melted| melted.check_paid());
+    }
+    match res {
+        Ok(melted) => {
+            if melted.is_paid() {
+                println!(
+                    "Sent LN, amount {} sats, fee {} sats, from {} mints",
+                    melted.amount,
+                    melted.fee,
+                    melted.parts.len()
+                );
+            }
+            print_mpp_parts(&melted);
+            melted
+                .check_paid()
+                .inspect_err(|err| println!("\nERROR sending LN, {}", err))
+        }
+        Err(err) => {
+            println!("\nERROR sending LN, {}", err);
+            Err(err)
+        }
     }
 }
 
-async fn cmd_send(app: &mut PKApp, amount_sats: u64) {
-    match app.send_ecash(amount_sats).await {
-        Ok((_sent, token)) => println!(
-            "Prepared token for sending, amount {} (sats):\n\n{}\n",
-            amount_sats, token,
+async fn cmd_rec(
+    app: &mut PKApp,
+    token: &str,
+    trust_mint: bool,
+    interactive: bool,
+    json: bool,
+) -> Result<(), PkError> {
+    let mut res = app.receive_ecash(token, trust_mint).await;
+    if let Err(PkError::UntrustedMint(mint_url)) = &res {
+        if interactive && !json {
+            println!("The token is from an unknown mint, {}", mint_url);
+            if read_line("Trust the mint and receive? (y/n): ").unwrap_or_default() == "y" {
+                res = app.receive_ecash(token, true).await;
+            }
+        }
+    }
+    if json {
+        return print_json(AppEvent::ReceivedEC(res));
+    }
+    match res {
+        Ok(received) => {
+            println!("Received ecash for {} sats", received);
+            Ok(())
+        }
+        Err(err) => {
+            println!("\nERROR receiving, {}", err);
+            Err(err)
+        }
+    }
+}
+
+async fn cmd_send(app: &mut PKApp, amount_sats: u64, json: bool) -> Result<(), PkError> {
+    let res = app.send_ecash(amount_sats).await;
+    print_sent(res, json)
+}
+
+async fn cmd_send_for_request(app: &mut PKApp, request: &str, json: bool) -> Result<(), PkError> {
+    textln!("Paying the payment request with a token, give the token to the requester.");
+    let res = app.send_ecash_for_request(request, None).await;
+    print_sent(res, json)
+}
+
+fn print_sent(res: Result<SendEcashResult, PkError>, json: bool) -> Result<(), PkError> {
+    if json {
+        return print_json(AppEvent::SendECRes(res));
+    }
+    match res {
+        Ok(sent) => {
+            println!(
+                "Prepared token for sending, amount {} (sats), from mint {}:\n\n{}\n",
+                sent.amount, sent.mint_url, sent.token,
+            );
+            Ok(())
+        }
+        Err(err) => {
+            println!("\nERROR in send, {}", err);
+            Err(err)
+        }
+    }
+}
+
+fn print_restore_progress(progress: &RestoreProgress) {
+    match &progress.restored {
+        None => println!(
+            "Recovering from mint {}/{} {} ...",
+            progress.mint_index, progress.mint_count, progress.mint_url
         ),
-        Err(err) => println!("\nERROR in send, {}", err.to_string()),
+        Some(Ok(amount)) => println!("  recovered {} sats", amount),
+        Some(Err(err)) => println!("  ERROR recovering from {}, {}", progress.mint_url, err),
     }
 }
 
-async fn poll_for_user_input(app: &mut PKApp) {
-    println!("Enter \"help\" to view available commands. Press Ctrl-D to quit.");
-    loop {
-        print_status(&app).await;
+async fn cmd_transfer(
+    app: &mut PKApp,
+    from_mint: &str,
+    to_mint: &str,
+    amount_sats: u64,
+    interactive: bool,
+    json: bool,
+) -> Result<(), PkError> {
+    let quote = match app.transfer_quote(from_mint, to_mint, amount_sats).await {
+        Ok(quote) => quote,
+        Err(err) => {
+            if json {
+                return print_json(AppEvent::TransferQuoteRes(Err(err)));
+            }
+            println!("\nERROR preparing transfer, {}", err);
+            return Err(err);
+        }
+    };
+    if json {
+        let _res = print_json(AppEvent::TransferQuoteRes(Ok(quote.clone())));
+    } else {
+        println!(
+            "Transfer {} sats from {} to {}, fee reserve {} sats",
+            quote.amount, quote.from_mint, quote.to_mint, quote.fee_reserve
+        );
+        if interactive && read_line("Proceed? (y/n): ").unwrap_or_default() != "y" {
+            println!("Transfer cancelled");
+            return app.transfer_cancel(quote).await;
+        }
+    }
+    let res = app.transfer_execute(quote).await;
+    if json {
+        return print_json(AppEvent::TransferRes(res));
+    }
+    match res {
+        Ok(transfer) if transfer.pending_receive.is_some() => {
+            println!(
+                "Transferred {} sats, fee {} sats, the receive with mint {} is pending, it can be claimed later (claim)",
+                transfer.amount_sent, transfer.fee_paid, transfer.to_mint
+            );
+            Ok(())
+        }
+        Ok(transfer) => {
+            println!(
+                "Transferred {} sats, fee {} sats, received {} sats with mint {}",
+                transfer.amount_sent, transfer.fee_paid, transfer.amount_received, transfer.to_mint
+            );
+            Ok(())
+        }
+        Err(err) => {
+            println!("\nERROR in transfer, {}", err);
+            Err(err)
+        }
+    }
+}
 
-        print!("> ");
-        std::io::stdout().flush().unwrap(); // Without flushing, the `>` doesn't print
+async fn cmd_send_multi(
+    app: &mut PKApp,
+    amount_sats: u64,
+    consolidate: bool,
+    json: bool,
+) -> Result<(), PkError> {
+    let res = app.send_ecash_multi(amount_sats, consolidate).await;
+    if json {
+        return print_json(AppEvent::SendECMultiRes(res));
+    }
+    match res {
+        Ok(sent) => {
+            println!(
+                "Prepared {} token(s) for sending, amount {} (sats), fee {} (sats):",
+                sent.tokens.len(),
+                sent.amount,
+                sent.fee
+            );
+            for t in &sent.tokens {
+                println!(
+                    "\n{} (sats) from mint {}:\n\n{}\n",
+                    t.amount, t.mint_url, t.token
+                );
+            }
+            Ok(())
+        }
+        Err(err) => {
+            println!("\nERROR in send, {}", err);
+            Err(err)
+        }
+    }
+}
 
-        let mut line = String::new();
-        if let Err(e) = io::stdin().read_line(&mut line) {
-            break println!("ERROR: {}", e);
+async fn cmd_pending(app: &mut PKApp, json: bool) -> Result<(), PkError> {
+    let res = app.check_pending_tokens().await;
+    if json {
+        return print_json(AppEvent::PendingTokens(res));
+    }
+    match res {
+        Ok(pending) => {
+            if pending.newly_claimed > 0 {
+                println!(
+                    "{} sent token(s) claimed by the recipient",
+                    pending.newly_claimed
+                );
+            }
+            println!("Sent tokens not claimed yet:");
+            print_transactions(&pending.tokens);
+            Ok(())
+        }
+        Err(err) => {
+            println!("\nERROR checking sent tokens, {}", err);
+            Err(err)
         }
+    }
+}
 
-        if line.len() == 0 {
-            // We hit EOF / Ctrl-D
-            break;
+async fn cmd_reclaim(app: &mut PKApp, tx_id: u64, json: bool) -> Result<(), PkError> {
+    let res = app.reclaim_token(tx_id).await;
+    if json {
+        return print_json(AppEvent::ReclaimRes(res));
+    }
+    match res {
+        Ok(reclaimed) => {
+            println!("Reclaimed sent token, got ecash for {} sats", reclaimed);
+            Ok(())
+        }
+        Err(err) => {
+            println!("\nERROR reclaiming sent token, {}", err);
+            Err(err)
+        }
+    }
+}
+
+async fn cmd_health(app: &mut PKApp, json: bool) -> Result<(), PkError> {
+    let res = app.check_wallet_health().await;
+    if json {
+        return print_json(AppEvent::WalletHealth(res));
+    }
+    match res {
+        Ok(report) => {
+            print_wallet_health(&report);
+            Ok(())
+        }
+        Err(err) => {
+            println!("\nERROR checking wallet, {}", err);
+            Err(err)
+        }
+    }
+}
+
+fn print_wallet_health(report: &WalletHealthReport) {
+    for (mint_url, res) in &report.mints {
+        match res {
+            Ok(health) => println!(
+                "  {}  {} proofs, fixed {} sats, resolved {} sats, lost {} sats",
+                mint_url,
+                health.proof_count,
+                health.fixed_amount,
+                health.resolved_amount,
+                health.lost_amount
+            ),
+            Err(err) => println!("  {}  ERROR checking, {}", mint_url, err),
+        }
+    }
+    println!(
+        "Wallet check done: fixed {} sats, resolved {} sats, lost {} sats",
+        report.fixed_amount, report.resolved_amount, report.lost_amount
+    );
+}
+
+async fn cmd_proofs(app: &PKApp, mint_url: Option<&str>, json: bool) -> Result<(), PkError> {
+    let res = app.list_proofs(mint_url).await;
+    if json {
+        return print_json(AppEvent::Proofs(res));
+    }
+    match res {
+        Ok(mints) => {
+            print_proofs(&mints);
+            Ok(())
+        }
+        Err(err) => {
+            println!("\nERROR listing proofs, {}", err);
+            Err(err)
+        }
+    }
+}
+
+fn print_proofs(mints: &[MintProofs]) {
+    for mint in mints {
+        println!("Mint {}, {} proofs", mint.mint_url, mint.proofs.len());
+        let denominations: Vec<String> = mint
+            .denominations
+            .iter()
+            .map(|(amount, count)| format!("{}x{}", count, amount))
+            .collect();
+        println!("  unspent denominations: {}", denominations.join(" "));
+        for keyset in &mint.keysets {
+            println!(
+                "  keyset {}  {}  fee {} ppk",
+                keyset.id,
+                if keyset.active {
+                    "active  "
+                } else {
+                    "inactive"
+                },
+                keyset.input_fee_ppk
+            );
+        }
+        for proof in &mint.proofs {
+            println!(
+                "  {:8} sats  keyset {}{}  {:13}  {}  {}",
+                proof.amount,
+                proof.keyset_id,
+                if proof.keyset_active {
+                    ""
+                } else {
+                    " (inactive)"
+                },
+                proof.state,
+                if proof.has_dleq { "dleq" } else { "    " },
+                proof.spending_condition.as_deref().unwrap_or_default(),
+            );
+        }
+    }
+}
+
+async fn cmd_consolidate(
+    app: &mut PKApp,
+    mint_url: Option<&str>,
+    target_value: Option<u64>,
+    json: bool,
+) -> Result<(), PkError> {
+    let res = app.consolidate(mint_url, target_value).await;
+    if json {
+        return print_json(AppEvent::ConsolidateRes(res));
+    }
+    match res {
+        Ok(result) => {
+            print_consolidate_result(&result);
+            Ok(())
+        }
+        Err(err) => {
+            println!("\nERROR consolidating proofs, {}", err);
+            Err(err)
+        }
+    }
+}
+
+/// Parse the arguments of the consolidate command: optional mint URL and `--value <amount>`
+fn parse_consolidate_args<'a>(
+    mut words: impl Iterator<Item = &'a str>,
+) -> Result<(Option<&'a str>, Option<u64>), String> {
+    let mut mint_url = None;
+    let mut target_value = None;
+    while let Some(word) = words.next() {
+        if word == "--value" {
+            let value = words
+                .next()
+                .ok_or("--value requires an amount (in sats)".to_owned())?;
+            target_value = Some(
+                value
+                    .parse::<u64>()
+                    .map_err(|e| format!("Invalid value {}, {}", value, e))?,
+            );
+        } else {
+            mint_url = Some(word);
+        }
+    }
+    Ok((mint_url, target_value))
+}
+
+fn print_consolidate_result(result: &ConsolidateResult) {
+    if result.proofs_before == result.proofs_after && result.fee == 0 {
+        println!(
+            "Proofs of mint {} are already consolidated ({} proofs)",
+            result.mint_url, result.proofs_before
+        );
+    } else {
+        println!(
+            "Consolidated {} sats at mint {}, {} proofs -> {} proofs, fee {} sats",
+            result.amount, result.mint_url, result.proofs_before, result.proofs_after, result.fee
+        );
+    }
+}
+
+async fn cmd_keysets(app: &PKApp, json: bool) -> Result<(), PkError> {
+    let res = app.refresh_keysets().await;
+    if json {
+        return print_json(AppEvent::KeysetsRefreshed(res));
+    }
+    match res {
+        Ok(mints) => {
+            print_mints(&mints);
+            print_inactive_keysets(&mints);
+            Ok(())
+        }
+        Err(err) => {
+            println!("\nERROR retrieving keysets, {}", err);
+            Err(err)
+        }
+    }
+}
+
+async fn cmd_migrate(app: &mut PKApp, mint_url: Option<&str>, json: bool) -> Result<(), PkError> {
+    let res = app.migrate_inactive_keysets(mint_url).await;
+    if json {
+        return print_json(AppEvent::MigrateKeysetsRes(res));
+    }
+    match res {
+        Ok(summary) => {
+            print_keyset_migration(&summary);
+            Ok(())
+        }
+        Err(err) => {
+            println!("\nERROR migrating keysets, {}", err);
+            Err(err)
+        }
+    }
+}
+
+async fn cmd_recover(app: &mut PKApp, mints: &[String], json: bool) -> Result<(), PkError> {
+    if json {
+        let res = app
+            .restore_from_seed(mints, |progress| {
+                let _res = print_json(AppEvent::RestoreProgress(progress.clone()));
+            })
+            .await;
+        return print_json(AppEvent::RestoreRes(res));
+    }
+    match app.restore_from_seed(mints, print_restore_progress).await {
+        Ok(summary) => {
+            println!("Recovered {} sats in total", summary.total_restored);
+            Ok(())
+        }
+        Err(err) => {
+            println!("\nERROR recovering ecash, {}", err);
+            Err(err)
+        }
+    }
+}
+
+fn print_transactions(transactions: &[Transaction]) {
+    if transactions.is_empty() {
+        println!("No transactions.");
+        return;
+    }
+    for tx in transactions {
+        println!(
+            "  {:4}  {}  {:3} {:5} {:8} sats  fee {:4}  {:9}  {}  {}",
+            tx.id,
+            tx.time_string(),
+            tx.direction,
+            tx.kind,
+            tx.amount,
+            tx.fee,
+            tx.status,
+            tx.mint_url,
+            tx.memo.as_deref().unwrap_or_default(),
+        );
+    }
+}
+
+fn cmd_history(app: &PKApp, filter: &HistoryFilter, json: bool) -> Result<(), PkError> {
+    let res = app.list_transactions(filter);
+    if json {
+        return print_json(AppEvent::History(res));
+    }
+    match res {
+        Ok(transactions) => {
+            print_transactions(&transactions);
+            Ok(())
+        }
+        Err(err) => {
+            println!("\nERROR retrieving history, {}", err);
+            Err(err)
         }
+    }
+}
 
-        let mut words = line.split_whitespace();
-        if let Some(word) = words.next() {
-            match word {
-                "help" => cmd_help(),
-                "quit" | "exit" | "q" => break,
-                "status" => cmd_status(app).await,
-                "listmints" => cmd_list_mints(app).await,
-
-                "addmint" => {
-                    let mint_url = if let Some(word) = words.next() {
-                        word
-                    } else {
-                        println!("\nERROR: addmint requires <mint_url>");
-                        continue;
-                    };
-                    cmd_addmint(app, mint_url).await;
+/// Parse and execute one command, with its arguments.
+/// In interactive mode some commands behave differently (e.g. recln always waits for the payment).
+async fn process_command(app: &mut PKApp, words: &[&str], interactive: bool, json: bool) -> CmdRes {
+    let mut words = words.iter().copied();
+    let word = match words.next() {
+        Some(word) => word,
+        None => return CmdRes::InvalidArgs,
+    };
+    match word {
+        "help" => {
+            cmd_help();
+            CmdRes::Ok
+        }
+        "seed" => CmdRes::from_result(cmd_seed(app, json)),
+        "verifyseed" => CmdRes::from_result(cmd_verifyseed(app, json)),
+        "status" => CmdRes::from_result(cmd_status(app, json).await),
+        "balance" => CmdRes::from_result(cmd_balance(app, json).await),
+        "listmints" => CmdRes::from_result(cmd_list_mints(app, json).await),
+
+        "addmint" => {
+            let mint_url = if let Some(word) = words.next() {
+                word
+            } else {
+                textln!("\nERROR: addmint requires <mint_url>");
+                return CmdRes::InvalidArgs;
+            };
+            CmdRes::from_result(cmd_addmint(app, mint_url, json).await)
+        }
+
+        "selectmint" => {
+            let mint_number_or_url = if let Some(word) = words.next() {
+                word
+            } else {
+                textln!("\nERROR: selectmint requires <mint_number> OR <mint_url>");
+                return CmdRes::InvalidArgs;
+            };
+            match mint_number_or_url.parse::<usize>() {
+                Ok(mint_number) => {
+                    CmdRes::from_result(cmd_selectmint_by_index(app, mint_number, json).await)
+                }
+                Err(_) => {
+                    // could not parse parameter as number, assume url
+                    CmdRes::from_result(cmd_selectmint_by_url(app, mint_number_or_url, json).await)
                 }
+            }
+        }
 
-                "selectmint" => {
-                    let mint_number_or_url = if let Some(word) = words.next() {
-                        word
-                    } else {
-                        println!("\nERROR: selectmint requires <mint_number> OR <mint_url>");
-                        continue;
-                    };
-                    match mint_number_or_url.parse::<usize>() {
-                        Ok(mint_number) => cmd_selectmint_by_index(app, mint_number).await,
-                        Err(_) => {
-                            // could not parse parameter as number, assume url
-                            cmd_selectmint_by_url(app, mint_number_or_url).await;
-                        }
-                    }
+        "recln" => {
+            let amount_str = if let Some(word) = words.next() {
+                word
+            } else {
+                textln!("\nERROR: recln requires amount (in sats)");
+                return CmdRes::InvalidArgs;
+            };
+            let amount = match amount_str.parse::<u64>() {
+                Ok(amount) => amount,
+                Err(err) => {
+                    textln!("\nERROR: recln requires amount (in sats); {}", err);
+                    return CmdRes::InvalidArgs;
                 }
+            };
+            let wait = interactive || words.any(|w| w == "--wait");
+            CmdRes::from_result(cmd_recln(app, amount, wait, json).await)
+        }
+
+        "claim" => CmdRes::from_result(cmd_claim(app, json).await),
 
-                "recln" => {
-                    let amount_str = if let Some(word) = words.next() {
-                        word
-                    } else {
-                        println!("\nERROR: recln requires amount (in sats)");
-                        continue;
-                    };
-                    let amount = match amount_str.parse::<u64>() {
-                        Ok(amount) => amount,
-                        Err(err) => {
-                            println!(
-                                "\nERROR: recln requires amount (in sats); {}",
-                                err.to_string()
-                            );
-                            continue;
-                        }
-                    };
-                    cmd_recln(app, amount).await;
+        "decode" => {
+            // the parts of a multi-part input come as separate words
+            let input = words.collect::<Vec<_>>().join(" ");
+            if input.is_empty() {
+                textln!("\nERROR: decode requires a LN invoice or an ecash token");
+                return CmdRes::InvalidArgs;
+            }
+            CmdRes::from_result(cmd_decode(app, &input, json).await)
+        }
+
+        "sendln" => {
+            let invoice_str = if let Some(word) = words.next() {
+                word
+            } else {
+                textln!("\nERROR: sendln requires a LN invoice");
+                return CmdRes::InvalidArgs;
+            };
+            if words.any(|w| w == "--mpp") {
+                CmdRes::from_result(cmd_sendln_mpp(app, invoice_str, json).await)
+            } else {
+                CmdRes::from_result(cmd_sendln(app, invoice_str, interactive, json).await)
+            }
+        }
+
+        "pay" => {
+            let args: Vec<&str> = words.collect();
+            let trust_mint = args.contains(&"--trust");
+            // the parts of a multi-part input come as separate words
+            let input = args
+                .into_iter()
+                .filter(|w| !w.starts_with("--"))
+                .collect::<Vec<_>>()
+                .join(" ");
+            if input.is_empty() {
+                textln!("\nERROR: pay requires an input, e.g. a LN invoice or an ecash token");
+                return CmdRes::InvalidArgs;
+            }
+            let res = match classify_input(&input) {
+                InputKind::Invoice(invoice) => cmd_sendln(app, &invoice, interactive, json).await,
+                InputKind::Token(token) => {
+                    cmd_rec(app, &token, trust_mint, interactive, json).await
+                }
+                InputKind::PaymentRequest(request) => {
+                    cmd_send_for_request(app, &request, json).await
+                }
+                InputKind::MintUrl(mint_url) => cmd_addmint(app, &mint_url, json).await,
+                InputKind::Unknown => {
+                    textln!("\nERROR: pay input not recognized");
+                    return CmdRes::InvalidArgs;
                 }
+            };
+            CmdRes::from_result(res)
+        }
+
+        "rec" | "receive" => {
+            let token_str = if let Some(word) = words.next() {
+                word
+            } else {
+                textln!("\nERROR: rec requires an ecash token");
+                return CmdRes::InvalidArgs;
+            };
+            let trust_mint = words.any(|w| w == "--trust");
+            CmdRes::from_result(cmd_rec(app, token_str, trust_mint, interactive, json).await)
+        }
 
-                "sendln" => {
-                    let invoice_str = if let Some(word) = words.next() {
-                        word
-                    } else {
-                        println!("\nERROR: sendln requires a LN invoice");
-                        continue;
-                    };
-                    cmd_sendln(app, invoice_str).await;
+        "send" => {
+            let amount_str = if let Some(word) = words.next() {
+                word
+            } else {
+                textln!("\nERROR: send requires amount (in sats)");
+                return CmdRes::InvalidArgs;
+            };
+            let amount = match amount_str.parse::<u64>() {
+                Ok(amount) => amount,
+                Err(err) => {
+                    textln!("\nERROR: send requires amount (in sats); {}", err);
+                    return CmdRes::InvalidArgs;
                 }
+            };
+            let flags: Vec<&str> = words.collect();
+            if flags.contains(&"--multi") || flags.contains(&"--consolidate") {
+                let consolidate = flags.contains(&"--consolidate");
+                CmdRes::from_result(cmd_send_multi(app, amount, consolidate, json).await)
+            } else {
+                CmdRes::from_result(cmd_send(app, amount, json).await)
+            }
+        }
 
-                "rec" => {
-                    let token_str = if let Some(word) = words.next() {
-                        word
-                    } else {
-                        println!("\nERROR: rec requires an ecash token");
-                        continue;
-                    };
-                    cmd_rec(app, token_str).await;
+        "transfer" => {
+            let (from_mint, to_mint, amount_str) = match (words.next(), words.next(), words.next())
+            {
+                (Some(from_mint), Some(to_mint), Some(amount_str)) => {
+                    (from_mint, to_mint, amount_str)
+                }
+                _ => {
+                    textln!(
+                        "\nERROR: transfer requires <from_mint_url> <to_mint_url> <amount_sats>"
+                    );
+                    return CmdRes::InvalidArgs;
                 }
+            };
+            let amount = match amount_str.parse::<u64>() {
+                Ok(amount) => amount,
+                Err(err) => {
+                    textln!("\nERROR: transfer requires amount (in sats); {}", err);
+                    return CmdRes::InvalidArgs;
+                }
+            };
+            CmdRes::from_result(
+                cmd_transfer(app, from_mint, to_mint, amount, interactive, json).await,
+            )
+        }
+
+        "pending" => CmdRes::from_result(cmd_pending(app, json).await),
 
-                "send" => {
-                    let amount_str = if let Some(word) = words.next() {
-                        word
-                    } else {
-                        println!("\nERROR: send requires amount (in sats");
-                        continue;
-                    };
-                    let amount = match amount_str.parse::<u64>() {
-                        Ok(amount) => amount,
-                        Err(err) => {
-                            println!(
-                                "\nERROR: send requires amount (in sats); {}",
-                                err.to_string()
-                            );
-                            continue;
-                        }
-                    };
-                    cmd_send(app, amount).await;
+        "reclaim" => {
+            let tx_id = match words.next().map(|w| w.parse::<u64>()) {
+                Some(Ok(tx_id)) => tx_id,
+                _ => {
+                    textln!("\nERROR: reclaim requires the ID of the sent token, see 'pending'");
+                    return CmdRes::InvalidArgs;
                 }
+            };
+            CmdRes::from_result(cmd_reclaim(app, tx_id, json).await)
+        }
+
+        "health" => CmdRes::from_result(cmd_health(app, json).await),
+
+        "proofs" => CmdRes::from_result(cmd_proofs(app, words.next(), json).await),
 
-                "test" => {
-                    print_status(&app).await;
+        "keysets" => CmdRes::from_result(cmd_keysets(app, json).await),
 
-                    // let _res1 = app.initialize().unwrap();
-                    let minted = app
-                        .mint_from_ln(10, |invoice| {
-                            println!("\nInvoice: {}\n", invoice);
-                        })
-                        .await
-                        .unwrap();
-                    println!("Minted {}", minted);
+        "migrate" => CmdRes::from_result(cmd_migrate(app, words.next(), json).await),
 
-                    print_status(&app).await;
+        "consolidate" => match parse_consolidate_args(words) {
+            Ok((mint_url, target_value)) => {
+                CmdRes::from_result(cmd_consolidate(app, mint_url, target_value, json).await)
+            }
+            Err(err) => {
+                textln!("\nERROR: {}", err);
+                CmdRes::InvalidArgs
+            }
+        },
 
-                    let (_sent, token) = app.send_ecash(10).await.unwrap();
-                    println!("Prepared for send {}", token);
+        "recover" => {
+            let mints: Vec<String> = words.map(|w| w.to_owned()).collect();
+            CmdRes::from_result(cmd_recover(app, &mints, json).await)
+        }
 
-                    print_status(&app).await;
+        "history" => {
+            let args: Vec<&str> = words.collect();
+            match HistoryFilter::from_args(&args) {
+                Ok(filter) => CmdRes::from_result(cmd_history(app, &filter, json)),
+                Err(err) => {
+                    textln!("\nERROR: {}", err);
+                    CmdRes::InvalidArgs
                 }
+            }
+        }
+
+        "test" => {
+            print_status(app).await;
+
+            // let _res1 = app.initialize().unwrap();
+            let minted = app
+                .mint_from_ln(10, |invoice| {
+                    println!("\nInvoice: {}\n", invoice);
+                })
+                .await
+                .unwrap();
+            println!("Minted {}", minted);
+
+            print_status(app).await;
+
+            let sent = app.send_ecash(10).await.unwrap();
+            println!("Prepared for send {}", sent.token);
+
+            print_status(app).await;
+            CmdRes::Ok
+        }
+
+        _ => {
+            textln!("Unknown command. See `\"help\" for available commands.");
+            CmdRes::InvalidArgs
+        }
+    }
+}
 
-                _ => println!("Unknown command. See `\"help\" for available commands."),
+async fn poll_for_user_input(app: &mut PKApp, json: bool) {
+    textln!("Enter \"help\" to view available commands. Press Ctrl-D to quit.");
+    loop {
+        if !json {
+            print_status(app).await;
+        }
+
+        // no prompt with JSON output, it would break the JSON lines
+        if !json {
+            print!("> ");
+            std::io::stdout().flush().unwrap(); // Without flushing, the `>` doesn't print
+        }
+
+        let mut line = String::new();
+        if let Err(e) = io::stdin().read_line(&mut line) {
+            break textln!("ERROR: {}", e);
+        }
+
+        if line.is_empty() {
+            // We hit EOF / Ctrl-D
+            break;
+        }
+
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.first() {
+            None => {}
+            Some(&"quit") | Some(&"exit") | Some(&"q") => break,
+            Some(_) => {
+                let _res = process_command(app, &words, true, json).await;
             }
         }
     }
 }
 
+/// Open the existing wallet, without prompts if possible: the password is taken from
+/// the `PARAKESH_PASSWORD` environment variable, or asked for if it is not set.
+async fn open_wallet_noninteractive(config: &PKAppConfig, json: bool) -> Option<PKApp> {
+    let res = if !PKApp::is_wallet_initialized(config) {
+        Err(PkError::WalletNotInitialized(
+            "no wallet found, start without a command to create one".to_owned(),
+        ))
+    } else {
+        let password = match std::env::var(PASSWORD_ENV_VAR) {
+            Ok(password) => Ok(password),
+            Err(_) => rpassword::prompt_password("Enter wallet password: ")
+                .map_err(|e| PkError::InvalidInput(format!("Could not read password, {}", e))),
+        };
+        match password {
+            Ok(password) => PKApp::open_wallet(config, &password).await,
+            Err(err) => Err(err),
+        }
+    };
+    match res {
+        Ok(app) => Some(app),
+        Err(err) => {
+            if json {
+                let _res = print_json(AppEvent::WalletOpened(Err(err)));
+            } else {
+                println!("ERROR opening wallet, {}", err);
+            }
+            None
+        }
+    }
+}
+
+/// Execute a single command given on the command line, and exit.
+/// An optional `--mint <mint_url>` selects the mint to use.
+async fn run_oneshot_command(config: &PKAppConfig, args: &[String], json: bool) -> ExitCode {
+    let mut mint_url: Option<String> = None;
+    let mut words: Vec<&str> = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if let Some(url) = arg.strip_prefix("--mint=") {
+            mint_url = Some(url.to_owned());
+        } else if arg == "--mint" {
+            match iter.next() {
+                Some(url) => mint_url = Some(url.clone()),
+                None => {
+                    textln!("ERROR: Missing value for option --mint");
+                    return ExitCode::from(EXIT_INVALID_ARGS);
+                }
+            }
+        } else {
+            words.push(arg.as_str());
+        }
+    }
+    if words.first() == Some(&"help") {
+        cmd_help();
+        return ExitCode::SUCCESS;
+    }
+
+    let mut app = match open_wallet_noninteractive(config, json).await {
+        Some(app) => app,
+        None => return ExitCode::from(EXIT_OPEN_FAILED),
+    };
+    if let Some(mint_url) = mint_url {
+        if let Err(err) = cmd_selectmint_by_url(&mut app, &mint_url, json).await {
+            return ExitCode::from(error_exit_code(&err));
+        }
+    }
+    process_command(&mut app, &words, false, json)
+        .await
+        .exit_code()
+}
+
+fn print_usage(program: &str) {
+    textln!(
+        "Usage: {} [options] [--json] [<command> [<args>] [--mint <mint_url>]]",
+        program
+    );
+    textln!(
+        "Without a command the interactive console is started. With a command, it is executed and"
+    );
+    textln!(
+        "the program exits, with exit code 0 on success, {} if the command failed, {} for invalid",
+        EXIT_FAILED,
+        EXIT_INVALID_ARGS
+    );
+    textln!(
+        "arguments, {} if the wallet could not be opened, {} for insufficient balance, {} if the mint",
+        EXIT_OPEN_FAILED, EXIT_INSUFFICIENT_BALANCE, EXIT_MINT_UNREACHABLE
+    );
+    textln!(
+        "could not be reached, {} if the token was already spent. The wallet password is taken from the",
+        EXIT_TOKEN_SPENT
+    );
+    textln!(
+        "{} environment variable, if set. See 'help' for the list of commands.",
+        PASSWORD_ENV_VAR
+    );
+    textln!("With --json the results are printed as JSON objects, one per line.");
+    textln!("{}", PKAppConfig::usage());
+}
+
+/// Process the command line arguments into a config, the JSON output flag,
+/// and the optional command with its arguments.
+/// Returns the exit code if the program should exit (help was requested, or invalid arguments).
+fn parse_args() -> Result<(PKAppConfig, bool, Vec<String>), ExitCode> {
+    let args: Vec<String> = std::env::args().collect();
+    // set first, for the output of the argument errors
+    let json = args[1..].iter().any(|arg| arg == "--json");
+    JSON_OUTPUT.store(json, Ordering::Relaxed);
+    let (config, mut rest) = match PKAppConfig::from_args(&args[1..]) {
+        Ok(res) => res,
+        Err(err) => {
+            textln!("ERROR: {}", err);
+            print_usage(&args[0]);
+            return Err(ExitCode::from(EXIT_INVALID_ARGS));
+        }
+    };
+    rest.retain(|arg| arg != "--json");
+    if let Some(arg) = rest.first() {
+        if arg == "--help" || arg == "-h" {
+            print_usage(&args[0]);
+            return Err(ExitCode::SUCCESS);
+        }
+        if arg.starts_with('-') {
+            textln!("ERROR: Unknown option {}", arg);
+            print_usage(&args[0]);
+            return Err(ExitCode::from(EXIT_INVALID_ARGS));
+        }
+    }
+    Ok((config, json, rest))
+}
+
 #[tokio::main]
-async fn main() {
-    println!("Parakesh: GM!");
+async fn main() -> ExitCode {
+    let (config, json, command) = match parse_args() {
+        Ok(res) => res,
+        Err(exit_code) => return exit_code,
+    };
+    if !command.is_empty() {
+        return run_oneshot_command(&config, &command, json).await;
+    }
+
+    textln!("Parakesh: GM!");
+    textln!("Using data dir {}", config.data_dir.display());
 
-    // args, init, etc.
+    let mut app = match open_or_init_wallet(&config).await {
+        Some(app) => app,
+        None => {
+            textln!("Could not open wallet");
+            return ExitCode::from(EXIT_OPEN_FAILED);
+        }
+    };
 
-    let mut app = PKApp::new().await.unwrap();
+    // detect keysets deactivated by the mints
+    let res = app.refresh_keysets().await;
+    if json {
+        let _res = print_json(AppEvent::KeysetsRefreshed(res));
+    } else if let Ok(mints) = res {
+        print_inactive_keysets(&mints);
+    }
 
     // handle interactive commands
-    poll_for_user_input(&mut app).await;
+    poll_for_user_input(&mut app, json).await;
 
     // event_loop_handle.abort();
 
@@ -307,5 +1641,6 @@ async fn main() {
     // while !bg_handle.is_finished() {
     //     std::thread::sleep(Duration::from_millis(10));
     // }
-    println!("Parakesh: ciao!");
+    textln!("Parakesh: ciao!");
+    ExitCode::SUCCESS
 }
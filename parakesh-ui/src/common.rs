@@ -11,6 +11,8 @@ pub(crate) enum UiMainTab {
     SendLN,
     SendEC,
     Mints,
+    Seed,
+    History,
     // Settings,
 }
 
@@ -18,30 +20,100 @@ pub(crate) enum UiMainTab {
 pub(crate) enum WidgetMessage {
     ShowLongStringModeString,
     ShowLongStringModeQR,
+    ShowLongStringModeAnimatedQR,
+    /// Timer, show the next part of the animated QR code
+    AnimatedQRTick,
+    AnimatedQRFaster,
+    AnimatedQRSlower,
+    AnimatedQRSmallerParts,
+    AnimatedQRLargerParts,
 }
 
 /// UI Messages
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug, Clone)]
 pub(crate) enum Message {
     SubscriptionSender(Sender<AppEvent>),
     // RefreshNoop,
     // RefreshInfo,
     AppEvent(AppEvent),
+    PasswordInput(String),
+    PasswordRepeatInput(String),
+    OpenWallet(String),
+    InitNewWallet(String),
+    RestoreMode(bool),
+    MnemonicInput(String),
+    /// Restore from mnemonic, with password
+    RestoreWallet(String, String),
+    ShowSeed,
+    HideSeed,
+    SeedVerifyStart,
+    /// Word input for seed verification, position in the quiz and the word
+    SeedVerifyWordInput(usize, String),
+    SeedVerifyCheck,
+    SeedVerifyOK,
+    RecoverMintsInput(String),
+    /// Recover ecash from seed, from the given mints (all known if empty)
+    Recover(Vec<String>),
+    RecoverOK,
+    /// Show the transaction history, from the given offset (newest first)
+    HistoryPage(usize),
     Tab(UiMainTab),
     AmountInput(String),
     InvoiceInput(String),
     TokenInput(String),
     AddMintInput(String),
+    /// Pasted input of any kind, to be routed to the right tab
+    SmartInput(String),
+    SmartInputGo,
     ReceiveLN(u64),
     ReceiveLNOK,
+    ClaimPending,
     ReceiveEC(String),
+    /// Receive the token, trusting (adding) its mint
+    ReceiveECTrust(String),
     ReceiveECOK,
+    /// Request a quote for paying the invoice, for the fee preview
     SendLN(String),
+    SendLNConfirm,
+    SendLNCancel,
+    /// Pay the invoice from several mints (multi-path payment)
+    SendLNMpp(String),
     SendLNOK,
     SendEC(u64),
+    /// Send from several mints; the flag is set to consolidate into one mint first
+    SendECMulti(u64, bool),
+    /// Send for the payment request being paid, with the amount if the request has none
+    SendECForRequest(u64),
+    /// Drop the payment request being paid
+    PaymentRequestCancel,
+    /// Show the token with the index, of several sent tokens
+    SendECTokenIndex(usize),
     SendECOK,
+    /// Check the sent tokens not claimed yet, with the mints
+    CheckPendingTokens,
+    /// Reclaim a sent token not claimed yet, by its transaction ID
+    ReclaimToken(u64),
+    /// Check the state of all the proofs with the mints, and clean up
+    CheckWalletHealth,
+    CheckWalletHealthOK,
+    /// Show the proofs of the mint
+    ShowProofs(String),
+    HideProofs,
+    /// Consolidate the proofs of the mint into the fewest denominations
+    Consolidate(String),
+    /// Move the proofs in inactive keysets to the active keyset, of the mint or of all mints
+    MigrateKeysets(Option<String>),
+    MigrateKeysetsOK,
     SelectMint(String),
     AddMint(String),
+    /// Select the target mint of a transfer
+    TransferToInput(String),
+    /// Prepare a transfer from the selected mint, with the amount, for the fee preview
+    TransferQuote(u64),
+    TransferConfirm,
+    TransferCancel,
+    TransferOK,
     WidgetMessage((String, WidgetMessage)),
     CopyToClipboard(String),
 }
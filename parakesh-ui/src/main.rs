@@ -4,13 +4,26 @@ mod widget;
 
 use crate::ui::IcedApp;
 use iced::Task;
-use parakesh_common::PKAppAsync;
+use parakesh_common::{PKAppAsync, PKAppConfig};
 
 #[tokio::main]
 async fn main() {
     println!("Parakesh UI Iced");
 
-    let backend = PKAppAsync::new().expect("Backend creation error");
+    let args: Vec<String> = std::env::args().collect();
+    let config = match PKAppConfig::from_args(&args[1..]) {
+        Ok((config, rest)) if rest.is_empty() => config,
+        Ok(_) => {
+            println!("Usage: {} [options]\n{}", args[0], PKAppConfig::usage());
+            return;
+        }
+        Err(err) => {
+            println!("ERROR: {}\n{}", err, PKAppConfig::usage());
+            return;
+        }
+    };
+
+    let backend = PKAppAsync::new(config).expect("Backend creation error");
 
     let _res = iced::application("Parakesh", IcedApp::update, IcedApp::view)
         .subscription(IcedApp::subscription)
@@ -1,20 +1,108 @@
 use crate::common::{Message, MyFonts, UiMainTab};
 use crate::widget::{ShowInvoiceWidget, ShowTokenWidget};
-use parakesh_common::pk_app::{BalanceInfo, MintInfo, MintsSummary, WalletInfo};
-use parakesh_common::{AppEvent, PKAppAsync};
+use parakesh_common::pk_app::{
+    BalanceInfo, ConsolidateResult, DecodedInput, KeysetMigrationSummary, MeltQuotePreview,
+    MeltResult, MintInfo, MintProofs, MintsSummary, MppMeltResult, MultiSendResult,
+    PaymentRequestInfo, RestoreProgress, RestoreSummary, SendEcashResult, TransferQuote,
+    TransferResult, WalletHealthReport, WalletInfo,
+};
+use parakesh_common::pk_history::{format_unix_time, HistoryFilter, Transaction};
+use parakesh_common::pk_input::{classify_input, InputKind};
+use parakesh_common::{AppEvent, PKAppAsync, PkError, SEED_VERIFY_WORD_COUNT};
 
 use iced::clipboard;
 use iced::futures::channel::mpsc;
 use iced::futures::{SinkExt, StreamExt};
-use iced::widget::{button, column, mouse_area, row, scrollable, text, text_input, Column};
+use iced::widget::{
+    button, column, mouse_area, pick_list, row, scrollable, text, text_input, Column,
+};
 use iced::{Element, Renderer, Task, Theme};
 
+/// Number of transactions shown on a page of the history
+const HISTORY_PAGE_SIZE: usize = 20;
+
+/// Error message to show, with a hint for the user where applicable
+fn error_text(err: &PkError) -> String {
+    let hint = match err {
+        PkError::SeedDecryptionFailed(_) => "\nCheck the password.",
+        PkError::NoSelectedMint => "\nGo to Mints to add or select one.",
+        PkError::InsufficientBalance => "\nReceive some ecash first, or send a smaller amount.",
+        PkError::MintUnreachable(_) => "\nCheck your connection, or try again later.",
+        PkError::TokenAlreadySpent => "\nThe token has been claimed already.",
+        PkError::QuoteExpired => "\nStart again with a new request.",
+        _ => "",
+    };
+    format!("ERROR: {}{}", err, hint)
+}
+
+#[derive(Default)]
+enum OpenWalletState {
+    #[default]
+    NotRequested,
+    Requested,
+    Completed(Result<(), PkError>),
+}
+
+#[derive(Default)]
+enum SeedVerifyState {
+    #[default]
+    NotRequested,
+    Requested,
+    /// The 1-based indices of the words to enter
+    Quiz(Vec<usize>),
+    Completed(Result<bool, PkError>),
+}
+
+#[derive(Default)]
+enum RecoverState {
+    #[default]
+    NotRequested,
+    /// In progress, with the latest progress report
+    Requested(Option<RestoreProgress>),
+    Completed(Result<RestoreSummary, PkError>),
+}
+
+#[derive(Default)]
+enum TransferState {
+    #[default]
+    NotRequested,
+    QuoteRequested,
+    /// The transfer is prepared, the user should confirm the fee
+    QuoteReady(TransferQuote),
+    Requested,
+    Completed(Result<TransferResult, PkError>),
+}
+
 #[derive(Default)]
 enum AddMintState {
     #[default]
     NotRequested,
     Requested,
-    Completed(Result<(), String>),
+    Completed(Result<(), PkError>),
+}
+
+#[derive(Default)]
+enum WalletHealthState {
+    #[default]
+    NotRequested,
+    Requested,
+    Completed(Result<WalletHealthReport, PkError>),
+}
+
+#[derive(Default)]
+enum ConsolidateState {
+    #[default]
+    NotRequested,
+    Requested,
+    Completed(Result<ConsolidateResult, PkError>),
+}
+
+#[derive(Default)]
+enum MigrateKeysetsState {
+    #[default]
+    NotRequested,
+    Requested,
+    Completed(Result<KeysetMigrationSummary, PkError>),
 }
 
 #[derive(Default)]
@@ -23,7 +111,8 @@ enum RecLNState {
     NotRequested,
     Requested(u64),
     InvoiceReady(String),
-    Completed(Result<u64, String>),
+    ClaimRequested,
+    Completed(Result<u64, PkError>),
 }
 
 #[derive(Default)]
@@ -31,15 +120,21 @@ enum RecECState {
     #[default]
     NotRequested,
     Requested,
-    Completed(Result<u64, String>),
+    /// The token is from an unknown mint, the user should confirm to trust it
+    ConfirmMint(String),
+    Completed(Result<u64, PkError>),
 }
 
 #[derive(Default)]
 enum SendLNState {
     #[default]
     NotRequested,
+    QuoteRequested,
+    /// The payment is prepared, the user should confirm the fee
+    QuoteReady(MeltQuotePreview),
     Requested,
-    Completed(Result<u64, String>),
+    Completed(Result<MeltResult, PkError>),
+    CompletedMpp(Result<MppMeltResult, PkError>),
 }
 
 #[derive(Default)]
@@ -47,11 +142,15 @@ enum SendECState {
     #[default]
     NotRequested,
     Requested,
-    Completed(Result<(u64, String), String>),
+    Completed(Result<SendEcashResult, PkError>),
+    /// Several tokens, the index of the shown one
+    CompletedMulti(Result<MultiSendResult, PkError>, usize),
 }
 
 pub(crate) struct IcedApp {
     app: PKAppAsync,
+    /// Whether the wallet exists, and should be opened, or should be created
+    is_wallet_initialized: bool,
 
     wallet_info: Option<WalletInfo>,
     balance: Option<BalanceInfo>,
@@ -63,8 +162,41 @@ pub(crate) struct IcedApp {
     invoice_input: String,
     token_input: String,
     add_mint_input: String,
+    /// Target mint of a transfer
+    transfer_to_input: Option<String>,
+    /// Details of the entered invoice or token
+    decoded_input: Option<Result<DecodedInput, PkError>>,
+    /// Pasted input on the main screen, see `Message::SmartInputGo`
+    smart_input: String,
+    smart_input_error: Option<String>,
+    /// Payment request (NUT-18) being paid in the Send EC tab, with its details
+    payment_request: Option<(String, PaymentRequestInfo)>,
+    password_input: String,
+    password_repeat_input: String,
+    /// Restore from mnemonic, instead of creating a new wallet
+    restore_mode: bool,
+    mnemonic_input: String,
+    seed_mnemonic: Option<Result<String, PkError>>,
+    seed_verify_inputs: Vec<String>,
+    recover_mints_input: String,
+    /// Offset of the shown history page
+    history_offset: usize,
+    history: Option<Result<Vec<Transaction>, PkError>>,
+    /// Sent tokens not claimed yet by the recipient
+    pending_tokens: Option<Result<Vec<Transaction>, PkError>>,
+    /// Result of the last reclaim of a sent token
+    reclaim_result: Option<Result<u64, PkError>>,
+    /// Proofs of the mint shown in the proof details panel
+    mint_proofs: Option<Result<Vec<MintProofs>, PkError>>,
 
+    open_wallet_state: OpenWalletState,
+    seed_verify_state: SeedVerifyState,
+    recover_state: RecoverState,
     add_mint_state: AddMintState,
+    wallet_health_state: WalletHealthState,
+    consolidate_state: ConsolidateState,
+    migrate_keysets_state: MigrateKeysetsState,
+    transfer_state: TransferState,
     rec_ln_state: RecLNState,
     rec_ec_state: RecECState,
     send_ln_state: SendLNState,
@@ -77,9 +209,29 @@ impl IcedApp {
     fn refresh_info(&mut self) {
         let _res = self.app.get_balance_and_wallet_info();
         let _res = self.app.get_mints_info();
+        if let UiMainTab::History = self.main_tab {
+            self.refresh_history();
+        }
+    }
+
+    fn refresh_history(&mut self) {
+        let _res = self.app.get_history(HistoryFilter {
+            offset: self.history_offset,
+            limit: Some(HISTORY_PAGE_SIZE),
+            ..Default::default()
+        });
     }
 
-    fn amount_input(&self) -> Element<Message> {
+    /// Request the details of the entered invoice or token
+    fn decode_input(&mut self, input: String) {
+        if input.trim().is_empty() {
+            self.decoded_input = None;
+        } else {
+            let _res = self.app.decode_input(input);
+        }
+    }
+
+    fn amount_input(&self) -> Element<'_, Message> {
         row![
             text("Amount: ").size(20),
             text_input("0", &self.amount_input)
@@ -92,7 +244,26 @@ impl IcedApp {
         .into()
     }
 
-    fn invoice_input(&self) -> Element<Message> {
+    fn smart_input(&self) -> Element<'_, Message> {
+        let mut contents = row![
+            text_input(
+                "Paste an invoice, token, payment request, or mint URL",
+                &self.smart_input
+            )
+            .on_input(Message::SmartInput)
+            .on_submit(Message::SmartInputGo)
+            .size(15)
+            .width(400),
+            button("Go").on_press(Message::SmartInputGo),
+        ]
+        .spacing(5);
+        if let Some(err) = &self.smart_input_error {
+            contents = contents.push(text(err).size(15));
+        }
+        contents.into()
+    }
+
+    fn invoice_input(&self) -> Element<'_, Message> {
         row![
             text("LN invoice: ").size(20),
             text_input("0", &self.invoice_input)
@@ -104,7 +275,7 @@ impl IcedApp {
         .into()
     }
 
-    fn token_input(&self) -> Element<Message> {
+    fn token_input(&self) -> Element<'_, Message> {
         row![
             text("Ecash token: ").size(20),
             text_input("", &self.token_input)
@@ -116,7 +287,111 @@ impl IcedApp {
         .into()
     }
 
-    fn view_rec_ln(&self) -> Element<Message> {
+    fn password_input(&self) -> Element<'_, Message> {
+        row![
+            text("Password: ").size(20),
+            text_input("", &self.password_input)
+                .on_input(Message::PasswordInput)
+                .secure(true)
+                .size(20)
+                .width(300),
+        ]
+        .spacing(5)
+        .into()
+    }
+
+    fn view_open_wallet(&self) -> Element<'_, Message> {
+        let contents: Element<Message> = if self.is_wallet_initialized {
+            column![
+                row![text("Open wallet").size(20)],
+                self.password_input(),
+                row![button("Open").on_press(Message::OpenWallet(self.password_input.clone()))],
+            ]
+            .spacing(10)
+            .into()
+        } else {
+            let passwords_match = self.password_input == self.password_repeat_input;
+            let mode_row = row![
+                button("Create new wallet").on_press(Message::RestoreMode(false)),
+                button("Restore from seed phrase").on_press(Message::RestoreMode(true)),
+            ]
+            .spacing(10);
+            let (title, info, mnemonic_row, action) = if self.restore_mode {
+                (
+                    "Restore wallet",
+                    "The seed will be restored from the seed phrase, and stored encrypted with the password.",
+                    Some(
+                        row![
+                            text("Seed phrase: ").size(20),
+                            text_input("(mnemonic words)", &self.mnemonic_input)
+                                .on_input(Message::MnemonicInput)
+                                .size(20)
+                                .width(500),
+                        ]
+                        .spacing(5),
+                    ),
+                    button("Restore").on_press_maybe(if passwords_match {
+                        Some(Message::RestoreWallet(
+                            self.mnemonic_input.clone(),
+                            self.password_input.clone(),
+                        ))
+                    } else {
+                        None
+                    }),
+                )
+            } else {
+                (
+                    "Create new wallet",
+                    "A new random seed will be generated, and stored encrypted with the password.",
+                    None,
+                    button("Create").on_press_maybe(if passwords_match {
+                        Some(Message::InitNewWallet(self.password_input.clone()))
+                    } else {
+                        None
+                    }),
+                )
+            };
+            column![
+                mode_row,
+                row![text(title).size(20)],
+                row![text(info).size(15)],
+            ]
+            .push_maybe(mnemonic_row)
+            .push(self.password_input())
+            .push(
+                row![
+                    text("Repeat password: ").size(20),
+                    text_input("", &self.password_repeat_input)
+                        .on_input(Message::PasswordRepeatInput)
+                        .secure(true)
+                        .size(20)
+                        .width(300),
+                ]
+                .spacing(5),
+            )
+            .push(row![text(if passwords_match {
+                ""
+            } else {
+                "The passwords don't match"
+            })
+            .size(15)])
+            .push(row![action])
+            .spacing(10)
+            .into()
+        };
+        let status = match &self.open_wallet_state {
+            OpenWalletState::NotRequested => "".to_owned(),
+            OpenWalletState::Requested => "Opening wallet...".to_owned(),
+            OpenWalletState::Completed(Ok(_)) => "Wallet opened".to_owned(),
+            OpenWalletState::Completed(Err(err)) => error_text(err),
+        };
+        column![contents, row![text(status).size(15)]]
+            .spacing(10)
+            .padding(10)
+            .into()
+    }
+
+    fn view_rec_ln(&self) -> Element<'_, Message> {
         let contents: Element<Message> = match &self.rec_ln_state {
             RecLNState::NotRequested => {
                 // No receive in progress
@@ -125,6 +400,11 @@ impl IcedApp {
                     row![button("Receive").on_press(Message::ReceiveLN(
                         self.amount_input.parse::<u64>().unwrap_or_default()
                     )),],
+                    row![
+                        button("Claim pending").on_press(Message::ClaimPending),
+                        text("Claim earlier invoices, paid after the waiting has ended").size(15),
+                    ]
+                    .spacing(10),
                 ]
                 .spacing(10)
             }
@@ -133,6 +413,11 @@ impl IcedApp {
                 button("(Cancel)").on_press(Message::ReceiveLNOK),
             ]
             .spacing(10),
+            RecLNState::ClaimRequested => column![
+                row![text("Claiming pending receives ...").size(20)],
+                button("(Cancel)").on_press(Message::ReceiveLNOK),
+            ]
+            .spacing(10),
             RecLNState::InvoiceReady(_invoice) => column![
                 row![text("Pay the invoice").size(20)],
                 button("(Cancel)").on_press(Message::ReceiveLNOK),
@@ -140,7 +425,7 @@ impl IcedApp {
             ]
             .spacing(10),
             RecLNState::Completed(Err(err)) => column![
-                row![text(format!("ERROR: {}", err)).size(20)],
+                row![text(error_text(err)).size(20)],
                 button("OK").on_press(Message::ReceiveLNOK),
             ]
             .spacing(10),
@@ -156,23 +441,83 @@ impl IcedApp {
             .into()
     }
 
-    fn view_rec_ec(&self) -> Element<Message> {
+    /// Details of the entered invoice or token, for checking before use
+    fn view_decoded_input(&self) -> Element<'_, Message> {
+        let lines: Vec<String> = match &self.decoded_input {
+            None => Vec::new(),
+            Some(Err(err)) => vec![error_text(err)],
+            Some(Ok(DecodedInput::Invoice(invoice))) => vec![
+                match invoice.amount_msat {
+                    Some(msat) => format!("Invoice for {} sats", msat / 1000),
+                    None => "Invoice without amount".to_owned(),
+                },
+                format!("Description: {}", invoice.description),
+                format!("Payee: {}", invoice.payee),
+                format!(
+                    "Expiry: {} UTC, network: {}",
+                    format_unix_time(invoice.expiry),
+                    invoice.network
+                ),
+            ],
+            Some(Ok(DecodedInput::Token(token))) => {
+                let mut lines = vec![
+                    format!(
+                        "Token of {} {}, {} proofs",
+                        token.amount,
+                        token.unit.as_deref().unwrap_or("sat"),
+                        token.proof_count
+                    ),
+                    format!(
+                        "Mint: {}{}",
+                        token.mint_urls.join(", "),
+                        if token.mint_known {
+                            ""
+                        } else {
+                            " (unknown mint)"
+                        }
+                    ),
+                ];
+                if let Some(memo) = &token.memo {
+                    lines.push(format!("Memo: {}", memo));
+                }
+                for condition in &token.spending_conditions {
+                    lines.push(format!("Spending condition: {}", condition));
+                }
+                lines
+            }
+        };
+        Column::with_children(lines.into_iter().map(|line| text(line).size(15).into())).into()
+    }
+
+    fn view_rec_ec(&self) -> Element<'_, Message> {
         let contents: Element<Message> = match &self.rec_ec_state {
             RecECState::NotRequested => {
                 // No receive in progress
                 column![
                     self.token_input(),
+                    self.view_decoded_input(),
                     row![button("Receive").on_press(Message::ReceiveEC(self.token_input.clone())),],
                 ]
                 .spacing(10)
             }
             RecECState::Requested => column![
-                row![text(format!("Receive in progress...")).size(20)],
+                row![text("Receive in progress...").size(20)],
                 button("(Cancel)").on_press(Message::ReceiveECOK),
             ]
             .spacing(10),
+            RecECState::ConfirmMint(mint_url) => column![
+                row![text("The token is from an unknown mint:").size(20)],
+                row![text(mint_url).size(15)],
+                row![
+                    button("Trust mint and receive")
+                        .on_press(Message::ReceiveECTrust(self.token_input.clone())),
+                    button("Cancel").on_press(Message::ReceiveECOK),
+                ]
+                .spacing(10),
+            ]
+            .spacing(10),
             RecECState::Completed(Err(err)) => column![
-                row![text(format!("ERROR: {}", err)).size(20)],
+                row![text(error_text(err)).size(20)],
                 button("OK").on_press(Message::ReceiveECOK),
             ]
             .spacing(10),
@@ -188,29 +533,92 @@ impl IcedApp {
             .into()
     }
 
-    fn view_send_ln(&self) -> Element<Message> {
+    fn view_send_ln(&self) -> Element<'_, Message> {
         let contents: Element<Message> = match &self.send_ln_state {
             &SendLNState::NotRequested => {
                 // No send in progress
                 column![
                     self.invoice_input(),
-                    row![button("Send (pay the invoice)")
-                        .on_press(Message::SendLN(self.invoice_input.clone()))],
+                    self.view_decoded_input(),
+                    row![
+                        button("Send (pay the invoice)")
+                            .on_press(Message::SendLN(self.invoice_input.clone())),
+                        button("Pay from multiple mints")
+                            .on_press(Message::SendLNMpp(self.invoice_input.clone())),
+                    ]
+                    .spacing(10),
                 ]
                 .spacing(10)
             }
+            SendLNState::QuoteRequested => column![
+                row![text("Preparing payment...").size(20)],
+                button("(Cancel)").on_press(Message::SendLNOK),
+            ]
+            .spacing(10),
+            SendLNState::QuoteReady(quote) => column![
+                row![text(format!(
+                    "Pay {} sats, fee reserve {} sats",
+                    quote.amount, quote.fee_reserve
+                ))
+                .size(20)],
+                row![text(format!("From mint {}", quote.mint_url)).size(15)],
+                row![text(format!("Description: {}", quote.invoice.description)).size(15)],
+                row![text("The unused part of the fee reserve is returned").size(15)],
+                row![
+                    button("Confirm").on_press(Message::SendLNConfirm),
+                    button("Cancel").on_press(Message::SendLNCancel),
+                ]
+                .spacing(10),
+            ]
+            .spacing(10),
             SendLNState::Requested => column![
-                row![text(format!("Send in progress...")).size(20)],
+                row![text("Send in progress...").size(20)],
                 button("(Cancel)").on_press(Message::SendLNOK),
             ]
             .spacing(10),
             SendLNState::Completed(Err(err)) => column![
-                row![text(format!("ERROR: {}", err)).size(20)],
+                row![text(error_text(err)).size(20)],
                 button("OK").on_press(Message::SendLNOK),
             ]
             .spacing(10),
-            SendLNState::Completed(Ok(amount)) => column![
-                row![text(format!("Sent {} sats, paid the invoice", amount)).size(20)],
+            SendLNState::Completed(Ok(melted)) => column![
+                row![text(format!(
+                    "Sent {} sats (fee {} sats, change {} sats), paid the invoice",
+                    melted.amount, melted.fee, melted.change
+                ))
+                .size(20)],
+                row![text(format!("From mint {}", melted.mint_url)).size(15)],
+                button("OK").on_press(Message::SendLNOK),
+            ]
+            .spacing(10),
+            SendLNState::CompletedMpp(Err(err)) => column![
+                row![text(error_text(err)).size(20)],
+                button("OK").on_press(Message::SendLNOK),
+            ]
+            .spacing(10),
+            SendLNState::CompletedMpp(Ok(melted)) => column![
+                row![text(match melted.check_paid() {
+                    Ok(()) => format!(
+                        "Sent {} sats (fee {} sats), paid the invoice",
+                        melted.amount, melted.fee
+                    ),
+                    Err(err) => error_text(&err),
+                })
+                .size(20)],
+                Column::with_children(melted.parts.iter().map(|part| {
+                    text(match &part.error {
+                        None => format!(
+                            "{} sats (fee {} sats) from mint {}",
+                            part.amount, part.fee, part.mint_url
+                        ),
+                        Some(err) => format!(
+                            "{} sats {} from mint {} (quote {}), {}",
+                            part.amount, part.state, part.mint_url, part.quote_id, err
+                        ),
+                    })
+                    .size(15)
+                    .into()
+                })),
                 button("OK").on_press(Message::SendLNOK),
             ]
             .spacing(10),
@@ -221,8 +629,39 @@ impl IcedApp {
             .into()
     }
 
-    fn view_send_ec(&self) -> Element<Message> {
+    /// Paying a payment request, with a token from a mint accepted by the requester
+    fn view_pay_request(&self, info: &PaymentRequestInfo) -> Column<'_, Message> {
+        let mut contents = column![text("Payment request").size(20)].spacing(10);
+        if let Some(description) = &info.description {
+            contents = contents.push(text(description.clone()).size(15));
+        }
+        contents = match info.amount {
+            Some(amount) => contents.push(text(format!("Amount: {} sats", amount)).size(20)),
+            None => contents.push(self.amount_input()),
+        };
+        if !info.mints.is_empty() {
+            contents =
+                contents.push(text(format!("Accepted mints: {}", info.mints.join(", "))).size(15));
+        }
+        contents
+            .push(
+                row![
+                    button("Send Ecash").on_press(Message::SendECForRequest(
+                        self.amount_input.parse::<u64>().unwrap_or_default()
+                    )),
+                    button("Cancel").on_press(Message::PaymentRequestCancel),
+                ]
+                .spacing(10),
+            )
+            .push(text("Give the token to the requester.").size(15))
+    }
+
+    fn view_send_ec(&self) -> Element<'_, Message> {
         let contents: Element<Message> = match &self.send_ec_state {
+            SendECState::NotRequested if self.payment_request.is_some() => {
+                let (_, info) = self.payment_request.as_ref().expect("checked");
+                self.view_pay_request(info)
+            }
             SendECState::NotRequested => {
                 // Prepare for send
                 column![
@@ -230,6 +669,18 @@ impl IcedApp {
                     row![button("Send Ecash").on_press(Message::SendEC(
                         self.amount_input.parse::<u64>().unwrap_or_default()
                     )),]
+                    .spacing(10),
+                    row![
+                        button("Send from multiple mints").on_press(Message::SendECMulti(
+                            self.amount_input.parse::<u64>().unwrap_or_default(),
+                            false
+                        )),
+                        button("Consolidate and send").on_press(Message::SendECMulti(
+                            self.amount_input.parse::<u64>().unwrap_or_default(),
+                            true
+                        )),
+                    ]
+                    .spacing(10),
                 ]
                 .spacing(10)
             }
@@ -238,24 +689,106 @@ impl IcedApp {
                 row![button("OK").on_press(Message::SendECOK),],
             ]
             .spacing(10),
-            SendECState::Completed(Ok(_res)) => column![
+            SendECState::Completed(Ok(sent)) => column![
                 row![button("OK").on_press(Message::SendECOK),],
+                row![text(format!("From mint {}", sent.mint_url)).size(15)],
                 scrollable(self.show_token_widget.view()),
             ]
             .spacing(10),
-            SendECState::Completed(Err(err)) => column![
-                row![text(format!("ERROR: {}", err)).size(20)],
+            SendECState::Completed(Err(err)) | SendECState::CompletedMulti(Err(err), _) => column![
+                row![text(error_text(err)).size(20)],
                 row![button("OK").on_press(Message::SendECOK),],
             ]
             .spacing(10),
+            SendECState::CompletedMulti(Ok(sent), index) => {
+                let count = sent.tokens.len();
+                let (amount, mint_url) = sent
+                    .tokens
+                    .get(*index)
+                    .map(|t| (t.amount, t.mint_url.clone()))
+                    .unwrap_or_default();
+                column![
+                    row![button("OK").on_press(Message::SendECOK),],
+                    row![text(format!(
+                        "Sent {} sats in {} token(s), fee {} sats",
+                        sent.amount, count, sent.fee
+                    ))
+                    .size(15)],
+                    row![
+                        button("Previous").on_press_maybe(
+                            (*index > 0).then(|| Message::SendECTokenIndex(index - 1))
+                        ),
+                        text(format!(
+                            "Token {} of {}, {} sats from mint {}",
+                            index + 1,
+                            count,
+                            amount,
+                            mint_url
+                        ))
+                        .size(15),
+                        button("Next").on_press_maybe(
+                            (index + 1 < count).then(|| Message::SendECTokenIndex(index + 1))
+                        ),
+                    ]
+                    .spacing(10),
+                    scrollable(self.show_token_widget.view()),
+                ]
+                .spacing(10)
+            }
         }
         .into();
-        column![row![text("Send Ecash").size(20)], contents,]
-            .spacing(10)
-            .into()
+        let mut send_ec = column![row![text("Send Ecash").size(20)], contents,].spacing(10);
+        if let SendECState::NotRequested = self.send_ec_state {
+            send_ec = send_ec.push(self.view_pending_tokens());
+        }
+        send_ec.into()
     }
 
-    fn view_mints(&self) -> Element<Message> {
+    /// The sent tokens not claimed yet, with a reclaim button for each
+    fn view_pending_tokens(&self) -> Element<'_, Message> {
+        let mut contents: Vec<Element<Message>> = vec![row![
+            text("Sent tokens not claimed yet").size(20),
+            button("Check").on_press(Message::CheckPendingTokens),
+        ]
+        .spacing(10)
+        .into()];
+        match &self.reclaim_result {
+            None => {}
+            Some(Ok(reclaimed)) => {
+                contents.push(row![text(format!("Reclaimed {} sats", reclaimed)).size(15)].into())
+            }
+            Some(Err(err)) => contents.push(row![text(error_text(err)).size(15)].into()),
+        }
+        match &self.pending_tokens {
+            None => contents.push(row![text("Loading...").size(15)].into()),
+            Some(Err(err)) => contents.push(row![text(error_text(err)).size(15)].into()),
+            Some(Ok(tokens)) if tokens.is_empty() => {
+                contents.push(row![text("No pending tokens").size(15)].into())
+            }
+            Some(Ok(tokens)) => {
+                for tx in tokens {
+                    contents.push(
+                        row![
+                            text(format!(
+                                "{}  {:8} sats  {}",
+                                tx.time_string(),
+                                tx.amount,
+                                tx.mint_url,
+                            ))
+                            .font(MyFonts::mono())
+                            .size(14),
+                            button("Reclaim").on_press(Message::ReclaimToken(tx.id)),
+                        ]
+                        .spacing(10)
+                        .into(),
+                    );
+                }
+            }
+        }
+        Column::with_children(contents).spacing(5).into()
+    }
+
+    fn view_mints(&self) -> Element<'_, Message> {
         let selected_mint = self
             .wallet_info
             .as_ref()
@@ -263,7 +796,7 @@ impl IcedApp {
             .unwrap_or("?".to_owned());
 
         let mut contents = Vec::new();
-        if self.mints_info.len() == 0 {
+        if self.mints_info.is_empty() {
             contents.push(row![text("No Mint added").size(20)]);
             contents.push(row![text("Add a mint").size(20)]);
         } else {
@@ -277,11 +810,11 @@ impl IcedApp {
                     Column::with_children(self.mints_info.iter().map(|mi| {
                         mouse_area(row![
                             text(mi.url.to_string())
-                                .font(MyFonts::bold_if(mi.url.to_string() == selected_mint))
+                                .font(MyFonts::bold_if(mi.url == selected_mint))
                                 .size(15)
                                 .width(300),
                             text(format!("  {}", mi.balance))
-                                .font(MyFonts::bold_if(mi.url.to_string() == selected_mint))
+                                .font(MyFonts::bold_if(mi.url == selected_mint))
                                 .size(15),
                         ])
                         .on_press(Message::SelectMint(mi.url.to_string()))
@@ -289,6 +822,7 @@ impl IcedApp {
                     }));
                 contents.push(row![text("List (click to select)").size(15),]);
                 contents.push(row![mints_ui]);
+                contents.push(row![self.view_transfer(&selected_mint)]);
             }
         }
         // Add mint is common
@@ -307,7 +841,7 @@ impl IcedApp {
                 AddMintState::NotRequested => "-".to_owned(),
                 AddMintState::Requested => "Add in progress...".to_owned(),
                 AddMintState::Completed(Ok(_)) => "Mint added".to_owned(),
-                AddMintState::Completed(Err(e)) => format!("Error adding mint, {}", e),
+                AddMintState::Completed(Err(err)) => error_text(err),
             })
             .size(15),]
             .spacing(10),
@@ -323,17 +857,457 @@ impl IcedApp {
                     .into()
             }));
         contents.push(row![recommended_mints_ui]);
+        if !self.mints_info.is_empty() {
+            contents.push(row![self.view_inactive_keysets()]);
+            contents.push(row![self.view_proofs(&selected_mint)]);
+            contents.push(row![self.view_wallet_health()]);
+        }
 
         Column::with_children(contents.into_iter().map(|e| e.into()))
             .spacing(10)
             .into()
     }
 
-    // fn view_settings(&self) -> Element<Message> {
+    /// Warning about funds in keysets deactivated by the mints, and their migration
+    fn view_inactive_keysets(&self) -> Element<'_, Message> {
+        let mut contents: Vec<Element<Message>> = Vec::new();
+        match &self.migrate_keysets_state {
+            MigrateKeysetsState::NotRequested => {
+                for mint in self.mints_info.iter().filter(|m| m.inactive_balance > 0) {
+                    contents.push(
+                        row![
+                            text(format!(
+                                "Warning: {} sats at {} are in inactive keysets",
+                                mint.inactive_balance, mint.url
+                            ))
+                            .size(15),
+                            button("Migrate")
+                                .on_press(Message::MigrateKeysets(Some(mint.url.clone()))),
+                        ]
+                        .spacing(10)
+                        .into(),
+                    );
+                }
+            }
+            MigrateKeysetsState::Requested => {
+                contents.push(row![text("Migrating to the active keysets...").size(15)].into())
+            }
+            MigrateKeysetsState::Completed(res) => {
+                let result_text = match res {
+                    Ok(summary) => format!(
+                        "Keyset migration done: {} sats migrated, fee {} sats",
+                        summary.amount, summary.fee
+                    ),
+                    Err(err) => error_text(err),
+                };
+                contents.push(row![text(result_text).size(15)].into());
+                if let Ok(summary) = res {
+                    for (mint_url, res) in &summary.mints {
+                        if let Err(err) = res {
+                            contents.push(
+                                row![text(format!("{}  {}", mint_url, error_text(err))).size(14)]
+                                    .into(),
+                            );
+                        }
+                    }
+                }
+                contents.push(row![button("OK").on_press(Message::MigrateKeysetsOK)].into());
+            }
+        }
+        Column::with_children(contents).spacing(5).into()
+    }
+
+    /// Proof details panel: the denominations and the proofs of the selected mint
+    fn view_proofs(&self, selected_mint: &str) -> Element<'_, Message> {
+        let consolidate_status = match &self.consolidate_state {
+            ConsolidateState::NotRequested => String::new(),
+            ConsolidateState::Requested => "Consolidating...".to_owned(),
+            ConsolidateState::Completed(Ok(result)) => format!(
+                "Consolidated, {} proofs -> {} proofs, fee {} sats",
+                result.proofs_before, result.proofs_after, result.fee
+            ),
+            ConsolidateState::Completed(Err(err)) => error_text(err),
+        };
+        let buttons = row![
+            if self.mint_proofs.is_none() {
+                button("Show proofs").on_press(Message::ShowProofs(selected_mint.to_owned()))
+            } else {
+                button("Hide proofs").on_press(Message::HideProofs)
+            },
+            button("Consolidate").on_press(Message::Consolidate(selected_mint.to_owned())),
+            text(consolidate_status).size(15),
+        ]
+        .spacing(10);
+        let mut contents: Vec<Element<Message>> = vec![buttons.into()];
+        match &self.mint_proofs {
+            None => {}
+            Some(Err(err)) => contents.push(row![text(error_text(err)).size(15)].into()),
+            Some(Ok(mints)) if mints.is_empty() => {
+                contents.push(row![text("Loading...").size(15)].into())
+            }
+            Some(Ok(mints)) => {
+                for mint in mints {
+                    contents.push(
+                        row![text(format!(
+                            "Proofs of {}: {}",
+                            mint.mint_url,
+                            mint.proofs.len()
+                        ))
+                        .size(15)]
+                        .into(),
+                    );
+                    let denominations: Vec<String> = mint
+                        .denominations
+                        .iter()
+                        .map(|(amount, count)| format!("{} x {}", count, amount))
+                        .collect();
+                    contents.push(
+                        row![text(format!("Unspent: {}", denominations.join(", "))).size(15)]
+                            .into(),
+                    );
+                    for keyset in &mint.keysets {
+                        contents.push(
+                            row![text(format!(
+                                "Keyset {}  {}  fee {} ppk",
+                                keyset.id,
+                                if keyset.active { "active" } else { "inactive" },
+                                keyset.input_fee_ppk
+                            ))
+                            .size(14)]
+                            .into(),
+                        );
+                    }
+                    let lines: Vec<Element<Message>> = mint
+                        .proofs
+                        .iter()
+                        .map(|proof| {
+                            row![text(format!(
+                                "{:8} sats  keyset {}{}  {:13}  {}  {}",
+                                proof.amount,
+                                proof.keyset_id,
+                                if proof.keyset_active {
+                                    ""
+                                } else {
+                                    " (inactive)"
+                                },
+                                proof.state,
+                                if proof.has_dleq { "dleq" } else { "    " },
+                                proof.spending_condition.as_deref().unwrap_or_default(),
+                            ))
+                            .font(MyFonts::mono())
+                            .size(14)]
+                            .into()
+                        })
+                        .collect();
+                    contents.push(
+                        scrollable(Column::with_children(lines).spacing(2))
+                            .height(200)
+                            .into(),
+                    );
+                }
+            }
+        }
+        Column::with_children(contents).spacing(5).into()
+    }
+
+    /// Check of the proofs with the mints, and its summary
+    fn view_wallet_health(&self) -> Element<'_, Message> {
+        let mut contents: Vec<Element<Message>> = Vec::new();
+        match &self.wallet_health_state {
+            WalletHealthState::NotRequested => contents
+                .push(row![button("Check wallet").on_press(Message::CheckWalletHealth)].into()),
+            WalletHealthState::Requested => {
+                contents.push(row![text("Checking proofs with the mints...").size(15)].into())
+            }
+            WalletHealthState::Completed(Err(err)) => {
+                contents.push(row![text(error_text(err)).size(15)].into())
+            }
+            WalletHealthState::Completed(Ok(report)) => {
+                contents.push(
+                    row![text(format!(
+                        "Wallet check done: fixed {} sats, resolved {} sats, lost {} sats",
+                        report.fixed_amount, report.resolved_amount, report.lost_amount
+                    ))
+                    .size(15)]
+                    .into(),
+                );
+                for (mint_url, res) in &report.mints {
+                    let line = match res {
+                        Ok(health) => format!(
+                            "{}  {} proofs, fixed {}, resolved {}, lost {}",
+                            mint_url,
+                            health.proof_count,
+                            health.fixed_amount,
+                            health.resolved_amount,
+                            health.lost_amount
+                        ),
+                        Err(err) => format!("{}  {}", mint_url, error_text(err)),
+                    };
+                    contents.push(row![text(line).size(14)].into());
+                }
+            }
+        }
+        if let WalletHealthState::Completed(_) = &self.wallet_health_state {
+            contents.push(row![button("OK").on_press(Message::CheckWalletHealthOK)].into());
+        }
+        Column::with_children(contents).spacing(5).into()
+    }
+
+    /// Transfer from the selected mint to another one
+    fn view_transfer(&self, selected_mint: &str) -> Element<'_, Message> {
+        let contents: Element<Message> = match &self.transfer_state {
+            TransferState::NotRequested => {
+                let other_mints: Vec<String> = self
+                    .mints_info
+                    .iter()
+                    .map(|mi| mi.url.to_string())
+                    .filter(|url| url != selected_mint)
+                    .collect();
+                row![
+                    self.amount_input(),
+                    text("to").size(20),
+                    pick_list(
+                        other_mints,
+                        self.transfer_to_input.clone(),
+                        Message::TransferToInput
+                    )
+                    .width(300),
+                    button("Transfer").on_press(Message::TransferQuote(
+                        self.amount_input.parse::<u64>().unwrap_or_default()
+                    )),
+                ]
+                .spacing(10)
+                .into()
+            }
+            TransferState::QuoteRequested => text("Preparing transfer ...").size(15).into(),
+            TransferState::QuoteReady(quote) => column![
+                text(format!(
+                    "Transfer {} sats to {}, fee reserve {} sats",
+                    quote.amount, quote.to_mint, quote.fee_reserve
+                ))
+                .size(15),
+                row![
+                    button("Confirm").on_press(Message::TransferConfirm),
+                    button("Cancel").on_press(Message::TransferCancel),
+                ]
+                .spacing(10),
+            ]
+            .spacing(10)
+            .into(),
+            TransferState::Requested => text("Transfer in progress...").size(15).into(),
+            TransferState::Completed(Err(err)) => column![
+                text(error_text(err)).size(15),
+                button("OK").on_press(Message::TransferOK),
+            ]
+            .spacing(10)
+            .into(),
+            TransferState::Completed(Ok(transfer)) => column![
+                text(match transfer.pending_receive {
+                    Some(_) => format!(
+                        "Transferred {} sats, fee {} sats, the receive with {} is pending",
+                        transfer.amount_sent, transfer.fee_paid, transfer.to_mint
+                    ),
+                    None => format!(
+                        "Transferred {} sats, fee {} sats, received {} sats with {}",
+                        transfer.amount_sent,
+                        transfer.fee_paid,
+                        transfer.amount_received,
+                        transfer.to_mint
+                    ),
+                })
+                .size(15),
+                button("OK").on_press(Message::TransferOK),
+            ]
+            .spacing(10)
+            .into(),
+        };
+        column![text("Transfer from the selected mint").size(20), contents]
+            .spacing(10)
+            .into()
+    }
+
+    fn view_seed(&self) -> Element<'_, Message> {
+        let mut contents: Vec<Element<Message>> = vec![row![text("Seed backup").size(20)].into()];
+        match &self.seed_mnemonic {
+            None => {
+                contents.push(row![button("Show seed phrase").on_press(Message::ShowSeed)].into())
+            }
+            Some(Ok(mnemonic)) => {
+                contents.push(
+                    row![text("Write down the seed phrase, and keep it secret!").size(15)].into(),
+                );
+                let words: Vec<String> = mnemonic
+                    .split_whitespace()
+                    .enumerate()
+                    .map(|(i, word)| format!("{:2}. {:10}", i + 1, word))
+                    .collect();
+                for line in words.chunks(4) {
+                    contents
+                        .push(row![text(line.join("  ")).font(MyFonts::mono()).size(15)].into());
+                }
+                contents.push(row![button("Hide").on_press(Message::HideSeed)].into());
+            }
+            Some(Err(err)) => {
+                contents.push(row![text(error_text(err)).size(15)].into());
+                contents.push(row![button("OK").on_press(Message::HideSeed)].into());
+            }
+        }
+
+        contents.push(row![text("Verify seed phrase backup").size(20)].into());
+        match &self.seed_verify_state {
+            SeedVerifyState::NotRequested => contents
+                .push(row![button("Verify backup").on_press(Message::SeedVerifyStart)].into()),
+            SeedVerifyState::Requested => {
+                contents.push(row![text("Verification in progress...").size(15)].into())
+            }
+            SeedVerifyState::Quiz(indices) => {
+                contents.push(
+                    row![text("Enter the following words of your seed phrase:").size(15)].into(),
+                );
+                for (pos, index) in indices.iter().enumerate() {
+                    contents.push(
+                        row![
+                            text(format!("Word #{}: ", index)).size(15).width(100),
+                            text_input("", &self.seed_verify_inputs[pos])
+                                .on_input(move |word| Message::SeedVerifyWordInput(pos, word))
+                                .size(15)
+                                .width(200),
+                        ]
+                        .into(),
+                    );
+                }
+                contents.push(
+                    row![
+                        button("Check").on_press(Message::SeedVerifyCheck),
+                        button("(Cancel)").on_press(Message::SeedVerifyOK),
+                    ]
+                    .spacing(10)
+                    .into(),
+                );
+            }
+            SeedVerifyState::Completed(res) => {
+                let result_text = match res {
+                    Ok(true) => "Seed phrase backup verified OK".to_owned(),
+                    Ok(false) => "The words don't match, check your seed phrase backup!".to_owned(),
+                    Err(err) => error_text(err),
+                };
+                contents.push(row![text(result_text).size(15)].into());
+                contents.push(row![button("OK").on_press(Message::SeedVerifyOK)].into());
+            }
+        }
+
+        contents.push(row![text("Recover ecash from seed").size(20)].into());
+        match &self.recover_state {
+            RecoverState::NotRequested => {
+                contents.push(
+                    row![text(
+                        "Recover ecash from the mints, e.g. after restoring from seed phrase. Enter the mints used (space separated), or leave empty for all known mints."
+                    )
+                    .size(15)]
+                    .into(),
+                );
+                contents.push(
+                    row![
+                        text_input("(mint urls)", &self.recover_mints_input)
+                            .on_input(Message::RecoverMintsInput)
+                            .size(15)
+                            .width(500),
+                        button("Recover").on_press(Message::Recover(
+                            self.recover_mints_input
+                                .split_whitespace()
+                                .map(|m| m.to_owned())
+                                .collect()
+                        )),
+                    ]
+                    .spacing(10)
+                    .into(),
+                );
+            }
+            RecoverState::Requested(progress) => {
+                let progress_text = match progress {
+                    None => "Recover in progress...".to_owned(),
+                    Some(progress) => format!(
+                        "Recover in progress, mint {}/{} {} ...",
+                        progress.mint_index, progress.mint_count, progress.mint_url
+                    ),
+                };
+                contents.push(row![text(progress_text).size(15)].into());
+            }
+            RecoverState::Completed(Ok(summary)) => {
+                contents.push(
+                    row![text(format!("Recovered {} sats", summary.total_restored)).size(15)]
+                        .into(),
+                );
+                for (mint_url, res) in &summary.mints {
+                    let mint_text = match res {
+                        Ok(amount) => format!("  {}  {} sats", mint_url, amount),
+                        Err(err) => format!("  {}  {}", mint_url, error_text(err)),
+                    };
+                    contents.push(row![text(mint_text).size(15)].into());
+                }
+                contents.push(row![button("OK").on_press(Message::RecoverOK)].into());
+            }
+            RecoverState::Completed(Err(err)) => {
+                contents.push(row![text(error_text(err)).size(15)].into());
+                contents.push(row![button("OK").on_press(Message::RecoverOK)].into());
+            }
+        }
+
+        Column::with_children(contents).spacing(10).into()
+    }
+
+    fn view_history(&self) -> Element<'_, Message> {
+        let mut contents: Vec<Element<Message>> =
+            vec![row![text("Transaction history").size(20)].into()];
+        match &self.history {
+            None => contents.push(row![text("Loading...").size(15)].into()),
+            Some(Err(err)) => contents.push(row![text(error_text(err)).size(15)].into()),
+            Some(Ok(transactions)) => {
+                if transactions.is_empty() {
+                    contents.push(row![text("No transactions").size(15)].into());
+                }
+                let lines: Vec<Element<Message>> = transactions
+                    .iter()
+                    .map(|tx| {
+                        row![text(format!(
+                            "{}  {:3} {:5} {:8} sats  fee {:4}  {:9}  {}  {}",
+                            tx.time_string(),
+                            tx.direction,
+                            tx.kind,
+                            tx.amount,
+                            tx.fee,
+                            tx.status,
+                            tx.mint_url,
+                            tx.memo.as_deref().unwrap_or_default(),
+                        ))
+                        .font(MyFonts::mono())
+                        .size(14)]
+                        .into()
+                    })
+                    .collect();
+                contents.push(scrollable(Column::with_children(lines).spacing(5)).into());
+                let mut paging = row![].spacing(10);
+                if self.history_offset > 0 {
+                    paging = paging.push(button("Newer").on_press(Message::HistoryPage(
+                        self.history_offset.saturating_sub(HISTORY_PAGE_SIZE),
+                    )));
+                }
+                if transactions.len() == HISTORY_PAGE_SIZE {
+                    paging = paging.push(button("Older").on_press(Message::HistoryPage(
+                        self.history_offset + HISTORY_PAGE_SIZE,
+                    )));
+                }
+                contents.push(paging.into());
+            }
+        }
+        Column::with_children(contents).spacing(10).into()
+    }
+
+    // fn view_settings(&self) -> Element<'_, Message> {
     //     column![row![text("Settings").size(20)], row![text("TODO").size(20)],].into()
     // }
 
-    fn view_header(&self) -> Element<Message> {
+    fn view_header(&self) -> Element<'_, Message> {
         let (wallet_info, mints_info) = match &self.wallet_info {
             None => ("?".to_owned(), "?".to_owned()),
             Some(wi) => {
@@ -376,7 +1350,7 @@ impl IcedApp {
         .into()
     }
 
-    fn view_main(&self) -> Element<Message> {
+    fn view_main(&self) -> Element<'_, Message> {
         let header = self.view_header();
 
         let tab_header: Element<Message> = row![
@@ -385,6 +1359,8 @@ impl IcedApp {
             button("Receive EC").on_press(Message::Tab(UiMainTab::RecEC)),
             button("Send LN").on_press(Message::Tab(UiMainTab::SendLN)),
             button("Send EC").on_press(Message::Tab(UiMainTab::SendEC)),
+            button("History").on_press(Message::Tab(UiMainTab::History)),
+            button("Seed").on_press(Message::Tab(UiMainTab::Seed)),
             // button("Settings").on_press(Message::Tab(UiMainTab::Settings)),
             // text("|").size(20),
             // button("(Refresh)").on_press(Message::RefreshInfo),
@@ -398,10 +1374,12 @@ impl IcedApp {
             UiMainTab::SendLN => self.view_send_ln(),
             UiMainTab::SendEC => self.view_send_ec(),
             UiMainTab::Mints => self.view_mints(),
+            UiMainTab::Seed => self.view_seed(),
+            UiMainTab::History => self.view_history(),
             // UiMainTab::Settings => self.view_settings(),
         };
 
-        column![header, tab_header, tab_view,]
+        column![header, self.smart_input(), tab_header, tab_view,]
             .spacing(10)
             .padding(10)
             .into()
@@ -411,6 +1389,7 @@ impl IcedApp {
 impl IcedApp {
     pub fn new(backend: PKAppAsync) -> Self {
         IcedApp {
+            is_wallet_initialized: backend.is_wallet_initialized(),
             app: backend,
             wallet_info: None,
             balance: None,
@@ -421,8 +1400,32 @@ impl IcedApp {
             invoice_input: "".to_owned(),
             token_input: "".to_owned(),
             add_mint_input: "".to_owned(),
+            transfer_to_input: None,
+            decoded_input: None,
+            smart_input: "".to_owned(),
+            smart_input_error: None,
+            payment_request: None,
+            password_input: "".to_owned(),
+            password_repeat_input: "".to_owned(),
+            restore_mode: false,
+            mnemonic_input: "".to_owned(),
+            seed_mnemonic: None,
+            seed_verify_inputs: Vec::new(),
+            recover_mints_input: "".to_owned(),
+            history_offset: 0,
+            history: None,
+            pending_tokens: None,
+            reclaim_result: None,
+            mint_proofs: None,
 
+            open_wallet_state: OpenWalletState::NotRequested,
+            seed_verify_state: SeedVerifyState::NotRequested,
+            recover_state: RecoverState::NotRequested,
             add_mint_state: AddMintState::NotRequested,
+            wallet_health_state: WalletHealthState::NotRequested,
+            consolidate_state: ConsolidateState::NotRequested,
+            migrate_keysets_state: MigrateKeysetsState::NotRequested,
+            transfer_state: TransferState::NotRequested,
             rec_ln_state: RecLNState::NotRequested,
             rec_ec_state: RecECState::NotRequested,
             send_ln_state: SendLNState::NotRequested,
@@ -437,7 +1440,7 @@ impl IcedApp {
             // Create channel for getting events from outside
             let (inbound_sender, mut inbound_receiver) = mpsc::channel::<AppEvent>(100);
 
-            let _res = output
+            output
                 .send(Message::SubscriptionSender(inbound_sender))
                 .await
                 .unwrap();
@@ -457,7 +1460,11 @@ impl IcedApp {
     }
 
     pub fn subscription(&self) -> iced::Subscription<Message> {
-        iced::Subscription::run(Self::event_listener)
+        iced::Subscription::batch([
+            iced::Subscription::run(Self::event_listener),
+            self.show_invoice_widget.subscription(),
+            self.show_token_widget.subscription(),
+        ])
     }
 
     pub fn update(&mut self, message: Message) -> Task<Message> {
@@ -471,6 +1478,37 @@ impl IcedApp {
             //     self.refresh_info();
             // }
             Message::AppEvent(ev) => match ev {
+                AppEvent::WalletOpened(res) => {
+                    if res.is_ok() {
+                        self.is_wallet_initialized = true;
+                        self.password_input.clear();
+                        self.password_repeat_input.clear();
+                        self.mnemonic_input.clear();
+                        // detect keysets deactivated by the mints
+                        let _res = self.app.refresh_keysets();
+                    }
+                    self.open_wallet_state = OpenWalletState::Completed(res);
+                }
+                AppEvent::SeedMnemonic(res) => {
+                    self.seed_mnemonic = Some(res);
+                }
+                AppEvent::SeedVerifyQuiz(res) => match res {
+                    Ok(indices) => {
+                        self.seed_verify_inputs = vec!["".to_owned(); indices.len()];
+                        self.seed_verify_state = SeedVerifyState::Quiz(indices);
+                    }
+                    Err(err) => self.seed_verify_state = SeedVerifyState::Completed(Err(err)),
+                },
+                AppEvent::SeedVerifyResult(res) => {
+                    self.seed_verify_state = SeedVerifyState::Completed(res);
+                }
+                AppEvent::RestoreProgress(progress) => {
+                    self.recover_state = RecoverState::Requested(Some(progress));
+                }
+                AppEvent::RestoreRes(res) => {
+                    self.recover_state = RecoverState::Completed(res);
+                    self.refresh_info();
+                }
                 AppEvent::WalletInfo(wallet_info) => {
                     if let Ok(wallet_info) = &wallet_info {
                         self.wallet_info = Some(wallet_info.clone());
@@ -496,6 +1534,16 @@ impl IcedApp {
                     self.add_mint_state = AddMintState::Completed(res);
                     self.refresh_info();
                 }
+                AppEvent::TransferQuoteRes(res) => {
+                    self.transfer_state = match res {
+                        Ok(quote) => TransferState::QuoteReady(quote),
+                        Err(err) => TransferState::Completed(Err(err)),
+                    };
+                }
+                AppEvent::TransferRes(res) => {
+                    self.transfer_state = TransferState::Completed(res);
+                    self.refresh_info();
+                }
                 AppEvent::MintSelectedByUrl(_res) => {
                     self.refresh_info();
                 }
@@ -513,18 +1561,47 @@ impl IcedApp {
                     // TODO notification with amount
                     self.refresh_info();
                 }
+                AppEvent::ClaimPendingRes(res) => {
+                    self.rec_ln_state = RecLNState::Completed(res);
+                    self.refresh_info();
+                }
+                AppEvent::ReceivedEC(Err(PkError::UntrustedMint(mint_url))) => {
+                    self.rec_ec_state = RecECState::ConfirmMint(mint_url);
+                }
                 AppEvent::ReceivedEC(res) => {
                     self.rec_ec_state = RecECState::Completed(res);
                     self.refresh_info();
                 }
+                AppEvent::DecodedInput(res) => {
+                    self.decoded_input = Some(res);
+                }
+                AppEvent::MeltQuoteRes(res) => {
+                    if let SendLNState::QuoteRequested = self.send_ln_state {
+                        self.send_ln_state = match res {
+                            Ok(quote) => SendLNState::QuoteReady(quote),
+                            Err(err) => SendLNState::Completed(Err(err)),
+                        };
+                    } else if let Ok(quote) = res {
+                        // cancelled meanwhile
+                        let _res = self.app.melt_cancel(quote.quote_id);
+                    }
+                }
                 AppEvent::MeltToLnRes(res) => {
                     self.send_ln_state = SendLNState::Completed(res);
                     self.refresh_info();
                 }
+                AppEvent::MeltToLnMppRes(res) => {
+                    self.send_ln_state = SendLNState::CompletedMpp(res);
+                    self.refresh_info();
+                }
+                AppEvent::History(res) => {
+                    self.history = Some(res);
+                }
                 AppEvent::SendECRes(res) => {
                     match &res {
-                        Ok((_sent, token)) => {
-                            self.show_token_widget.set_data(Some(token.clone()), None);
+                        Ok(sent) => {
+                            self.show_token_widget
+                                .set_data(Some(sent.token.clone()), None);
                         }
                         Err(_err) => {
                             self.show_token_widget.set_data(None, None);
@@ -534,8 +1611,144 @@ impl IcedApp {
                     // TODO notification with token
                     self.refresh_info();
                 }
+                AppEvent::PendingTokens(res) => {
+                    self.pending_tokens = Some(res.map(|pending| pending.tokens));
+                    if let UiMainTab::History = self.main_tab {
+                        self.refresh_history();
+                    }
+                }
+                AppEvent::Proofs(res) => {
+                    // only if still shown
+                    if self.mint_proofs.is_some() {
+                        self.mint_proofs = Some(res);
+                    }
+                }
+                AppEvent::ConsolidateRes(res) => {
+                    if let (Ok(result), Some(_)) = (&res, &self.mint_proofs) {
+                        let _res = self.app.list_proofs(Some(result.mint_url.clone()));
+                    }
+                    self.consolidate_state = ConsolidateState::Completed(res);
+                    self.refresh_info();
+                }
+                AppEvent::KeysetsRefreshed(minfo) => {
+                    if let Ok(minfo) = minfo {
+                        self.mints_info = minfo;
+                    }
+                }
+                AppEvent::MigrateKeysetsRes(res) => {
+                    self.migrate_keysets_state = MigrateKeysetsState::Completed(res);
+                    self.refresh_info();
+                }
+                AppEvent::WalletHealth(res) => {
+                    self.wallet_health_state = WalletHealthState::Completed(res);
+                    self.refresh_info();
+                }
+                AppEvent::ReclaimRes(res) => {
+                    self.reclaim_result = Some(res);
+                    let _res = self.app.check_pending_tokens();
+                    self.refresh_info();
+                }
+                AppEvent::SendECMultiRes(res) => {
+                    let token = res
+                        .as_ref()
+                        .ok()
+                        .and_then(|sent| sent.tokens.first())
+                        .map(|t| t.token.clone());
+                    self.show_token_widget.set_data(token, None);
+                    self.send_ec_state = SendECState::CompletedMulti(res, 0);
+                    self.refresh_info();
+                }
             },
+            Message::PasswordInput(password) => {
+                self.password_input = password;
+            }
+            Message::PasswordRepeatInput(password) => {
+                self.password_repeat_input = password;
+            }
+            Message::OpenWallet(password) => {
+                self.open_wallet_state = OpenWalletState::Requested;
+                let _res = self.app.open_wallet(password);
+            }
+            Message::InitNewWallet(password) => {
+                self.open_wallet_state = OpenWalletState::Requested;
+                let _res = self.app.init_new_wallet(password);
+            }
+            Message::RestoreMode(restore_mode) => {
+                self.restore_mode = restore_mode;
+            }
+            Message::MnemonicInput(mnemonic) => {
+                self.mnemonic_input = mnemonic;
+            }
+            Message::RestoreWallet(mnemonic, password) => {
+                self.open_wallet_state = OpenWalletState::Requested;
+                let _res = self.app.restore_wallet(mnemonic, password);
+            }
+            Message::ShowSeed => {
+                let _res = self.app.get_seed_mnemonic();
+            }
+            Message::HideSeed => {
+                self.seed_mnemonic = None;
+            }
+            Message::SeedVerifyStart => {
+                self.seed_verify_state = SeedVerifyState::Requested;
+                let _res = self.app.seed_verify_quiz(SEED_VERIFY_WORD_COUNT);
+            }
+            Message::SeedVerifyWordInput(pos, word) => {
+                if let Some(input) = self.seed_verify_inputs.get_mut(pos) {
+                    *input = word;
+                }
+            }
+            Message::SeedVerifyCheck => {
+                if let SeedVerifyState::Quiz(indices) = &self.seed_verify_state {
+                    let words = indices
+                        .iter()
+                        .cloned()
+                        .zip(self.seed_verify_inputs.iter().cloned())
+                        .collect();
+                    self.seed_verify_state = SeedVerifyState::Requested;
+                    let _res = self.app.seed_verify_check(words);
+                }
+            }
+            Message::SeedVerifyOK => {
+                self.seed_verify_state = SeedVerifyState::NotRequested;
+                self.seed_verify_inputs.clear();
+            }
+            Message::RecoverMintsInput(mints) => {
+                self.recover_mints_input = mints;
+            }
+            Message::Recover(mints) => {
+                self.recover_state = RecoverState::Requested(None);
+                let _res = self.app.restore_from_seed(mints);
+            }
+            Message::RecoverOK => {
+                self.recover_state = RecoverState::NotRequested;
+                self.recover_mints_input.clear();
+            }
+            Message::HistoryPage(offset) => {
+                self.history_offset = offset;
+                self.refresh_history();
+            }
             Message::Tab(tab) => {
+                // don't leave the seed on the screen
+                self.seed_mnemonic = None;
+                if let UiMainTab::History = tab {
+                    self.history_offset = 0;
+                    self.refresh_history();
+                }
+                // the details shown are of the input of the tab
+                self.decoded_input = None;
+                match tab {
+                    UiMainTab::SendLN => self.decode_input(self.invoice_input.clone()),
+                    UiMainTab::RecEC => self.decode_input(self.token_input.clone()),
+                    UiMainTab::SendEC => {
+                        self.reclaim_result = None;
+                        let _res = self.app.check_pending_tokens();
+                    }
+                    UiMainTab::Mints => {
+                        let _res = self.app.refresh_keysets();
+                    }
+                    _ => {}
+                }
                 self.main_tab = tab;
             }
             Message::SelectMint(url) => {
@@ -545,16 +1758,110 @@ impl IcedApp {
                 self.add_mint_state = AddMintState::Requested;
                 let _res = self.app.add_mint(url);
             }
+            Message::TransferToInput(mint_url) => {
+                self.transfer_to_input = Some(mint_url);
+            }
+            Message::TransferQuote(amount) => {
+                let from_mint = self
+                    .wallet_info
+                    .as_ref()
+                    .map(|wi| wi.selected_mint_url.to_string());
+                if let (Some(from_mint), Some(to_mint)) =
+                    (from_mint, self.transfer_to_input.clone())
+                {
+                    self.transfer_state = TransferState::QuoteRequested;
+                    let _res = self.app.transfer_quote(from_mint, to_mint, amount);
+                }
+            }
+            Message::TransferConfirm => {
+                if let TransferState::QuoteReady(quote) = &self.transfer_state {
+                    let _res = self.app.transfer(quote.clone());
+                    self.transfer_state = TransferState::Requested;
+                }
+            }
+            Message::TransferCancel => {
+                if let TransferState::QuoteReady(quote) = &self.transfer_state {
+                    let _res = self.app.transfer_cancel(quote.clone());
+                }
+                self.transfer_state = TransferState::NotRequested;
+            }
+            Message::TransferOK => {
+                self.transfer_state = TransferState::NotRequested;
+            }
             Message::AmountInput(amount_str) => {
                 if let Ok(amnt) = amount_str.parse::<f64>() {
                     self.amount_input = (amnt as u64).to_string();
                 };
             }
+            Message::SmartInput(input) => {
+                self.smart_input = input;
+                self.smart_input_error = None;
+            }
+            Message::SmartInputGo => {
+                let tab = match classify_input(&self.smart_input) {
+                    InputKind::Invoice(invoice) => {
+                        self.invoice_input = invoice;
+                        self.send_ln_state = SendLNState::NotRequested;
+                        UiMainTab::SendLN
+                    }
+                    InputKind::Token(token) => {
+                        self.token_input = token;
+                        self.rec_ec_state = RecECState::NotRequested;
+                        UiMainTab::RecEC
+                    }
+                    InputKind::PaymentRequest(request) => {
+                        let info = match PaymentRequestInfo::parse(&request) {
+                            Ok(info) => info,
+                            Err(err) => {
+                                self.smart_input_error = Some(error_text(&err));
+                                return Task::none();
+                            }
+                        };
+                        if !info.mints.is_empty() {
+                            // select the first accepted mint which is known
+                            let known = info
+                                .mints
+                                .iter()
+                                .find(|url| self.mints_info.iter().any(|mi| mi.url == **url));
+                            match known {
+                                Some(mint_url) => {
+                                    let _res = self.app.select_mint(mint_url.clone());
+                                }
+                                None => {
+                                    self.smart_input_error = Some(format!(
+                                        "None of the mints of the payment request is known: {}",
+                                        info.mints.join(", ")
+                                    ));
+                                    return Task::none();
+                                }
+                            }
+                        }
+                        if let Some(amount) = info.amount {
+                            self.amount_input = amount.to_string();
+                        }
+                        self.payment_request = Some((request, info));
+                        self.send_ec_state = SendECState::NotRequested;
+                        UiMainTab::SendEC
+                    }
+                    InputKind::MintUrl(mint_url) => {
+                        self.add_mint_input = mint_url;
+                        UiMainTab::Mints
+                    }
+                    InputKind::Unknown => {
+                        self.smart_input_error = Some("Not recognized".to_owned());
+                        return Task::none();
+                    }
+                };
+                self.smart_input.clear();
+                return self.update(Message::Tab(tab));
+            }
             Message::InvoiceInput(invoice) => {
                 self.invoice_input = invoice;
+                self.decode_input(self.invoice_input.clone());
             }
             Message::TokenInput(token) => {
                 self.token_input = token;
+                self.decode_input(self.token_input.clone());
             }
             Message::AddMintInput(mint_url) => {
                 self.add_mint_input = mint_url;
@@ -567,17 +1874,41 @@ impl IcedApp {
                 self.rec_ln_state = RecLNState::NotRequested;
                 self.show_invoice_widget.set_data(None, None);
             }
+            Message::ClaimPending => {
+                self.rec_ln_state = RecLNState::ClaimRequested;
+                let _res = self.app.claim_pending();
+            }
             Message::ReceiveEC(token) => {
                 self.rec_ec_state = RecECState::Requested;
-                let _res = self.app.receive_ec(token);
+                let _res = self.app.receive_ec(token, false);
+            }
+            Message::ReceiveECTrust(token) => {
+                self.rec_ec_state = RecECState::Requested;
+                let _res = self.app.receive_ec(token, true);
             }
             Message::ReceiveECOK => {
                 self.rec_ec_state = RecECState::NotRequested;
                 self.token_input.clear();
             }
             Message::SendLN(invoice) => {
+                self.send_ln_state = SendLNState::QuoteRequested;
+                let _res = self.app.melt_quote(invoice);
+            }
+            Message::SendLNConfirm => {
+                if let SendLNState::QuoteReady(quote) = &self.send_ln_state {
+                    let _res = self.app.melt_confirm(quote.quote_id.clone());
+                    self.send_ln_state = SendLNState::Requested;
+                }
+            }
+            Message::SendLNCancel => {
+                if let SendLNState::QuoteReady(quote) = &self.send_ln_state {
+                    let _res = self.app.melt_cancel(quote.quote_id.clone());
+                }
+                self.send_ln_state = SendLNState::NotRequested;
+            }
+            Message::SendLNMpp(invoice) => {
                 self.send_ln_state = SendLNState::Requested;
-                let _res = self.app.melt_to_ln(invoice);
+                let _res = self.app.melt_to_ln_mpp(invoice);
             }
             Message::SendLNOK => {
                 self.send_ln_state = SendLNState::NotRequested;
@@ -587,14 +1918,71 @@ impl IcedApp {
                 self.send_ec_state = SendECState::Requested;
                 let _res = self.app.send_ec(amount);
             }
+            Message::SendECForRequest(amount) => {
+                if let Some((request, _)) = &self.payment_request {
+                    self.send_ec_state = SendECState::Requested;
+                    let _res = self.app.send_ec_for_request(request.clone(), Some(amount));
+                }
+            }
+            Message::PaymentRequestCancel => {
+                self.payment_request = None;
+                self.amount_input = "0".to_owned();
+            }
+            Message::SendECMulti(amount, consolidate) => {
+                self.send_ec_state = SendECState::Requested;
+                let _res = self.app.send_ec_multi(amount, consolidate);
+            }
+            Message::SendECTokenIndex(new_index) => {
+                if let SendECState::CompletedMulti(Ok(sent), index) = &mut self.send_ec_state {
+                    if let Some(t) = sent.tokens.get(new_index) {
+                        self.show_token_widget.set_data(Some(t.token.clone()), None);
+                        *index = new_index;
+                    }
+                }
+            }
             Message::SendECOK => {
                 self.send_ec_state = SendECState::NotRequested;
+                self.payment_request = None;
                 self.amount_input = "0".to_owned();
                 self.show_token_widget.set_data(None, None);
+                let _res = self.app.check_pending_tokens();
+            }
+            Message::CheckWalletHealth => {
+                self.wallet_health_state = WalletHealthState::Requested;
+                let _res = self.app.check_wallet_health();
+            }
+            Message::ShowProofs(mint_url) => {
+                self.mint_proofs = Some(Ok(Vec::new()));
+                let _res = self.app.list_proofs(Some(mint_url));
+            }
+            Message::HideProofs => {
+                self.mint_proofs = None;
+            }
+            Message::Consolidate(mint_url) => {
+                self.consolidate_state = ConsolidateState::Requested;
+                let _res = self.app.consolidate(Some(mint_url), None);
+            }
+            Message::MigrateKeysets(mint_url) => {
+                self.migrate_keysets_state = MigrateKeysetsState::Requested;
+                let _res = self.app.migrate_keysets(mint_url);
+            }
+            Message::MigrateKeysetsOK => {
+                self.migrate_keysets_state = MigrateKeysetsState::NotRequested;
+            }
+            Message::CheckWalletHealthOK => {
+                self.wallet_health_state = WalletHealthState::NotRequested;
+            }
+            Message::CheckPendingTokens => {
+                self.reclaim_result = None;
+                let _res = self.app.check_pending_tokens();
+            }
+            Message::ReclaimToken(tx_id) => {
+                self.reclaim_result = None;
+                let _res = self.app.reclaim_token(tx_id);
             }
             Message::WidgetMessage((name, wmsg)) => {
-                let _res = self.show_invoice_widget.update(&name, &wmsg);
-                let _res = self.show_token_widget.update(&name, &wmsg);
+                self.show_invoice_widget.update(&name, &wmsg);
+                self.show_token_widget.update(&name, &wmsg);
             }
             Message::CopyToClipboard(text) => {
                 println!("Copying to cloibpoard... ({}...)", &text[0..8]);
@@ -604,7 +1992,10 @@ impl IcedApp {
         Task::none()
     }
 
-    pub fn view(&self) -> Element<Message> {
-        self.view_main()
+    pub fn view(&self) -> Element<'_, Message> {
+        match self.open_wallet_state {
+            OpenWalletState::Completed(Ok(_)) => self.view_main(),
+            _ => self.view_open_wallet(),
+        }
     }
 }
@@ -1,13 +1,25 @@
 use crate::common::{Message, MyFonts, WidgetMessage};
 use iced::widget::qr_code::{Data, ErrorCorrection};
 use iced::widget::{button, qr_code, row, text, Column, Row};
-use iced::Element;
+use iced::{Element, Subscription};
+use parakesh_common::pk_multipart::{encode_parts, DEFAULT_FRAGMENT_LEN, MIN_FRAGMENT_LEN};
+
+use std::time::Duration;
+
+/// Time a part of an animated QR code is shown, default and limits, in millisecs
+const DEFAULT_FRAME_MS: u64 = 500;
+const MIN_FRAME_MS: u64 = 100;
+const MAX_FRAME_MS: u64 = 2000;
+const MAX_FRAGMENT_LEN: usize = 800;
 
 #[derive(Clone, Debug, Default)]
 enum ShowMode {
     #[default]
     String,
+    /// QR code, animated if the string does not fit in one
     QRCode,
+    /// Animated QR code, cycling through the parts of the string
+    AnimatedQR,
 }
 
 /// A 'widget' to show a long string, string or QR code representation
@@ -19,11 +31,16 @@ struct ShowLongStringWidget {
     data_string: Option<String>,
     data_bin: Option<Vec<u8>>,
     data_qr: Option<Data>,
+    /// QR codes of the parts, for the animated QR code
+    parts_qr: Vec<Data>,
+    part_index: usize,
+    fragment_len: usize,
+    frame_ms: u64,
     show_mode: ShowMode,
 }
 
 /// TODO: use iced 13.1 advanced::widget::text for proper wrapping
-fn wrap_text(text: &String) -> String {
+fn wrap_text(text: &str) -> String {
     let tl = text.len();
     let mut out = String::with_capacity(tl * 2);
     let mut pos = 0;
@@ -58,6 +75,10 @@ impl ShowLongStringWidget {
             data_string: None,
             data_bin: None,
             data_qr: None,
+            parts_qr: Vec::new(),
+            part_index: 0,
+            fragment_len: DEFAULT_FRAGMENT_LEN,
+            frame_ms: DEFAULT_FRAME_MS,
             show_mode: ShowMode::String,
         }
     }
@@ -66,7 +87,7 @@ impl ShowLongStringWidget {
     /// by default (if it's None), the string is used.
     fn set_data(&mut self, data_string: Option<String>, data_bin: Option<Vec<u8>>) {
         if let Some(data_string) = data_string {
-            let mut bin = match data_bin {
+            let bin = match data_bin {
                 Some(data) => data,
                 None => data_string.as_bytes().to_vec(),
             };
@@ -76,11 +97,9 @@ impl ShowLongStringWidget {
                     self.data_qr = Some(qr);
                 }
                 Err(err) => {
+                    // Too long, will be shown as animated QR code
                     println!("QR error: {}", err);
-                    // Put the error in the QR code
-                    bin = format!("Error: {}", err).as_bytes().to_vec();
-                    self.data_bin = Some(bin.clone());
-                    self.data_qr = Data::new(bin).ok();
+                    self.data_qr = None;
                 }
             }
             self.data_string = Some(data_string);
@@ -89,9 +108,68 @@ impl ShowLongStringWidget {
             self.data_bin = None;
             self.data_qr = None;
         }
+        self.set_parts();
+    }
+
+    /// Split the string into parts, for the animated QR code.
+    /// Uppercase, as BC-UR parts are encoded more compactly in QR alphanumeric mode.
+    fn set_parts(&mut self) {
+        self.part_index = 0;
+        self.parts_qr = match &self.data_string {
+            Some(data_string) => encode_parts(data_string, self.fragment_len)
+                .into_iter()
+                .filter_map(|part| {
+                    Data::with_error_correction(part.to_uppercase(), ErrorCorrection::Low).ok()
+                })
+                .collect(),
+            None => Vec::new(),
+        };
     }
 
-    fn view(&self) -> Element<Message> {
+    fn is_animated(&self) -> bool {
+        match self.show_mode {
+            ShowMode::String => false,
+            ShowMode::QRCode => self.data_qr.is_none() && !self.parts_qr.is_empty(),
+            ShowMode::AnimatedQR => !self.parts_qr.is_empty(),
+        }
+    }
+
+    /// Timer for the animated QR code, if shown
+    fn subscription(&self) -> Subscription<Message> {
+        if self.is_animated() && self.parts_qr.len() > 1 {
+            iced::time::every(Duration::from_millis(self.frame_ms))
+                .with(self.name.clone())
+                .map(|(name, _)| Message::WidgetMessage((name, WidgetMessage::AnimatedQRTick)))
+        } else {
+            Subscription::none()
+        }
+    }
+
+    fn view_animated_qr(&self) -> Vec<Element<'_, Message>> {
+        let wmsg = |wmsg| Message::WidgetMessage((self.name.clone(), wmsg));
+        vec![
+            row![
+                text(format!(
+                    "Part {} of {}",
+                    self.part_index + 1,
+                    self.parts_qr.len()
+                ))
+                .size(15),
+                button("Slower").on_press(wmsg(WidgetMessage::AnimatedQRSlower)),
+                button("Faster").on_press(wmsg(WidgetMessage::AnimatedQRFaster)),
+                button("Smaller parts").on_press(wmsg(WidgetMessage::AnimatedQRSmallerParts)),
+                button("Larger parts").on_press(wmsg(WidgetMessage::AnimatedQRLargerParts)),
+            ]
+            .spacing(10)
+            .into(),
+            match self.parts_qr.get(self.part_index) {
+                Some(data) => row![qr_code(data)].into(),
+                None => row![text("(empty QR)")].into(),
+            },
+        ]
+    }
+
+    fn view(&self) -> Element<'_, Message> {
         let mut header_arr: Vec<Element<Message>> = vec![button("Copy")
             .on_press(Message::CopyToClipboard(
                 self.data_string.as_ref().unwrap_or(&"".to_owned()).clone(),
@@ -109,11 +187,11 @@ impl ShowLongStringWidget {
                         )))
                         .into(),
                 );
-                let textc = wrap_text(&self.data_string.as_ref().unwrap_or(&"(empty)".to_owned()));
+                let textc = wrap_text(self.data_string.as_deref().unwrap_or("(empty)"));
                 contents_arr
                     .push(row![text(textc).font(MyFonts::mono()).size(15).height(400)].into());
             }
-            ShowMode::QRCode => {
+            ShowMode::QRCode | ShowMode::AnimatedQR => {
                 header_arr.push(
                     button("Show String")
                         .on_press(Message::WidgetMessage((
@@ -122,9 +200,23 @@ impl ShowLongStringWidget {
                         )))
                         .into(),
                 );
-                match &self.data_qr {
-                    Some(data) => contents_arr.push(row![qr_code(data)].into()),
-                    None => contents_arr.push(row![text("(empty QR)")].into()),
+                if self.is_animated() {
+                    contents_arr.extend(self.view_animated_qr());
+                } else {
+                    if self.parts_qr.len() > 1 {
+                        header_arr.push(
+                            button("Show animated QR")
+                                .on_press(Message::WidgetMessage((
+                                    self.name.clone(),
+                                    WidgetMessage::ShowLongStringModeAnimatedQR,
+                                )))
+                                .into(),
+                        );
+                    }
+                    match &self.data_qr {
+                        Some(data) => contents_arr.push(row![qr_code(data)].into()),
+                        None => contents_arr.push(row![text("(empty QR)")].into()),
+                    }
                 }
             }
         }
@@ -155,6 +247,28 @@ impl ShowLongStringWidget {
             match wmsg {
                 WidgetMessage::ShowLongStringModeQR => self.show_mode = ShowMode::QRCode,
                 WidgetMessage::ShowLongStringModeString => self.show_mode = ShowMode::String,
+                WidgetMessage::ShowLongStringModeAnimatedQR => {
+                    self.show_mode = ShowMode::AnimatedQR
+                }
+                WidgetMessage::AnimatedQRTick => {
+                    if !self.parts_qr.is_empty() {
+                        self.part_index = (self.part_index + 1) % self.parts_qr.len();
+                    }
+                }
+                WidgetMessage::AnimatedQRFaster => {
+                    self.frame_ms = (self.frame_ms * 2 / 3).max(MIN_FRAME_MS)
+                }
+                WidgetMessage::AnimatedQRSlower => {
+                    self.frame_ms = (self.frame_ms * 3 / 2).min(MAX_FRAME_MS)
+                }
+                WidgetMessage::AnimatedQRSmallerParts => {
+                    self.fragment_len = (self.fragment_len * 2 / 3).max(MIN_FRAGMENT_LEN);
+                    self.set_parts();
+                }
+                WidgetMessage::AnimatedQRLargerParts => {
+                    self.fragment_len = (self.fragment_len * 3 / 2).min(MAX_FRAGMENT_LEN);
+                    self.set_parts();
+                }
             }
         }
     }
@@ -176,10 +290,14 @@ impl ShowTokenWidget {
         self.base.set_data(data_string, data_bin);
     }
 
-    pub(crate) fn view(&self) -> Element<Message> {
+    pub(crate) fn view(&self) -> Element<'_, Message> {
         self.base.view()
     }
 
+    pub(crate) fn subscription(&self) -> Subscription<Message> {
+        self.base.subscription()
+    }
+
     pub(crate) fn update(&mut self, name: &str, wmsg: &WidgetMessage) {
         self.base.update(name, wmsg)
     }
@@ -201,10 +319,14 @@ impl ShowInvoiceWidget {
         self.base.set_data(data_string, data_bin);
     }
 
-    pub(crate) fn view(&self) -> Element<Message> {
+    pub(crate) fn view(&self) -> Element<'_, Message> {
         self.base.view()
     }
 
+    pub(crate) fn subscription(&self) -> Subscription<Message> {
+        self.base.subscription()
+    }
+
     pub(crate) fn update(&mut self, name: &str, wmsg: &WidgetMessage) {
         self.base.update(name, wmsg)
     }
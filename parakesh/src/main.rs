@@ -1,36 +1,245 @@
-use parakesh_common::pk_app::{BalanceInfo, WalletInfo};
+use parakesh_common::pk_app::{
+    BalanceInfo, ConsolidateResult, DecodedInput, KeysetMigrationSummary, MintInfo, MintProofs,
+    WalletHealthReport, WalletInfo,
+};
 use parakesh_common::pk_app_async::AppEvent;
-use parakesh_common::{MintsSummary, PKAppAsync};
+use parakesh_common::pk_history::{format_unix_time, HistoryFilter, Transaction};
+use parakesh_common::pk_input::{classify_input, InputKind};
+use parakesh_common::{MintsSummary, PKAppAsync, PKAppConfig, PkError, SEED_VERIFY_WORD_COUNT};
+
+use futures::channel::mpsc;
+use futures::StreamExt;
 
 use std::io;
 use std::io::Write;
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+/// Environment variable for the wallet password, for non-interactive use
+const PASSWORD_ENV_VAR: &str = "PARAKESH_PASSWORD";
+
+/// With JSON output, stdout has only the JSON objects, one per line;
+/// other text (prompts, help, argument errors) goes to stderr
+static JSON_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+/// The quote of a Lightning payment waiting for the confirmation of the user (interactive mode)
+static PENDING_MELT_QUOTE: Mutex<Option<String>> = Mutex::new(None);
+
+/// Print a line of text which is not a result: to stdout, or to stderr with JSON output
+macro_rules! textln {
+    ($($arg:tt)*) => {
+        if JSON_OUTPUT.load(Ordering::Relaxed) {
+            eprintln!($($arg)*)
+        } else {
+            println!($($arg)*)
+        }
+    };
+}
+
+/// Print text which is not a result, without newline (e.g. a prompt), see `textln`
+macro_rules! text {
+    ($($arg:tt)*) => {
+        if JSON_OUTPUT.load(Ordering::Relaxed) {
+            eprint!($($arg)*);
+            std::io::stderr().flush().unwrap();
+        } else {
+            print!($($arg)*);
+            std::io::stdout().flush().unwrap();
+        }
+    };
+}
+
+/// Exit codes of the one-shot (non-interactive) mode
+const EXIT_FAILED: u8 = 1;
+const EXIT_INVALID_ARGS: u8 = 2;
+const EXIT_OPEN_FAILED: u8 = 3;
+const EXIT_INSUFFICIENT_BALANCE: u8 = 4;
+const EXIT_MINT_UNREACHABLE: u8 = 5;
+const EXIT_TOKEN_SPENT: u8 = 6;
 
 fn get_status(app: &mut PKAppAsync) {
     let _res = app.get_balance_and_wallet_info();
 }
 
 fn cmd_help() {
-    println!("\nAvailable commands:");
-    println!("  help\t\t\t\tShows a list of commands.");
-    println!("  quit | exit | q\t\tExit.");
-    println!("");
-    println!("  status\t\t\tShow wallet status.");
-    println!("  listmints\t\t\tList used mints.");
-    println!("  addmint <mint_url>\t\tAdd a mint.");
-    println!("  selectmint <mint_number>\tSelect a mint, from known ones, by number, as listed in 'listminst', e.g. '1'; OR");
-    println!("  selectmint <mint_url>\t\tSelect a mint, from known ones, by url.");
-    println!("");
-    println!("  recln <amount_sats>\t\tReceive LN, show LN invoice to-be-paid, for the specified amount, with the current mint.");
-    println!("  sendln <ln_invoice>\t\tSend LN.");
-    println!("  rec <ecash_token>\t\tReceive ecash");
-    println!("  send <amount_sats>\t\tSend ecash, prepare ecash token for sending.");
-    println!("");
+    textln!("\nAvailable commands:");
+    textln!("  help\t\t\t\tShows a list of commands.");
+    textln!("  quit | exit | q\t\tExit.");
+    textln!();
+    textln!("  open\t\t\t\tOpen the wallet, asks for the password.");
+    textln!("  init\t\t\t\tInitialize a new wallet, with a new seed, asks for a password.");
+    textln!("  restore\t\t\tRestore the wallet from seed phrase, asks for a password.");
+    textln!("  seed\t\t\t\tShow the seed phrase (mnemonic), for backup.");
+    textln!("  verifyseed\t\t\tVerify the seed phrase backup, shows which words to enter; then");
+    textln!(
+        "  verifyseed <n>:<word> ...\tVerify the seed phrase backup, with the requested words."
+    );
+    textln!("  status\t\t\tShow wallet status.");
+    textln!("  balance\t\t\tShow the balance.");
+    textln!("  listmints\t\t\tList used mints.");
+    textln!("  addmint <mint_url>\t\tAdd a mint.");
+    textln!("  selectmint <mint_number>\tSelect a mint, from known ones, by number, as listed in 'listminst', e.g. '1'; OR");
+    textln!("  selectmint <mint_url>\t\tSelect a mint, from known ones, by url.");
+    textln!();
+    textln!("  recln <amount_sats> [--wait]\tReceive LN, show LN invoice to-be-paid, for the specified amount, with the current mint;");
+    textln!("  \t\t\t\twith --wait, wait for the payment (one-shot mode).");
+    textln!("  claim\t\t\t\tClaim the pending LN receives, paid after the waiting has ended.");
+    textln!("  decode <ln_invoice|ecash_token>\tShow the details of an invoice or token.");
+    textln!("  pay <input> [--trust]\t\tAct on a pasted input: pay an invoice (sendln), receive a token (rec),");
+    textln!(
+        "  \t\t\t\tpay a payment request (creqA...) with a token, or add a mint URL (addmint)."
+    );
+    textln!("  \t\t\t\tThe parts of an animated QR code (ur:bytes/...) can be given, separated by spaces.");
+    textln!(
+        "  sendln <ln_invoice> [--mpp]\tSend LN, after confirmation of the amount and fee reserve;"
+    );
+    textln!("  \t\t\t\twith --mpp, pay from several mints (multi-path payment).");
+    textln!("  \t\t\t\tIn one-shot mode, --yes pays without asking (also for pay).");
+    textln!("  rec | receive <ecash_token> [--trust]\tReceive ecash, with the mint of the token;");
+    textln!("  \t\t\t\twith --trust, the mint is added if not known yet.");
+    textln!("  send <amount_sats> [--multi|--consolidate]");
+    textln!(
+        "  \t\t\t\tSend ecash, prepare ecash token for sending; with --multi, from several mints,"
+    );
+    textln!("  \t\t\t\twith a token per mint; with --consolidate, transfer to one mint first.");
+    textln!("  pending\t\t\tShow the sent tokens not claimed yet by the recipient, checked with the mints.");
+    textln!("  reclaim <tx_id>\t\tReclaim a sent token not claimed yet, by its ID, as listed in 'pending'.");
+    textln!();
+    textln!("  transfer <from_mint_url> <to_mint_url> <amount_sats>");
+    textln!("  \t\t\t\tTransfer funds from one mint to another, via Lightning.");
+    textln!();
+    textln!("  recover [<mint_url> ...]\tRecover ecash from the seed, from all known mints, or the given ones.");
+    textln!("  health\t\t\tCheck the state of all proofs with the mints, remove spent ones,");
+    textln!("  \t\t\t\trelease ones stuck by interrupted operations.");
+    textln!("  proofs [<mint_url>]\t\tList the stored proofs and denominations, of all mints or the given one.");
+    textln!("  consolidate [<mint_url>] [--value <amount>]");
+    textln!(
+        "  \t\t\t\tSwap the proofs of the mint (default: selected) into the fewest denominations,"
+    );
+    textln!("  \t\t\t\tor into proofs of the given value.");
+    textln!(
+        "  keysets\t\t\tRetrieve the keysets of the mints, check for funds in inactive keysets."
+    );
+    textln!("  migrate [<mint_url>]\t\tMove the funds from inactive keysets to the active one, of all mints or the given one.");
+    textln!();
+    textln!("  history [<count> [<skip>]]\tShow the transaction history, newest first; can be filtered by");
+    textln!("  \t\t\t\tin|out, ln|ecash, pending|completed|failed|reclaimed, <mint_url>.");
+    textln!();
+}
+
+/// Read a new password, asking twice, until the two match
+fn read_new_password() -> Option<String> {
+    loop {
+        let password = match rpassword::prompt_password("Enter password for the new wallet: ") {
+            Ok(password) => password,
+            Err(err) => {
+                textln!("\nERROR reading password {}", err);
+                return None;
+            }
+        };
+        let password2 = match rpassword::prompt_password("Repeat password: ") {
+            Ok(password) => password,
+            Err(err) => {
+                textln!("\nERROR reading password {}", err);
+                return None;
+            }
+        };
+        if password == password2 {
+            return Some(password);
+        }
+        textln!("\nERROR: The passwords don't match, try again");
+    }
+}
+
+fn cmd_open(app: &mut PKAppAsync) {
+    match rpassword::prompt_password("Enter wallet password: ") {
+        Ok(password) => {
+            let _res = app.open_wallet(password);
+        }
+        Err(err) => textln!("\nERROR reading password {}", err),
+    }
+}
+
+fn cmd_init(app: &mut PKAppAsync) {
+    textln!("Creating a new wallet, with a new random seed, stored encrypted with a password.");
+    if let Some(password) = read_new_password() {
+        let _res = app.init_new_wallet(password);
+        textln!(
+            "The seed is stored in the secret file {}",
+            app.config().secret_file_path().display()
+        );
+    }
+}
+
+/// Read a line from the console, after printing the prompt
+fn read_line(prompt: &str) -> Option<String> {
+    text!("{}", prompt);
+    let mut line = String::new();
+    match io::stdin().read_line(&mut line) {
+        Ok(0) => None,
+        Ok(_) => Some(line.trim().to_owned()),
+        Err(e) => {
+            textln!("ERROR: {}", e);
+            None
+        }
+    }
+}
+
+fn cmd_restore(app: &mut PKAppAsync) {
+    let mnemonic = match read_line("Enter the seed phrase (mnemonic words): ") {
+        Some(mnemonic) => mnemonic,
+        None => return,
+    };
+    if let Some(password) = read_new_password() {
+        let _res = app.restore_wallet(mnemonic, password);
+        textln!(
+            "The seed is stored in the secret file {}",
+            app.config().secret_file_path().display()
+        );
+        textln!("After the wallet is restored, use 'recover <mint_url> ...' to recover the ecash from the mints used.");
+    }
+}
+
+/// Open the wallet if it exists, otherwise create or restore one
+fn open_or_init_wallet(app: &mut PKAppAsync) {
+    if app.is_wallet_initialized() {
+        cmd_open(app);
+    } else {
+        textln!("No wallet found.");
+        loop {
+            match read_line("Create a (n)ew wallet, or (r)estore from seed phrase? [n/r] ")
+                .as_deref()
+            {
+                Some("n") => break cmd_init(app),
+                Some("r") => break cmd_restore(app),
+                Some(_) => {}
+                None => break,
+            }
+        }
+    }
+}
+
+fn cmd_seed(app: &mut PKAppAsync) {
+    let _res = app.get_seed_mnemonic();
+}
+
+fn cmd_verifyseed_start(app: &mut PKAppAsync) {
+    let _res = app.seed_verify_quiz(SEED_VERIFY_WORD_COUNT);
+}
+
+fn cmd_verifyseed_check(app: &mut PKAppAsync, words: Vec<(usize, String)>) {
+    let _res = app.seed_verify_check(words);
 }
 
 fn cmd_status(app: &mut PKAppAsync) {
     get_status(app)
 }
 
+fn cmd_balance(app: &mut PKAppAsync) {
+    let _res = app.get_balance();
+}
+
 fn cmd_list_mints(app: &mut PKAppAsync) {
     let _res = app.get_mints_info();
 }
@@ -51,133 +260,373 @@ fn cmd_recln(app: &mut PKAppAsync, amount_sats: u64) {
     let _res = app.mint_from_ln(amount_sats);
 }
 
-fn cmd_sendln(app: &mut PKAppAsync, ln_invoice: &str) {
-    let _res = app.melt_to_ln(ln_invoice.to_owned());
+fn cmd_claim(app: &mut PKAppAsync) {
+    let _res = app.claim_pending();
+}
+
+fn cmd_decode(app: &mut PKAppAsync, input: &str) {
+    let _res = app.decode_input(input.to_owned());
+}
+
+/// Send LN: with MPP it is paid right away, otherwise a quote is requested first,
+/// and paid after confirmation, see `cmd_sendln_confirm`
+fn cmd_sendln(app: &mut PKAppAsync, ln_invoice: &str, mpp: bool) {
+    let _res = if mpp {
+        app.melt_to_ln_mpp(ln_invoice.to_owned())
+    } else {
+        app.melt_quote(ln_invoice.to_owned())
+    };
+}
+
+/// Pay the quote of a Lightning payment, or cancel it
+fn cmd_sendln_confirm(app: &mut PKAppAsync, quote_id: String, confirmed: bool) {
+    if confirmed {
+        let _res = app.melt_confirm(quote_id);
+    } else {
+        textln!("Send LN cancelled");
+        let _res = app.melt_cancel(quote_id);
+    }
+}
+
+fn cmd_rec(app: &mut PKAppAsync, token: &str, trust_mint: bool) {
+    let _res = app.receive_ec(token.to_owned(), trust_mint);
+}
+
+fn cmd_send(app: &mut PKAppAsync, amount_sats: u64, multi: bool, consolidate: bool) {
+    let _res = if multi || consolidate {
+        app.send_ec_multi(amount_sats, consolidate)
+    } else {
+        app.send_ec(amount_sats)
+    };
+}
+
+fn cmd_send_for_request(app: &mut PKAppAsync, request: &str) {
+    textln!("Paying the payment request with a token, give the token to the requester.");
+    let _res = app.send_ec_for_request(request.to_owned(), None);
+}
+
+fn cmd_pending(app: &mut PKAppAsync) {
+    let _res = app.check_pending_tokens();
+}
+
+fn cmd_reclaim(app: &mut PKAppAsync, tx_id: u64) {
+    let _res = app.reclaim_token(tx_id);
+}
+
+fn cmd_health(app: &mut PKAppAsync) {
+    let _res = app.check_wallet_health();
 }
 
-fn cmd_rec(app: &mut PKAppAsync, token: &str) {
-    let _res = app.receive_ec(token.to_owned());
+fn cmd_proofs(app: &mut PKAppAsync, mint_url: Option<&str>) {
+    let _res = app.list_proofs(mint_url.map(|m| m.to_owned()));
 }
 
-fn cmd_send(app: &mut PKAppAsync, amount_sats: u64) {
-    let _res = app.send_ec(amount_sats);
+fn cmd_consolidate(app: &mut PKAppAsync, mint_url: Option<&str>, target_value: Option<u64>) {
+    let _res = app.consolidate(mint_url.map(|m| m.to_owned()), target_value);
+}
+
+fn cmd_keysets(app: &mut PKAppAsync) {
+    let _res = app.refresh_keysets();
+}
+
+fn cmd_migrate(app: &mut PKAppAsync, mint_url: Option<&str>) {
+    let _res = app.migrate_keysets(mint_url.map(|m| m.to_owned()));
+}
+
+fn cmd_transfer(app: &mut PKAppAsync, from_mint: &str, to_mint: &str, amount_sats: u64) {
+    let _res = app.transfer_between_mints(from_mint.to_owned(), to_mint.to_owned(), amount_sats);
+}
+
+fn cmd_recover(app: &mut PKAppAsync, mints: Vec<String>) {
+    let _res = app.restore_from_seed(mints);
+}
+
+fn cmd_history(app: &mut PKAppAsync, filter: HistoryFilter) {
+    let _res = app.get_history(filter);
 }
 
 fn print_prompt() {
+    if PENDING_MELT_QUOTE.lock().unwrap().is_some() {
+        // a Lightning payment waits for confirmation, asked also with JSON output
+        text!("Proceed? (y/n): ");
+        return;
+    }
+    // no prompt with JSON output, it would break the JSON lines
+    if JSON_OUTPUT.load(Ordering::Relaxed) {
+        return;
+    }
     print!("> ");
     std::io::stdout().flush().unwrap(); // Without flushing, the `>` doesn't print
 }
 
-fn poll_for_user_input(app: &mut PKAppAsync) {
-    println!("Enter \"help\" to view available commands. Press Ctrl-D to quit.");
-    loop {
-        print_prompt();
+/// Parse one command with its arguments, and send the corresponding request.
+/// Returns false if the command is unknown or the arguments are invalid.
+fn process_command(app: &mut PKAppAsync, words: &[&str]) -> bool {
+    let mut words = words.iter().copied();
+    let word = match words.next() {
+        Some(word) => word,
+        None => return false,
+    };
+    match word {
+        "help" => cmd_help(),
+        "open" => cmd_open(app),
+        "init" => cmd_init(app),
+        "restore" => cmd_restore(app),
+        "seed" => cmd_seed(app),
 
-        let mut line = String::new();
-        if let Err(e) = io::stdin().read_line(&mut line) {
-            break println!("ERROR: {}", e);
+        "verifyseed" => {
+            // optional (index, word) pairs, as <n>:<word>
+            let seed_words: Option<Vec<(usize, String)>> = words
+                .map(|index_and_word| {
+                    let (index, word) = index_and_word.split_once(':')?;
+                    Some((index.parse::<usize>().ok()?, word.to_owned()))
+                })
+                .collect();
+            match seed_words {
+                None => {
+                    textln!("\nERROR: verifyseed requires words as <n>:<word>, e.g. 3:apple");
+                    return false;
+                }
+                Some(seed_words) if seed_words.is_empty() => cmd_verifyseed_start(app),
+                Some(seed_words) => cmd_verifyseed_check(app, seed_words),
+            }
         }
 
-        if line.len() == 0 {
-            // We hit EOF / Ctrl-D
-            break;
+        "status" => cmd_status(app),
+        "balance" => cmd_balance(app),
+        "listmints" => cmd_list_mints(app),
+
+        "addmint" => {
+            let mint_url = if let Some(word) = words.next() {
+                word
+            } else {
+                textln!("\nERROR: addmint requires <mint_url>");
+                return false;
+            };
+            cmd_addmint(app, mint_url);
         }
 
-        let mut words = line.split_whitespace();
-        if let Some(word) = words.next() {
-            match word {
-                "help" => cmd_help(),
-                "quit" | "exit" | "q" => break,
-                "status" => cmd_status(app),
-                "listmints" => cmd_list_mints(app),
-
-                "addmint" => {
-                    let mint_url = if let Some(word) = words.next() {
-                        word
-                    } else {
-                        println!("\nERROR: addmint requires <mint_url>");
-                        continue;
-                    };
-                    cmd_addmint(app, mint_url);
+        "selectmint" => {
+            let mint_number_or_url = if let Some(word) = words.next() {
+                word
+            } else {
+                textln!("\nERROR: selectmint requires <mint_number> OR <mint_url>");
+                return false;
+            };
+            match mint_number_or_url.parse::<usize>() {
+                Ok(mint_number) => cmd_selectmint_by_index(app, mint_number),
+                Err(_) => {
+                    // could not parse parameter as number, assume url
+                    cmd_selectmint_by_url(app, mint_number_or_url);
                 }
+            }
+        }
 
-                "selectmint" => {
-                    let mint_number_or_url = if let Some(word) = words.next() {
-                        word
-                    } else {
-                        println!("\nERROR: selectmint requires <mint_number> OR <mint_url>");
-                        continue;
-                    };
-                    match mint_number_or_url.parse::<usize>() {
-                        Ok(mint_number) => cmd_selectmint_by_index(app, mint_number),
-                        Err(_) => {
-                            // could not parse parameter as number, assume url
-                            cmd_selectmint_by_url(app, mint_number_or_url);
-                        }
-                    }
+        "recln" => {
+            let amount_str = if let Some(word) = words.next() {
+                word
+            } else {
+                textln!("\nERROR: recln requires amount (in sats)");
+                return false;
+            };
+            let amount = match amount_str.parse::<u64>() {
+                Ok(amount) => amount,
+                Err(err) => {
+                    textln!("\nERROR: recln requires amount (in sats); {}", err);
+                    return false;
+                }
+            };
+            cmd_recln(app, amount);
+        }
+
+        "claim" => cmd_claim(app),
+
+        "decode" => {
+            // the parts of a multi-part input come as separate words
+            let input = words.collect::<Vec<_>>().join(" ");
+            if input.is_empty() {
+                textln!("\nERROR: decode requires a LN invoice or an ecash token");
+                return false;
+            }
+            cmd_decode(app, &input);
+        }
+
+        "sendln" => {
+            let invoice_str = if let Some(word) = words.next() {
+                word
+            } else {
+                textln!("\nERROR: sendln requires a LN invoice");
+                return false;
+            };
+            let mpp = words.any(|w| w == "--mpp");
+            cmd_sendln(app, invoice_str, mpp);
+        }
+
+        "pay" => {
+            let args: Vec<&str> = words.collect();
+            let trust_mint = args.contains(&"--trust");
+            // the parts of a multi-part input come as separate words
+            let input = args
+                .into_iter()
+                .filter(|w| !w.starts_with("--"))
+                .collect::<Vec<_>>()
+                .join(" ");
+            if input.is_empty() {
+                textln!("\nERROR: pay requires an input, e.g. a LN invoice or an ecash token");
+                return false;
+            }
+            match classify_input(&input) {
+                InputKind::Invoice(invoice) => cmd_sendln(app, &invoice, false),
+                InputKind::Token(token) => cmd_rec(app, &token, trust_mint),
+                InputKind::PaymentRequest(request) => cmd_send_for_request(app, &request),
+                InputKind::MintUrl(mint_url) => cmd_addmint(app, &mint_url),
+                InputKind::Unknown => {
+                    textln!("\nERROR: pay input not recognized");
+                    return false;
                 }
+            }
+        }
 
-                "recln" => {
-                    let amount_str = if let Some(word) = words.next() {
-                        word
-                    } else {
-                        println!("\nERROR: recln requires amount (in sats)");
-                        continue;
-                    };
-                    let amount = match amount_str.parse::<u64>() {
-                        Ok(amount) => amount,
-                        Err(err) => {
-                            println!(
-                                "\nERROR: recln requires amount (in sats); {}",
-                                err.to_string()
-                            );
-                            continue;
-                        }
-                    };
-                    cmd_recln(app, amount);
+        "rec" | "receive" => {
+            let token_str = if let Some(word) = words.next() {
+                word
+            } else {
+                textln!("\nERROR: rec requires an ecash token");
+                return false;
+            };
+            let trust_mint = words.any(|w| w == "--trust");
+            cmd_rec(app, token_str, trust_mint);
+        }
+
+        "send" => {
+            let amount_str = if let Some(word) = words.next() {
+                word
+            } else {
+                textln!("\nERROR: send requires amount (in sats)");
+                return false;
+            };
+            let amount = match amount_str.parse::<u64>() {
+                Ok(amount) => amount,
+                Err(err) => {
+                    textln!("\nERROR: send requires amount (in sats); {}", err);
+                    return false;
                 }
+            };
+            let flags: Vec<&str> = words.collect();
+            cmd_send(
+                app,
+                amount,
+                flags.contains(&"--multi"),
+                flags.contains(&"--consolidate"),
+            );
+        }
 
-                "sendln" => {
-                    let invoice_str = if let Some(word) = words.next() {
-                        word
-                    } else {
-                        println!("\nERROR: sendln requires a LN invoice");
-                        continue;
-                    };
-                    cmd_sendln(app, invoice_str);
+        "transfer" => {
+            let (from_mint, to_mint, amount_str) = match (words.next(), words.next(), words.next())
+            {
+                (Some(from_mint), Some(to_mint), Some(amount_str)) => {
+                    (from_mint, to_mint, amount_str)
+                }
+                _ => {
+                    textln!(
+                        "\nERROR: transfer requires <from_mint_url> <to_mint_url> <amount_sats>"
+                    );
+                    return false;
                 }
+            };
+            let amount = match amount_str.parse::<u64>() {
+                Ok(amount) => amount,
+                Err(err) => {
+                    textln!("\nERROR: transfer requires amount (in sats); {}", err);
+                    return false;
+                }
+            };
+            cmd_transfer(app, from_mint, to_mint, amount);
+        }
 
-                "rec" => {
-                    let token_str = if let Some(word) = words.next() {
-                        word
-                    } else {
-                        println!("\nERROR: rec requires an ecash token");
-                        continue;
-                    };
-                    cmd_rec(app, token_str);
+        "pending" => cmd_pending(app),
+
+        "reclaim" => {
+            let tx_id = match words.next().map(|w| w.parse::<u64>()) {
+                Some(Ok(tx_id)) => tx_id,
+                _ => {
+                    textln!("\nERROR: reclaim requires the ID of the sent token, see 'pending'");
+                    return false;
                 }
+            };
+            cmd_reclaim(app, tx_id);
+        }
+
+        "health" => cmd_health(app),
+
+        "proofs" => cmd_proofs(app, words.next()),
 
-                "send" => {
-                    let amount_str = if let Some(word) = words.next() {
-                        word
-                    } else {
-                        println!("\nERROR: send requires amount (in sats");
-                        continue;
-                    };
-                    let amount = match amount_str.parse::<u64>() {
-                        Ok(amount) => amount,
-                        Err(err) => {
-                            println!(
-                                "\nERROR: send requires amount (in sats); {}",
-                                err.to_string()
-                            );
-                            continue;
-                        }
-                    };
-                    cmd_send(app, amount);
+        "keysets" => cmd_keysets(app),
+
+        "migrate" => cmd_migrate(app, words.next()),
+
+        "consolidate" => match parse_consolidate_args(words) {
+            Ok((mint_url, target_value)) => cmd_consolidate(app, mint_url, target_value),
+            Err(err) => {
+                textln!("\nERROR: {}", err);
+                return false;
+            }
+        },
+
+        "recover" => {
+            let mints: Vec<String> = words.map(|w| w.to_owned()).collect();
+            cmd_recover(app, mints);
+        }
+
+        "history" => {
+            let args: Vec<&str> = words.collect();
+            match HistoryFilter::from_args(&args) {
+                Ok(filter) => cmd_history(app, filter),
+                Err(err) => {
+                    textln!("\nERROR: {}", err);
+                    return false;
                 }
+            }
+        }
+
+        _ => {
+            textln!("Unknown command. See `\"help\" for available commands.");
+            return false;
+        }
+    }
+    true
+}
+
+fn poll_for_user_input(app: &mut PKAppAsync) {
+    textln!("Enter \"help\" to view available commands. Press Ctrl-D to quit.");
+    loop {
+        print_prompt();
+
+        let mut line = String::new();
+        if let Err(e) = io::stdin().read_line(&mut line) {
+            break textln!("ERROR: {}", e);
+        }
+
+        // the answer to the confirmation of a Lightning payment
+        let pending_quote = PENDING_MELT_QUOTE.lock().unwrap().take();
+        if let Some(quote_id) = pending_quote {
+            cmd_sendln_confirm(app, quote_id, line.trim() == "y");
+            if !line.is_empty() {
+                continue;
+            }
+        }
 
-                _ => println!("Unknown command. See `\"help\" for available commands."),
+        if line.is_empty() {
+            // We hit EOF / Ctrl-D
+            break;
+        }
+
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.first() {
+            None => {}
+            Some(&"quit") | Some(&"exit") | Some(&"q") => break,
+            Some(_) => {
+                let _res = process_command(app, &words);
             }
         }
 
@@ -204,87 +653,804 @@ fn print_balance_and_wallet_info(balance_info: Option<&BalanceInfo>, wallet_info
     print!(" \t ");
     print!("Selected: {}", wallet_info.selected_mint_url);
     print!(" \t ");
-    println!("");
+    println!();
 }
 
-fn handle_event(event: AppEvent) {
+fn handle_event(app: &mut PKAppAsync, event: AppEvent, json: bool) {
     // println!("Got AppEvent {:?}", event);
+    output_event(&event, json);
+    if let AppEvent::WalletOpened(Ok(_)) = event {
+        // detect keysets deactivated by the mints
+        cmd_keysets(app);
+    }
+    if let AppEvent::MeltQuoteRes(Ok(quote)) = event {
+        // the answer is taken by the input loop
+        *PENDING_MELT_QUOTE.lock().unwrap() = Some(quote.quote_id);
+    }
+    // for nicer console reading
+    print_prompt();
+}
+
+/// Print the event, as text or as JSON
+fn output_event(event: &AppEvent, json: bool) {
+    if json {
+        println!("{}", event.to_json());
+    } else {
+        print_event(event);
+    }
+}
+
+fn print_event(event: &AppEvent) {
     match event {
+        AppEvent::WalletOpened(res) => match res {
+            Ok(_) => println!("Wallet opened"),
+            Err(err) => println!(
+                "\nERROR opening wallet, {}\nUse 'open', 'init' or 'restore' to retry.",
+                err
+            ),
+        },
+        AppEvent::SeedMnemonic(res) => match res {
+            Ok(mnemonic) => {
+                println!("Seed phrase, write it down and keep it secret!\n");
+                for (i, word) in mnemonic.split_whitespace().enumerate() {
+                    println!("  {:2}. {}", i + 1, word);
+                }
+                println!();
+            }
+            Err(err) => println!("\nERROR retrieving seed, {}", err),
+        },
+        AppEvent::SeedVerifyQuiz(res) => match res {
+            Ok(indices) => {
+                let numbers: Vec<String> = indices.iter().map(|i| format!("#{}", i)).collect();
+                let example: Vec<String> =
+                    indices.iter().map(|i| format!("{}:<word>", i)).collect();
+                println!(
+                    "Enter the words {} of your seed phrase, as:\n  verifyseed {}",
+                    numbers.join(", "),
+                    example.join(" ")
+                );
+            }
+            Err(err) => println!("\nERROR in seed verification, {}", err),
+        },
+        AppEvent::SeedVerifyResult(res) => match res {
+            Ok(true) => println!("Seed phrase backup verified OK"),
+            Ok(false) => println!("\nERROR: The words don't match, check your seed phrase backup!"),
+            Err(err) => println!("\nERROR in seed verification, {}", err),
+        },
         AppEvent::BalanceChange(balance_info) => match balance_info {
             Ok(balance) => println!("Balance: {} sats", balance.0),
             Err(err) => println!("\nERROR retrieving balance! {}", err),
         },
         AppEvent::WalletInfo(wallet_info) => match wallet_info {
             Ok(wallet_info) => {
-                print_balance_and_wallet_info(None, &wallet_info);
+                print_balance_and_wallet_info(None, wallet_info);
             }
             Err(err) => println!("\nERROR retrieving wallet info! {}", err),
         },
         AppEvent::BalanceAndWalletInfo(result) => match result {
             Ok((balance_info, wallet_info)) => {
-                print_balance_and_wallet_info(Some(&balance_info), &wallet_info);
+                print_balance_and_wallet_info(Some(balance_info), wallet_info);
             }
             Err(err) => println!("\nERROR retrieving balance/wallet info! {}", err),
         },
         AppEvent::MintsInfo(mint_info) => match mint_info {
-            Ok(mints) => {
-                if mints.is_empty() {
-                    println!("No mints used.");
-                } else {
-                    println!("Mints used: ({})", mints.len());
-                    for (i, mint) in mints.iter().enumerate() {
-                        println!("    {}\t{}\t{}", i + 1, mint.url, mint.balance);
-                    }
-                }
-            }
-            Err(err) => println!("\nERROR retrieving mints info {}", err.to_string()),
+            Ok(mints) => print_mints(mints),
+            Err(err) => println!("\nERROR retrieving mints info {}", err),
+        },
+        AppEvent::KeysetsRefreshed(res) => match res {
+            Ok(mints) => print_inactive_keysets(mints),
+            Err(err) => println!("\nERROR retrieving keysets, {}", err),
+        },
+        AppEvent::MigrateKeysetsRes(res) => match res {
+            Ok(summary) => print_keyset_migration(summary),
+            Err(err) => println!("\nERROR migrating keysets, {}", err),
         },
         AppEvent::MintAdded(res) => match res {
             Ok(_) => println!("Mint added"),
-            Err(err) => println!("\nERROR adding mint {}", err.to_string()),
+            Err(err) => println!("\nERROR adding mint {}", err),
         },
         AppEvent::MintSelectedByUrl(res) => match res {
             Ok(url) => println!("Mint selected: {}", url),
-            Err(err) => println!("\nERROR selecting mint {}", err.to_string()),
+            Err(err) => println!("\nERROR selecting mint {}", err),
         },
         AppEvent::MintSelectedByIndex(res) => match res {
             Ok(index) => println!("Mint selected: {}", index),
-            Err(err) => println!("\nERROR selecting mint {}", err.to_string()),
+            Err(err) => println!("\nERROR selecting mint {}", err),
         },
         AppEvent::MintFromLnRes(res) => match res {
             Ok(minted) => println!("Received LN, got ecash for {} sats", minted),
-            Err(err) => println!("\nERROR in receive LN {}", err.to_string()),
+            Err(err) => println!("\nERROR in receive LN {}", err),
+        },
+        AppEvent::ClaimPendingRes(res) => match res {
+            Ok(0) => println!("No paid pending receives"),
+            Ok(received) => println!("Claimed pending receives, got ecash for {} sats", received),
+            Err(err) => println!("\nERROR claiming pending receives, {}", err),
         },
         AppEvent::MintFromLnInvoice(invoice) => println!("Pay the invoice!\n\n{}\n", invoice),
+        AppEvent::MeltQuoteRes(res) => match res {
+            Ok(quote) => {
+                println!(
+                    "Pay {} sats from mint {}, fee reserve {} sats, quote expires {} UTC",
+                    quote.amount,
+                    quote.mint_url,
+                    quote.fee_reserve,
+                    format_unix_time(quote.expiry)
+                );
+                if !quote.invoice.description.is_empty() {
+                    println!("Invoice description: {}", quote.invoice.description);
+                }
+            }
+            Err(err) => println!("\nERROR preparing send LN, {}", err),
+        },
         AppEvent::MeltToLnRes(res) => match res {
-            Ok(sent) => println!("Sent LN, amount {} sats", sent),
-            Err(err) => println!("\nERROR in send LN {}", err.to_string()),
+            Ok(melted) => println!(
+                "Sent LN, amount {} sats, fee {} sats, change {} sats, from mint {}",
+                melted.amount, melted.fee, melted.change, melted.mint_url
+            ),
+            Err(err) => println!("\nERROR in send LN {}", err),
+        },
+        AppEvent::MeltToLnMppRes(res) => match res {
+            Ok(melted) => {
+                if melted.is_paid() {
+                    println!(
+                        "Sent LN, amount {} sats, fee {} sats, from {} mints",
+                        melted.amount,
+                        melted.fee,
+                        melted.parts.len()
+                    );
+                }
+                for part in &melted.parts {
+                    match &part.error {
+                        None => println!("  {} sats, fee {} sats, from mint {}", part.amount, part.fee, part.mint_url),
+                        Some(err) => println!(
+                            "  {} sats {} from mint {} (quote {}), {}",
+                            part.amount, part.state, part.mint_url, part.quote_id, err
+                        ),
+                    }
+                }
+                if let Err(err) = melted.check_paid() {
+                    println!("\nERROR in send LN {}", err);
+                }
+            }
+            Err(err) => println!("\nERROR in send LN {}", err),
         },
         AppEvent::ReceivedEC(res) => match res {
             Ok(received) => println!("Received ecash for {} sats", received),
-            Err(err) => println!("\nERROR in receive {}", err.to_string()),
+            Err(PkError::UntrustedMint(mint_url)) => println!(
+                "\nThe token is from an unknown mint, {}\nIf you trust the mint, receive with: rec <ecash_token> --trust",
+                mint_url
+            ),
+            Err(err) => println!("\nERROR in receive {}", err),
+        },
+        AppEvent::TransferQuoteRes(res) => match res {
+            Ok(quote) => println!(
+                "Transferring {} sats from {} to {}, fee reserve {} sats ...",
+                quote.amount, quote.from_mint, quote.to_mint, quote.fee_reserve
+            ),
+            Err(err) => println!("\nERROR preparing transfer, {}", err),
+        },
+        AppEvent::TransferRes(res) => match res {
+            Ok(transfer) if transfer.pending_receive.is_some() => println!(
+                "Transferred {} sats from {} to {}, fee {} sats, the receive is pending, waiting for the mint ...",
+                transfer.amount_sent, transfer.from_mint, transfer.to_mint, transfer.fee_paid
+            ),
+            Ok(transfer) => println!(
+                "Transferred {} sats from {} to {}, fee {} sats, received {} sats",
+                transfer.amount_sent,
+                transfer.from_mint,
+                transfer.to_mint,
+                transfer.fee_paid,
+                transfer.amount_received
+            ),
+            Err(err) => println!("\nERROR in transfer, {}", err),
+        },
+        AppEvent::RestoreProgress(progress) => match &progress.restored {
+            None => println!(
+                "Recovering from mint {}/{} {} ...",
+                progress.mint_index, progress.mint_count, progress.mint_url
+            ),
+            Some(Ok(amount)) => println!("  recovered {} sats", amount),
+            Some(Err(err)) => println!("  ERROR recovering from {}, {}", progress.mint_url, err),
+        },
+        AppEvent::RestoreRes(res) => match res {
+            Ok(summary) => println!("Recovered {} sats in total", summary.total_restored),
+            Err(err) => println!("\nERROR recovering ecash, {}", err),
         },
         AppEvent::SendECRes(res) => match res {
-            Ok((amount, token)) => println!(
-                "Prepared token for sending, amount {} (sats):\n\n{}\n",
-                amount, token,
+            Ok(sent) => println!(
+                "Prepared token for sending, amount {} (sats), from mint {}:\n\n{}\n",
+                sent.amount, sent.mint_url, sent.token,
             ),
-            Err(err) => println!("\nERROR in send {}", err.to_string()),
+            Err(err) => println!("\nERROR in send {}", err),
+        },
+        AppEvent::SendECMultiRes(res) => match res {
+            Ok(sent) => {
+                println!(
+                    "Prepared {} token(s) for sending, amount {} (sats), fee {} (sats):",
+                    sent.tokens.len(),
+                    sent.amount,
+                    sent.fee
+                );
+                for t in &sent.tokens {
+                    println!("\n{} (sats) from mint {}:\n\n{}\n", t.amount, t.mint_url, t.token);
+                }
+            }
+            Err(err) => println!("\nERROR in send {}", err),
+        },
+        AppEvent::History(res) => match res {
+            Ok(transactions) => print_transactions(transactions),
+            Err(err) => println!("\nERROR retrieving history, {}", err),
+        },
+        AppEvent::DecodedInput(res) => match res {
+            Ok(decoded) => print_decoded_input(decoded),
+            Err(err) => println!("\nERROR decoding, {}", err),
+        },
+        AppEvent::PendingTokens(res) => match res {
+            Ok(pending) => {
+                if pending.newly_claimed > 0 {
+                    println!("{} sent token(s) claimed by the recipient", pending.newly_claimed);
+                }
+                println!("Sent tokens not claimed yet:");
+                print_transactions(&pending.tokens);
+            }
+            Err(err) => println!("\nERROR checking sent tokens, {}", err),
+        },
+        AppEvent::WalletHealth(res) => match res {
+            Ok(report) => print_wallet_health(report),
+            Err(err) => println!("\nERROR checking wallet, {}", err),
+        },
+        AppEvent::Proofs(res) => match res {
+            Ok(mints) => print_proofs(mints),
+            Err(err) => println!("\nERROR listing proofs, {}", err),
+        },
+        AppEvent::ConsolidateRes(res) => match res {
+            Ok(result) => print_consolidate_result(result),
+            Err(err) => println!("\nERROR consolidating proofs, {}", err),
+        },
+        AppEvent::ReclaimRes(res) => match res {
+            Ok(reclaimed) => println!("Reclaimed sent token, got ecash for {} sats", reclaimed),
+            Err(err) => println!("\nERROR reclaiming sent token, {}", err),
         },
     }
-    // for nicer console reading
-    print_prompt();
+}
+
+fn print_decoded_input(decoded: &DecodedInput) {
+    match decoded {
+        DecodedInput::Invoice(invoice) => {
+            println!("Lightning invoice");
+            match invoice.amount_msat {
+                Some(msat) if msat % 1000 == 0 => println!("  amount:       {} sats", msat / 1000),
+                Some(msat) => println!("  amount:       {} msats", msat),
+                None => println!("  amount:       (not specified)"),
+            }
+            println!("  description:  {}", invoice.description);
+            println!("  payee:        {}", invoice.payee);
+            println!("  payment hash: {}", invoice.payment_hash);
+            println!("  expiry:       {} UTC", format_unix_time(invoice.expiry));
+            println!("  network:      {}", invoice.network);
+        }
+        DecodedInput::Token(token) => {
+            println!("Ecash token");
+            for mint_url in &token.mint_urls {
+                println!("  mint:         {}", mint_url);
+            }
+            if !token.mint_known {
+                println!("                (unknown mint)");
+            }
+            let unit = token.unit.as_deref().unwrap_or("sat");
+            println!("  amount:       {} {}", token.amount, unit);
+            println!(
+                "  memo:         {}",
+                token.memo.as_deref().unwrap_or_default()
+            );
+            println!("  proofs:       {}", token.proof_count);
+            if token.spending_conditions.is_empty() {
+                println!("  conditions:   none");
+            }
+            for condition in &token.spending_conditions {
+                println!("  conditions:   {}", condition);
+            }
+        }
+    }
+}
+
+fn print_wallet_health(report: &WalletHealthReport) {
+    for (mint_url, res) in &report.mints {
+        match res {
+            Ok(health) => println!(
+                "  {}  {} proofs, fixed {} sats, resolved {} sats, lost {} sats",
+                mint_url,
+                health.proof_count,
+                health.fixed_amount,
+                health.resolved_amount,
+                health.lost_amount
+            ),
+            Err(err) => println!("  {}  ERROR checking, {}", mint_url, err),
+        }
+    }
+    println!(
+        "Wallet check done: fixed {} sats, resolved {} sats, lost {} sats",
+        report.fixed_amount, report.resolved_amount, report.lost_amount
+    );
+}
+
+fn print_proofs(mints: &[MintProofs]) {
+    for mint in mints {
+        println!("Mint {}, {} proofs", mint.mint_url, mint.proofs.len());
+        let denominations: Vec<String> = mint
+            .denominations
+            .iter()
+            .map(|(amount, count)| format!("{}x{}", count, amount))
+            .collect();
+        println!("  unspent denominations: {}", denominations.join(" "));
+        for keyset in &mint.keysets {
+            println!(
+                "  keyset {}  {}  fee {} ppk",
+                keyset.id,
+                if keyset.active {
+                    "active  "
+                } else {
+                    "inactive"
+                },
+                keyset.input_fee_ppk
+            );
+        }
+        for proof in &mint.proofs {
+            println!(
+                "  {:8} sats  keyset {}{}  {:13}  {}  {}",
+                proof.amount,
+                proof.keyset_id,
+                if proof.keyset_active {
+                    ""
+                } else {
+                    " (inactive)"
+                },
+                proof.state,
+                if proof.has_dleq { "dleq" } else { "    " },
+                proof.spending_condition.as_deref().unwrap_or_default(),
+            );
+        }
+    }
+}
+
+/// Parse the arguments of the consolidate command: optional mint URL and `--value <amount>`
+fn parse_consolidate_args<'a>(
+    mut words: impl Iterator<Item = &'a str>,
+) -> Result<(Option<&'a str>, Option<u64>), String> {
+    let mut mint_url = None;
+    let mut target_value = None;
+    while let Some(word) = words.next() {
+        if word == "--value" {
+            let value = words
+                .next()
+                .ok_or("--value requires an amount (in sats)".to_owned())?;
+            target_value = Some(
+                value
+                    .parse::<u64>()
+                    .map_err(|e| format!("Invalid value {}, {}", value, e))?,
+            );
+        } else {
+            mint_url = Some(word);
+        }
+    }
+    Ok((mint_url, target_value))
+}
+
+fn print_consolidate_result(result: &ConsolidateResult) {
+    if result.proofs_before == result.proofs_after && result.fee == 0 {
+        println!(
+            "Proofs of mint {} are already consolidated ({} proofs)",
+            result.mint_url, result.proofs_before
+        );
+    } else {
+        println!(
+            "Consolidated {} sats at mint {}, {} proofs -> {} proofs, fee {} sats",
+            result.amount, result.mint_url, result.proofs_before, result.proofs_after, result.fee
+        );
+    }
+}
+
+fn print_mints(mints: &[MintInfo]) {
+    if mints.is_empty() {
+        println!("No mints used.");
+        return;
+    }
+    println!("Mints used: ({})", mints.len());
+    for (i, mint) in mints.iter().enumerate() {
+        println!("    {}\t{}\t{}", i + 1, mint.url, mint.balance);
+        let keysets: Vec<String> = mint
+            .keysets
+            .iter()
+            .map(|k| {
+                format!(
+                    "{} ({}, fee {} ppk)",
+                    k.id,
+                    if k.active { "active" } else { "inactive" },
+                    k.input_fee_ppk
+                )
+            })
+            .collect();
+        if !keysets.is_empty() {
+            println!("      \tkeysets: {}", keysets.join(", "));
+        }
+        if mint.inactive_balance > 0 {
+            println!("      \t{} sats in inactive keysets", mint.inactive_balance);
+        }
+    }
+}
+
+fn print_inactive_keysets(mints: &[MintInfo]) {
+    let mut any = false;
+    for mint in mints.iter().filter(|m| m.inactive_balance > 0) {
+        println!(
+            "WARNING: {} sats at mint {} are in keysets deactivated by the mint, use 'migrate' to move them",
+            mint.inactive_balance, mint.url
+        );
+        any = true;
+    }
+    if !any {
+        println!("Keysets checked, no funds in inactive keysets");
+    }
+}
+
+fn print_keyset_migration(summary: &KeysetMigrationSummary) {
+    for (mint_url, res) in &summary.mints {
+        match res {
+            Ok(migration) if migration.proof_count == 0 => {}
+            Ok(migration) => println!(
+                "  {}  migrated {} sats in {} proofs, fee {} sats",
+                mint_url, migration.amount, migration.proof_count, migration.fee
+            ),
+            Err(err) => println!("  {}  ERROR migrating, {}", mint_url, err),
+        }
+    }
+    println!(
+        "Keyset migration done: {} sats migrated, fee {} sats",
+        summary.amount, summary.fee
+    );
+}
+
+fn print_transactions(transactions: &[Transaction]) {
+    if transactions.is_empty() {
+        println!("No transactions.");
+        return;
+    }
+    for tx in transactions {
+        println!(
+            "  {:4}  {}  {:3} {:5} {:8} sats  fee {:4}  {:9}  {}  {}",
+            tx.id,
+            tx.time_string(),
+            tx.direction,
+            tx.kind,
+            tx.amount,
+            tx.fee,
+            tx.status,
+            tx.mint_url,
+            tx.memo.as_deref().unwrap_or_default(),
+        );
+    }
+}
+
+/// Check if the event is the final result of the given one-shot command,
+/// and if yes, return whether it succeeded.
+/// For `recln` the final event is the invoice, or with `wait`, the result of the payment.
+fn command_result(command: &str, wait: bool, event: &AppEvent) -> Option<bool> {
+    match (command, event) {
+        ("seed", AppEvent::SeedMnemonic(res)) => Some(res.is_ok()),
+        ("verifyseed", AppEvent::SeedVerifyQuiz(res)) => Some(res.is_ok()),
+        ("verifyseed", AppEvent::SeedVerifyResult(res)) => Some(matches!(res, Ok(true))),
+        ("status", AppEvent::BalanceAndWalletInfo(res)) => Some(res.is_ok()),
+        ("balance", AppEvent::BalanceChange(res)) => Some(res.is_ok()),
+        ("listmints", AppEvent::MintsInfo(res)) => Some(res.is_ok()),
+        ("addmint", AppEvent::MintAdded(res)) => Some(res.is_ok()),
+        ("selectmint", AppEvent::MintSelectedByUrl(res)) => Some(res.is_ok()),
+        ("selectmint", AppEvent::MintSelectedByIndex(res)) => Some(res.is_ok()),
+        ("recln", AppEvent::MintFromLnInvoice(_)) if !wait => Some(true),
+        ("recln", AppEvent::MintFromLnRes(res)) => Some(res.is_ok()),
+        ("claim", AppEvent::ClaimPendingRes(res)) => Some(res.is_ok()),
+        ("sendln", AppEvent::MeltQuoteRes(Err(_))) => Some(false),
+        ("sendln", AppEvent::MeltToLnRes(res)) => Some(res.is_ok()),
+        ("sendln", AppEvent::MeltToLnMppRes(res)) => {
+            Some(res.as_ref().is_ok_and(|melted| melted.is_paid()))
+        }
+        ("rec" | "receive", AppEvent::ReceivedEC(res)) => Some(res.is_ok()),
+        ("send", AppEvent::SendECRes(res)) => Some(res.is_ok()),
+        ("send", AppEvent::SendECMultiRes(res)) => Some(res.is_ok()),
+        ("recover", AppEvent::RestoreRes(res)) => Some(res.is_ok()),
+        ("transfer", AppEvent::TransferQuoteRes(Err(_))) => Some(false),
+        ("transfer", AppEvent::TransferRes(res)) => Some(res.is_ok()),
+        ("history", AppEvent::History(res)) => Some(res.is_ok()),
+        ("decode", AppEvent::DecodedInput(res)) => Some(res.is_ok()),
+        ("pending", AppEvent::PendingTokens(res)) => Some(res.is_ok()),
+        ("reclaim", AppEvent::ReclaimRes(res)) => Some(res.is_ok()),
+        ("health", AppEvent::WalletHealth(res)) => Some(res.is_ok()),
+        ("proofs", AppEvent::Proofs(res)) => Some(res.is_ok()),
+        ("consolidate", AppEvent::ConsolidateRes(res)) => Some(res.is_ok()),
+        ("keysets", AppEvent::KeysetsRefreshed(res)) => Some(res.is_ok()),
+        ("migrate", AppEvent::MigrateKeysetsRes(res)) => Some(res.is_ok()),
+        ("pay", AppEvent::MeltQuoteRes(Err(_))) => Some(false),
+        ("pay", AppEvent::MeltToLnRes(res)) => Some(res.is_ok()),
+        ("pay", AppEvent::ReceivedEC(res)) => Some(res.is_ok()),
+        ("pay", AppEvent::SendECRes(res)) => Some(res.is_ok()),
+        ("pay", AppEvent::MintAdded(res)) => Some(res.is_ok()),
+        _ => None,
+    }
+}
+
+/// Exit code for a failed command, based on the kind of the error
+fn error_exit_code(err: Option<&PkError>) -> u8 {
+    match err {
+        Some(
+            PkError::WalletNotInitialized(_)
+            | PkError::SeedDecryptionFailed(_)
+            | PkError::SeedCheckMissing(_),
+        ) => EXIT_OPEN_FAILED,
+        Some(PkError::InsufficientBalance) => EXIT_INSUFFICIENT_BALANCE,
+        Some(PkError::MintUnreachable(_)) => EXIT_MINT_UNREACHABLE,
+        Some(PkError::TokenAlreadySpent) => EXIT_TOKEN_SPENT,
+        _ => EXIT_FAILED,
+    }
+}
+
+/// Check if the event is an intermediate event of the given one-shot command, worth showing
+fn is_command_progress(command: &str, event: &AppEvent) -> bool {
+    matches!(
+        (command, event),
+        ("recln", AppEvent::MintFromLnInvoice(_))
+            | ("recover", AppEvent::RestoreProgress(_))
+            | ("sendln" | "pay", AppEvent::MeltQuoteRes(Ok(_)))
+            | ("transfer", AppEvent::TransferQuoteRes(Ok(_)))
+    )
+}
+
+/// Open the existing wallet, without prompts if possible: the password is taken from
+/// the `PARAKESH_PASSWORD` environment variable, or asked for if it is not set.
+async fn open_wallet_noninteractive(
+    app: &mut PKAppAsync,
+    event_receiver: &mut mpsc::Receiver<AppEvent>,
+    json: bool,
+) -> bool {
+    if !app.is_wallet_initialized() {
+        let err = PkError::WalletNotInitialized(
+            "no wallet found, start without a command to create one".to_owned(),
+        );
+        output_event(&AppEvent::WalletOpened(Err(err)), json);
+        return false;
+    }
+    let password = match std::env::var(PASSWORD_ENV_VAR) {
+        Ok(password) => password,
+        Err(_) => match rpassword::prompt_password("Enter wallet password: ") {
+            Ok(password) => password,
+            Err(err) => {
+                let err = PkError::InvalidInput(format!("Could not read password, {}", err));
+                output_event(&AppEvent::WalletOpened(Err(err)), json);
+                return false;
+            }
+        },
+    };
+    let _res = app.open_wallet(password);
+    while let Some(event) = event_receiver.next().await {
+        if let AppEvent::WalletOpened(res) = &event {
+            if res.is_err() {
+                output_event(&event, json);
+            }
+            return res.is_ok();
+        }
+    }
+    false
+}
+
+/// Execute a single command given on the command line, wait for its result, and exit.
+/// An optional `--mint <mint_url>` selects the mint to use.
+async fn run_oneshot_command(config: PKAppConfig, args: &[String], json: bool) -> ExitCode {
+    let mut mint_url: Option<String> = None;
+    let mut words: Vec<&str> = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if let Some(url) = arg.strip_prefix("--mint=") {
+            mint_url = Some(url.to_owned());
+        } else if arg == "--mint" {
+            match iter.next() {
+                Some(url) => mint_url = Some(url.clone()),
+                None => {
+                    textln!("ERROR: Missing value for option --mint");
+                    return ExitCode::from(EXIT_INVALID_ARGS);
+                }
+            }
+        } else {
+            words.push(arg.as_str());
+        }
+    }
+    // `--wait` and `--yes` are not arguments of the command itself
+    let wait = words.contains(&"--wait");
+    let yes = words.contains(&"--yes");
+    words.retain(|w| *w != "--wait" && *w != "--yes");
+    let command = words[0];
+    match command {
+        "help" => {
+            cmd_help();
+            return ExitCode::SUCCESS;
+        }
+        "open" | "init" | "restore" => {
+            textln!(
+                "ERROR: {} is only available in the interactive mode",
+                command
+            );
+            return ExitCode::from(EXIT_INVALID_ARGS);
+        }
+        _ => {}
+    }
+
+    let mut app = match PKAppAsync::new(config) {
+        Ok(app) => app,
+        Err(err) => {
+            textln!("ERROR: {}", err);
+            return ExitCode::from(EXIT_FAILED);
+        }
+    };
+    let (event_sender, mut event_receiver) = mpsc::channel::<AppEvent>(100);
+    if let Err(err) = app.init_with_sender(event_sender) {
+        textln!("ERROR: {}", err);
+        return ExitCode::from(EXIT_FAILED);
+    }
+    let exit_code = execute_oneshot_command(
+        &mut app,
+        &mut event_receiver,
+        mint_url,
+        &words,
+        wait,
+        yes,
+        json,
+    )
+    .await;
+    // Keep consuming further events (e.g. info updates) till exit, to avoid send errors
+    tokio::task::spawn(async move { while event_receiver.next().await.is_some() {} });
+    exit_code
+}
+
+async fn execute_oneshot_command(
+    app: &mut PKAppAsync,
+    event_receiver: &mut mpsc::Receiver<AppEvent>,
+    mint_url: Option<String>,
+    words: &[&str],
+    wait: bool,
+    yes: bool,
+    json: bool,
+) -> ExitCode {
+    if !open_wallet_noninteractive(app, event_receiver, json).await {
+        return ExitCode::from(EXIT_OPEN_FAILED);
+    }
+
+    if let Some(mint_url) = mint_url {
+        let _res = app.select_mint(mint_url);
+        while let Some(event) = event_receiver.next().await {
+            if let AppEvent::MintSelectedByUrl(res) = &event {
+                if res.is_err() {
+                    output_event(&event, json);
+                    return ExitCode::from(error_exit_code(event.error()));
+                }
+                break;
+            }
+        }
+    }
+
+    if !process_command(app, words) {
+        return ExitCode::from(EXIT_INVALID_ARGS);
+    }
+    let command = words[0];
+    while let Some(event) = event_receiver.next().await {
+        if let Some(is_ok) = command_result(command, wait, &event) {
+            output_event(&event, json);
+            return if is_ok {
+                ExitCode::SUCCESS
+            } else {
+                ExitCode::from(error_exit_code(event.error()))
+            };
+        }
+        if is_command_progress(command, &event) {
+            output_event(&event, json);
+        }
+        if let AppEvent::MeltQuoteRes(Ok(quote)) = &event {
+            // paid with `--yes`, or after confirmation
+            let confirmed = yes || read_line("Proceed? (y/n): ").as_deref() == Some("y");
+            cmd_sendln_confirm(app, quote.quote_id.clone(), confirmed);
+            if !confirmed {
+                return ExitCode::from(EXIT_FAILED);
+            }
+        }
+    }
+    ExitCode::from(EXIT_FAILED)
+}
+
+fn print_usage(program: &str) {
+    textln!(
+        "Usage: {} [options] [--json] [<command> [<args>] [--mint <mint_url>]]",
+        program
+    );
+    textln!(
+        "Without a command the interactive console is started. With a command, it is executed and"
+    );
+    textln!(
+        "the program exits, with exit code 0 on success, {} if the command failed, {} for invalid",
+        EXIT_FAILED,
+        EXIT_INVALID_ARGS
+    );
+    textln!(
+        "arguments, {} if the wallet could not be opened, {} for insufficient balance, {} if the mint",
+        EXIT_OPEN_FAILED, EXIT_INSUFFICIENT_BALANCE, EXIT_MINT_UNREACHABLE
+    );
+    textln!(
+        "could not be reached, {} if the token was already spent. The wallet password is taken from the",
+        EXIT_TOKEN_SPENT
+    );
+    textln!(
+        "{} environment variable, if set. See 'help' for the list of commands.",
+        PASSWORD_ENV_VAR
+    );
+    textln!("With --json the results are printed as JSON objects, one per line.");
+    textln!("{}", PKAppConfig::usage());
+}
+
+/// Process the command line arguments into a config, the JSON output flag,
+/// and the optional command with its arguments.
+/// Returns the exit code if the program should exit (help was requested, or invalid arguments).
+fn parse_args() -> Result<(PKAppConfig, bool, Vec<String>), ExitCode> {
+    let args: Vec<String> = std::env::args().collect();
+    // set first, for the output of the argument errors
+    let json = args[1..].iter().any(|arg| arg == "--json");
+    JSON_OUTPUT.store(json, Ordering::Relaxed);
+    let (config, mut rest) = match PKAppConfig::from_args(&args[1..]) {
+        Ok(res) => res,
+        Err(err) => {
+            textln!("ERROR: {}", err);
+            print_usage(&args[0]);
+            return Err(ExitCode::from(EXIT_INVALID_ARGS));
+        }
+    };
+    rest.retain(|arg| arg != "--json");
+    if let Some(arg) = rest.first() {
+        if arg == "--help" || arg == "-h" {
+            print_usage(&args[0]);
+            return Err(ExitCode::SUCCESS);
+        }
+        if arg.starts_with('-') {
+            textln!("ERROR: Unknown option {}", arg);
+            print_usage(&args[0]);
+            return Err(ExitCode::from(EXIT_INVALID_ARGS));
+        }
+    }
+    Ok((config, json, rest))
 }
 
 #[tokio::main]
-async fn main() {
-    println!("Parakesh: GM!");
-    // TODO args, etc.
+async fn main() -> ExitCode {
+    let (config, json, command) = match parse_args() {
+        Ok(res) => res,
+        Err(exit_code) => return exit_code,
+    };
+    if !command.is_empty() {
+        return run_oneshot_command(config, &command, json).await;
+    }
+
+    textln!("Parakesh: GM!");
+    textln!("Using data dir {}", config.data_dir.display());
+
+    let mut app = PKAppAsync::new(config).unwrap();
+    let (event_sender, mut event_receiver) = mpsc::channel::<AppEvent>(100);
+    app.init_with_sender(event_sender).unwrap();
+    // the events are handled with a handle to the app, for follow-up requests
+    let mut event_app = app.clone();
+    tokio::task::spawn(async move {
+        while let Some(event) = event_receiver.next().await {
+            handle_event(&mut event_app, event, json);
+        }
+    });
 
-    let mut app = PKAppAsync::new_with_callback(handle_event).unwrap();
+    open_or_init_wallet(&mut app);
 
     // handle interactive commands
     poll_for_user_input(&mut app);
 
-    println!("Parakesh: ciao!");
+    textln!("Parakesh: ciao!");
+    ExitCode::SUCCESS
 }